@@ -0,0 +1,176 @@
+// Compares how many frames keyed operations take on a plain ring and on a partitioned one.
+// Run with `cargo bench --features harness --bench hops`.
+use std::{io, process, sync, thread, time};
+
+use linda::{
+    acl::{Access, Policy},
+    balance::Balance,
+    harness::address,
+    parser::ParseError,
+    partition::{Partition, Partitioned, Peer},
+    remote::RemoteSpace,
+    standalone,
+    stats::ServerMetrics,
+    transport::{Address, Listener, Stream, Transport},
+    tuple::*,
+    Linda, TupleSpace,
+};
+
+const SIZES: [usize; 3] = [3, 5, 8];
+const OPERATIONS: usize = 200;
+const WAIT_LIMIT: time::Duration = time::Duration::from_secs(5);
+// How long the census of every node is given to return before the operations start.
+const SETTLE: time::Duration = time::Duration::from_millis(200);
+
+type Node = sync::Arc<Linda<Stream>>;
+
+struct Run {
+    frames: u64,
+    elapsed: time::Duration,
+}
+
+// Both ends of a loopback connection.
+fn link() -> io::Result<(Stream, Stream)> {
+    let listener = Listener::bind(&Transport::Tcp.local_address())?;
+    let output = Stream::connect(&listener.local_address()?)?;
+
+    Ok((output, listener.accept()?))
+}
+
+// Node `i` writes to node `i + 1` over a loopback connection.
+fn ring(size: usize, balance: impl Fn(usize) -> Balance) -> io::Result<Vec<Node>> {
+    let mut links = (0..size).map(|_| link()).collect::<io::Result<Vec<_>>>()?;
+    links.rotate_right(1);
+    let mut nodes = vec![];
+    for (node, (_, input)) in links.iter().enumerate() {
+        let output = links[(node + 1) % size].0.try_clone()?;
+        let access = Access::new(address(node).to_string(), Policy::default());
+        nodes.push(sync::Arc::new(Linda::with_balance(
+            input.try_clone()?,
+            output,
+            address(node),
+            access,
+            balance(node),
+        )));
+    }
+
+    Ok(nodes)
+}
+
+fn ring_frames(nodes: &[Node]) -> u64 {
+    nodes
+        .iter()
+        .filter_map(|node| node.stats())
+        .map(|stats| stats.received)
+        .sum()
+}
+
+// Every tuple is produced at one node and taken by the next one.
+fn workload(spaces: &[&dyn TupleSpace]) -> Result<time::Duration, String> {
+    let start = time::Instant::now();
+    for i in 0..OPERATIONS {
+        let tuple = Tuple::from_vec(vec![
+            Value::int(i as i32),
+            Value::string(format!("job {i}")),
+        ]);
+        spaces[i % spaces.len()]
+            .out(tuple)
+            .map_err(|e| e.to_string())?;
+        let template = format!("(int: {i}, string: *)")
+            .parse()
+            .map_err(|e: ParseError| e.to_string())?;
+        spaces[(i + 1) % spaces.len()]
+            .input(template, WAIT_LIMIT)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(start.elapsed())
+}
+
+fn plain(size: usize) -> Result<Run, String> {
+    let nodes = ring(size, |_| Balance::default()).map_err(|e| e.to_string())?;
+    let spaces: Vec<&dyn TupleSpace> = nodes.iter().map(|node| &**node as _).collect();
+    let elapsed = workload(&spaces)?;
+
+    Ok(Run {
+        frames: ring_frames(&nodes),
+        elapsed,
+    })
+}
+
+fn partitioned(size: usize) -> Result<Run, String> {
+    let listeners = (0..size)
+        .map(|_| Listener::bind(&Transport::Tcp.partition_address()))
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    let endpoints = listeners
+        .iter()
+        .map(Listener::local_address)
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    let nodes = ring(size, |node| Balance {
+        partition: Some(Partition {
+            key: 0,
+            endpoint: endpoints[node].clone(),
+        }),
+        ..Balance::default()
+    })
+    .map_err(|e| e.to_string())?;
+
+    let mut servers = vec![];
+    for (node, listener) in nodes.iter().zip(listeners) {
+        let (owned, metrics) = (node.partition(), sync::Arc::new(ServerMetrics::default()));
+        let serving = metrics.clone();
+        thread::spawn(move || {
+            standalone::serve(listener, sync::Arc::new(owned), None, None, serving)
+        });
+        servers.push(metrics);
+    }
+    let dial = |endpoint: &Address| -> io::Result<Peer> {
+        let stream = Stream::connect(endpoint)?;
+        let input = stream.try_clone()?;
+        let address = stream.client_address()?;
+        Ok(sync::Arc::new(RemoteSpace::new(input, stream, address)))
+    };
+    let spaces: Vec<_> = nodes
+        .iter()
+        .map(|node| Partitioned::new(node.clone(), 0, dial))
+        .collect();
+
+    for node in &nodes {
+        node.census().map_err(|e| e.to_string())?;
+    }
+    thread::sleep(SETTLE);
+    let census = ring_frames(&nodes);
+
+    let elapsed = workload(&spaces.iter().map(|space| space as _).collect::<Vec<_>>())?;
+    // Every request to an owner is answered, so it takes two frames.
+    let routed: u64 = servers
+        .iter()
+        .map(|metrics| 2 * metrics.snapshot(None).received)
+        .sum();
+
+    Ok(Run {
+        frames: ring_frames(&nodes) - census + routed,
+        elapsed,
+    })
+}
+
+fn main() {
+    println!("nodes  mode         frames/op  time/op");
+    for size in SIZES {
+        for (mode, run) in [("ring", plain(size)), ("partitioned", partitioned(size))] {
+            match run {
+                Ok(run) => println!(
+                    "{size:>5}  {mode:<11}  {:>9.2}  {:?}",
+                    run.frames as f64 / (2 * OPERATIONS) as f64,
+                    run.elapsed / (2 * OPERATIONS) as u32,
+                ),
+                Err(e) => {
+                    eprintln!("{size} nodes, {mode}: {e}");
+                    process::exit(1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index};
+
+/// Derives `linda::typed::IntoTuple`, mapping each field to the tuple position
+/// matching its declaration order.
+#[proc_macro_derive(IntoTuple)]
+pub fn derive_into_tuple(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match struct_fields(&input) {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let values = fields.iter().map(|field| {
+        let access = &field.access;
+        quote! { ::linda::typed::Field::to_value(&self.#access) }
+    });
+
+    quote! {
+        impl #impl_generics ::linda::typed::IntoTuple for #name #ty_generics #where_clause {
+            fn to_tuple(&self) -> ::linda::tuple::Tuple<::linda::tuple::Value> {
+                ::linda::tuple::Tuple::from_vec(vec![#(#values),*])
+            }
+        }
+    }
+    .into()
+}
+
+/// Derives `linda::typed::FromTuple`, reading each field from the tuple position
+/// matching its declaration order.
+#[proc_macro_derive(FromTuple)]
+pub fn derive_from_tuple(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match struct_fields(&input) {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let count = fields.len();
+    let reads = fields.iter().map(|field| {
+        let binding = &field.binding;
+        quote! { let #binding = ::linda::typed::Field::from_value(values.next()?)?; }
+    });
+    let bindings = fields.iter().map(|field| &field.binding);
+    let construct = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(_) => quote! { #name { #(#bindings),* } },
+            Fields::Unnamed(_) => quote! { #name ( #(#bindings),* ) },
+            Fields::Unit => quote! { #name },
+        },
+        _ => unreachable!(),
+    };
+
+    quote! {
+        impl #impl_generics ::linda::typed::FromTuple for #name #ty_generics #where_clause {
+            fn from_tuple(tuple: ::linda::tuple::Tuple<::linda::tuple::Value>) -> Option<Self> {
+                if tuple.len() != #count {
+                    return None;
+                }
+
+                let mut values = tuple.into_iter();
+                #(#reads)*
+                Some(#construct)
+            }
+        }
+    }
+    .into()
+}
+
+/// Derives `linda::typed::Template` along with a `<Struct>Template` builder that
+/// has one method per field for constraining that tuple position.
+#[proc_macro_derive(Template)]
+pub fn derive_template(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match struct_fields(&input) {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    if !input.generics.params.is_empty() {
+        return syn::Error::new_spanned(
+            &input.generics,
+            "Template cannot be derived for generic structs",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let builder = format_ident!("{}Template", name);
+    let doc = format!("Builder for templates matching tuples created from [`{name}`].");
+    let empties = fields.iter().map(|field| {
+        let ty = &field.ty;
+        quote! {
+            ::linda::tuple::Request::new(
+                <#ty as ::linda::typed::Field>::empty(),
+                ::linda::tuple::ComparisonOperator::ANY,
+            )
+        }
+    });
+    let methods = fields.iter().enumerate().map(|(index, field)| {
+        let ty = &field.ty;
+        let method = &field.binding;
+        let doc = format!("Constrains tuple position {index} with `op` and `value`.");
+        quote! {
+            #[doc = #doc]
+            pub fn #method(mut self, op: ::linda::tuple::ComparisonOperator, value: #ty) -> Self {
+                self.0[#index] = ::linda::tuple::Request::new(::linda::typed::Field::to_value(&value), op);
+                self
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, Debug, PartialEq)]
+        #vis struct #builder(::linda::tuple::Tuple<::linda::tuple::Request>);
+
+        impl #builder {
+            #(#methods)*
+        }
+
+        impl From<#builder> for ::linda::tuple::Tuple<::linda::tuple::Request> {
+            fn from(builder: #builder) -> Self {
+                builder.0
+            }
+        }
+
+        impl ::linda::typed::Template for #name {
+            type Builder = #builder;
+
+            fn template() -> #builder {
+                #builder(::linda::tuple::Tuple::from_vec(vec![#(#empties),*]))
+            }
+        }
+    }
+    .into()
+}
+
+struct StructField {
+    access: proc_macro2::TokenStream,
+    binding: syn::Ident,
+    ty: syn::Type,
+}
+
+fn struct_fields(input: &DeriveInput) -> Result<Vec<StructField>, syn::Error> {
+    let data = match &input.data {
+        Data::Struct(data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "tuple conversions can only be derived for structs",
+            ))
+        }
+    };
+
+    let fields = match &data.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let ident = field.ident.clone().unwrap();
+                StructField {
+                    access: quote! { #ident },
+                    binding: ident,
+                    ty: field.ty.clone(),
+                }
+            })
+            .collect(),
+        Fields::Unnamed(fields) => fields
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let index = Index::from(i);
+                StructField {
+                    access: quote! { #index },
+                    binding: syn::Ident::new(&format!("field_{i}"), Span::call_site()),
+                    ty: field.ty.clone(),
+                }
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    Ok(fields)
+}
@@ -0,0 +1,259 @@
+use std::{fmt, fs, path, str};
+
+use crate::{
+    satisfies,
+    tuple::{Request, Serializable, Tuple, Value},
+    utils::read_le_u8,
+};
+
+const INVALID_RIGHT: &str = "Right needs to be one of 'out', 'in' or 'rd'!";
+const INCOMPLETE_RULE: &str = "Expected IDENTITY RIGHTS TEMPLATE!";
+const ANYONE: &str = "*";
+// Whoever connects without a certificate. It is not a DNS name, so no certificate can claim
+// it, but rules can still name it.
+pub const ANONYMOUS: &str = "(anonymous)";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Right {
+    Out,
+    In,
+    Rd,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    identity: String,
+    rights: Vec<Right>,
+    template: Tuple<Request>,
+}
+
+// Rules grant rights on the tuples matching their template. Tuples that no rule mentions
+// are open to every node; once a rule covers a tuple, only the rights granted by the rules
+// covering it remain.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+// The identity a node is authenticated as together with the policy it enforces on the
+// tuples it holds. Requests are judged by the identity the first node they reach
+// authenticated their sender as, its `predecessor`, which replaces whatever identity the
+// request arrived with. The nodes after it trust that stamp.
+#[derive(Clone, Debug)]
+pub struct Access {
+    pub identity: String,
+    pub predecessor: String,
+    pub policy: Policy,
+}
+
+impl str::FromStr for Right {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "out" => Ok(Right::Out),
+            "in" => Ok(Right::In),
+            "rd" => Ok(Right::Rd),
+            _ => Err(INVALID_RIGHT),
+        }
+    }
+}
+
+impl fmt::Display for Right {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Right::Out => write!(f, "out"),
+            Right::In => write!(f, "in"),
+            Right::Rd => write!(f, "rd"),
+        }
+    }
+}
+
+impl Serializable for Right {
+    fn to_bytes(&self) -> Vec<u8> {
+        let value = match self {
+            Right::Out => 0u8,
+            Right::In => 1,
+            Right::Rd => 2,
+        };
+
+        value.to_le_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &mut &[u8]) -> Option<Right> {
+        match read_le_u8(bytes)? {
+            0 => Some(Right::Out),
+            1 => Some(Right::In),
+            2 => Some(Right::Rd),
+            _ => None,
+        }
+    }
+}
+
+// One rule per line: IDENTITY RIGHTS TEMPLATE, where IDENTITY may be '*' for every node
+// and RIGHTS is a comma separated list, e.g.
+//   scheduler in,rd (string: "job", int: *)
+impl str::FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Vec::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = strip_comment(line);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let rule = Rule::parse(line).map_err(|e| format!("line {}: {e}", number + 1))?;
+            rules.push(rule);
+        }
+
+        Ok(Policy { rules })
+    }
+}
+
+// A '#' inside a string literal is part of the template rather than the start of a comment.
+fn strip_comment(line: &str) -> &str {
+    let (mut quoted, mut escaped) = (false, false);
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+
+    line
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Rule, String> {
+        let field = |s: &str| match s.trim_start().split_once(char::is_whitespace) {
+            Some((field, rest)) => Ok((field.to_owned(), rest.to_owned())),
+            None => Err(String::from(INCOMPLETE_RULE)),
+        };
+        let (identity, rest) = field(line)?;
+        let (rights, template) = field(&rest)?;
+
+        let rights = rights
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<Right>, _>>()?;
+        let template = template.trim().parse().map_err(|e| format!("{e}"))?;
+
+        Ok(Rule {
+            identity,
+            rights,
+            template,
+        })
+    }
+}
+
+impl Policy {
+    pub fn load(path: &path::Path) -> Result<Policy, String> {
+        match fs::read_to_string(path) {
+            Ok(policy) => policy.parse(),
+            Err(e) => Err(format!("Failed to read policy {}! {e}", path.display())),
+        }
+    }
+
+    pub fn allows(&self, identity: &str, right: Right, tuple: &Tuple<Value>) -> bool {
+        let mut covered = false;
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| satisfies(&rule.template, tuple))
+        {
+            if (rule.identity == ANYONE || rule.identity == identity)
+                && rule.rights.contains(&right)
+            {
+                return true;
+            }
+            covered = true;
+        }
+
+        !covered
+    }
+}
+
+impl Access {
+    pub fn new(identity: String, policy: Policy) -> Access {
+        Access {
+            identity,
+            predecessor: String::from(ANONYMOUS),
+            policy,
+        }
+    }
+
+    pub fn allows(&self, right: Right, tuple: &Tuple<Value>) -> bool {
+        self.policy.allows(&self.identity, right, tuple)
+    }
+}
+
+impl Default for Access {
+    fn default() -> Access {
+        Access::new(String::from(ANONYMOUS), Policy::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = "\
+# Only schedulers take jobs, everyone may submit and inspect them.
+scheduler in (string: \"job\", int: *)
+*         out,rd (string: \"job\", int: *)
+";
+
+    fn job(id: i32) -> Tuple<Value> {
+        Tuple::from_vec(vec![Value::string(String::from("job")), Value::int(id)])
+    }
+
+    #[test]
+    fn parse_policy() {
+        let policy: Policy = POLICY.parse().unwrap();
+        assert_eq!(policy.rules.len(), 2);
+        assert_eq!(policy.rules[1].rights, vec![Right::Out, Right::Rd]);
+        assert_eq!(Policy::default(), "\n# nothing\n".parse().unwrap());
+
+        assert_eq!(
+            "scheduler take (int: *)".parse::<Policy>(),
+            Err(format!("line 1: {INVALID_RIGHT}"))
+        );
+        assert!("\nscheduler in"
+            .parse::<Policy>()
+            .unwrap_err()
+            .starts_with("line 2:"));
+        assert!("* in (int: ?)".parse::<Policy>().is_err());
+    }
+
+    #[test]
+    fn comments_outside_strings() {
+        let policy: Policy = "* rd (string: \"#tag\", string: \"say \\\"#\\\"\") # tagged"
+            .parse()
+            .unwrap();
+        let tagged = Tuple::from_vec(vec![
+            Value::string(String::from("#tag")),
+            Value::string(String::from("say \"#\"")),
+        ]);
+        assert!(policy.allows("worker", Right::Rd, &tagged));
+        assert!(!policy.allows("worker", Right::In, &tagged));
+    }
+
+    #[test]
+    fn rules_cover_matching_tuples() {
+        let policy: Policy = POLICY.parse().unwrap();
+
+        assert!(policy.allows("scheduler", Right::In, &job(1)));
+        assert!(!policy.allows("worker", Right::In, &job(1)));
+        assert!(policy.allows("worker", Right::Rd, &job(1)));
+        assert!(policy.allows("worker", Right::Out, &job(1)));
+
+        let other = Tuple::from_vec(vec![Value::string(String::from("result")), Value::int(1)]);
+        assert!(policy.allows("worker", Right::In, &other));
+        assert!(Policy::default().allows("", Right::In, &job(1)));
+    }
+}
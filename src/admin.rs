@@ -0,0 +1,693 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, Write},
+    marker, str, sync, thread, time,
+};
+
+use crate::{
+    message::{Admin, LindaError, Message, MessageType, Reply},
+    remote::{unexpected, RemoteSpace},
+    send,
+    stats::ServerMetrics,
+    trace_message,
+    transport::{Address, Listener},
+    tuple::*,
+    ArcMutex, Linda,
+};
+
+// Evicting waits for the notice of the node leaving to go around the ring.
+const PATIENCE: time::Duration = time::Duration::from_secs(2);
+const EVICT_PATIENCE: time::Duration = time::Duration::from_secs(10);
+
+const UNKNOWN_COMMAND: &str = "Unknown command!";
+const MISSING_ARGUMENT: &str = "Command needs an argument!";
+const USAGE: &str = "\
+members           list the nodes in ring order with the number of tuples they hold
+health            check that every node answers and how quickly
+evict ADDRESS     make a node hand off its tuples and leave, then close the ring behind it
+purge TEMPLATE    remove the tuples matching TEMPLATE from every node
+help              show this list";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Control {
+    Members,
+    Health,
+    Evict(Address),
+    Purge(Tuple<Request>),
+    Help,
+}
+
+// What each node answered, in ring order.
+type Answers<T> = Result<Vec<(Address, Result<T, LindaError>)>, LindaError>;
+
+struct Member<Output> {
+    address: Address,
+    control: RemoteSpace<Output>,
+}
+
+// The nodes in ring order, each reachable over the connection the server formed the ring
+// with.
+pub struct Ring<Output> {
+    members: sync::Mutex<Vec<Member<Output>>>,
+    emptied: sync::Condvar,
+    metrics: sync::Arc<ServerMetrics>,
+}
+
+// One line per command; an argument is the rest of the line.
+impl str::FromStr for Control {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (command, argument) = match s.trim().split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, Some(argument.trim())),
+            None => (s.trim(), None),
+        };
+        match (&command.to_lowercase()[..], argument) {
+            ("members", None) => Ok(Control::Members),
+            ("health", None) => Ok(Control::Health),
+            ("help", None) => Ok(Control::Help),
+            ("evict", Some(address)) => match address.parse() {
+                Ok(address) => Ok(Control::Evict(address)),
+                Err(e) => Err(format!("Incorrect node address! {e}")),
+            },
+            ("purge", Some(template)) => match template.parse() {
+                Ok(template) => Ok(Control::Purge(template)),
+                Err(e) => Err(format!("{e}")),
+            },
+            ("evict" | "purge", None) => Err(String::from(MISSING_ARGUMENT)),
+            _ => Err(String::from(UNKNOWN_COMMAND)),
+        }
+    }
+}
+
+// How a node's part in the ring ended.
+#[derive(Debug, PartialEq)]
+pub enum Departure {
+    // The node withdrew and its predecessor was relinked past it.
+    Evicted,
+    // The node was the last one, so there is nobody to hand its tuples to.
+    Alone,
+}
+
+// Answers the server's requests on the connection this node registered with. Returns once
+// the node has left the ring and fails when the server goes away; `connect` opens the link
+// to a new successor.
+pub fn serve_node<Input, Control, Output>(
+    mut input: Input,
+    control: &ArcMutex<Control>,
+    space: &Linda<Output>,
+    connect: impl Fn(&Address) -> io::Result<Output>,
+) -> Result<Departure, LindaError>
+where
+    Input: io::Read,
+    Control: io::Write,
+    Output: 'static + io::Write + marker::Send,
+{
+    loop {
+        let msg = Message::recv(&mut input)?;
+        trace_message("Received", &msg);
+        let (id, admin) = match msg.tuple {
+            MessageType::Admin(id, admin) => (id, admin),
+            // The only request this node makes is to leave.
+            MessageType::Reply(_, Reply::Done) => return Ok(Departure::Alone),
+            MessageType::Reply(_, Reply::Failed(message)) => {
+                return Err(LindaError::ServerFailure(message))
+            }
+            _ => {
+                tracing::warn!(kind = msg.tuple.name(), "Unexpected message - skipping");
+                continue;
+            }
+        };
+
+        let evicting = admin == Admin::Evict;
+        let reply = match answer(admin, space, &connect) {
+            Ok(reply) => reply,
+            Err(e) => Reply::Failed(e.describe()),
+        };
+        let evicted = evicting && reply == Reply::Done;
+        send(control, Message::reply(id, reply, space.node.clone()))?;
+        if evicted {
+            return Ok(Departure::Evicted);
+        }
+    }
+}
+
+// Asks the server to take this node out of the ring. It evicts the node like the operator
+// would, so `serve_node` returns once the tuples are handed off, and then relinks the
+// predecessor past the node. The last node is let go without handing anything off. If the
+// server refuses, `serve_node` fails with its reason.
+pub fn depart<Control: io::Write>(
+    control: &ArcMutex<Control>,
+    node: Address,
+) -> Result<(), LindaError> {
+    let msg = Message {
+        tuple: MessageType::Admin(0, Admin::Leave),
+        node,
+    };
+    send(control, msg)
+}
+
+fn answer<Output: 'static + io::Write + marker::Send>(
+    admin: Admin,
+    space: &Linda<Output>,
+    connect: impl Fn(&Address) -> io::Result<Output>,
+) -> Result<Reply, LindaError> {
+    match admin {
+        Admin::Ping => Ok(Reply::Done),
+        Admin::Count => match space.local_tuples.lock() {
+            Ok(guard) => Ok(Reply::Count(guard.len())),
+            Err(e) => Err(LindaError::MutexLockFailure(e.to_string())),
+        },
+        Admin::Purge(template) => space.purge(&template).map(Reply::Count),
+        Admin::Evict => space.withdraw().map(|_| Reply::Done),
+        Admin::Relink(next) => {
+            tracing::info!(next = %next, "Relinking to skip an evicted node");
+            let mut output = connect(&next).map_err(LindaError::IoFailure)?;
+            Message::hello(space.node.clone()).send(&mut output)?;
+            space.relink_output(output)?;
+            Ok(Reply::Done)
+        }
+        Admin::Leave => Err(LindaError::Unsupported(
+            "leaving the ring on the server's behalf",
+        )),
+    }
+}
+
+impl<Output: 'static + io::Write + marker::Send> Ring<Output> {
+    // `members` are the nodes in ring order with their control connections; `server` is
+    // the address the requests are sent from. Members that announce they are leaving are
+    // evicted in the background.
+    pub fn new<Input: 'static + io::Read + marker::Send>(
+        server: Address,
+        members: Vec<(Address, Input, Output)>,
+        metrics: sync::Arc<ServerMetrics>,
+    ) -> sync::Arc<Ring<Output>> {
+        let (departures, departing) = sync::mpsc::channel();
+        let members = members
+            .into_iter()
+            .map(|(address, input, output)| {
+                let (departures, member) = (departures.clone(), address.clone());
+                // The connection tells who is leaving, whatever address the request names.
+                let notices = move |msg: Message| match msg.tuple {
+                    MessageType::Admin(id, Admin::Leave) => {
+                        let _ = departures.send((member.clone(), id));
+                    }
+                    _ => tracing::warn!(kind = msg.tuple.name(), "Unexpected message - skipping"),
+                };
+                Member {
+                    address,
+                    control: RemoteSpace::with_notices(input, output, server.clone(), notices),
+                }
+            })
+            .collect();
+
+        let ring = sync::Arc::new(Ring {
+            members: sync::Mutex::new(members),
+            emptied: sync::Condvar::new(),
+            metrics,
+        });
+        let leaving = sync::Arc::downgrade(&ring);
+        thread::spawn(move || {
+            for (address, id) in departing {
+                let ring = match leaving.upgrade() {
+                    Some(ring) => ring,
+                    None => break,
+                };
+                match ring.release(&address, id) {
+                    Ok(()) => tracing::info!(node = %address, "Node left the ring"),
+                    Err(e) => {
+                        tracing::warn!(node = %address, error = e.describe(), "Refused to let node leave");
+                        ring.refuse(&address, id, e);
+                    }
+                }
+            }
+        });
+
+        ring
+    }
+
+    fn lock(&self) -> Result<sync::MutexGuard<'_, Vec<Member<Output>>>, LindaError> {
+        self.members
+            .lock()
+            .map_err(|e| LindaError::MutexLockFailure(e.to_string()))
+    }
+
+    // The tuples each node holds.
+    pub fn members(&self) -> Answers<usize> {
+        let members = self.lock()?;
+        Ok(members
+            .iter()
+            .map(|member| {
+                let count = request(member, Admin::Count, PATIENCE).and_then(expect_count);
+                (member.address.clone(), count)
+            })
+            .collect())
+    }
+
+    // How long each node takes to answer.
+    pub fn health(&self) -> Answers<time::Duration> {
+        let members = self.lock()?;
+        Ok(members
+            .iter()
+            .map(|member| {
+                let start = time::Instant::now();
+                let answered = request(member, Admin::Ping, PATIENCE).and_then(expect_done);
+                (member.address.clone(), answered.map(|_| start.elapsed()))
+            })
+            .collect())
+    }
+
+    // The tuples each node removed.
+    pub fn purge(&self, template: &Tuple<Request>) -> Answers<usize> {
+        let members = self.lock()?;
+        Ok(members
+            .iter()
+            .map(|member| {
+                let purge = Admin::Purge(template.clone());
+                let count = request(member, purge, PATIENCE).and_then(expect_count);
+                (member.address.clone(), count)
+            })
+            .collect())
+    }
+
+    // The node hands its tuples to its successor and keeps relaying until its predecessor
+    // sends to the successor directly.
+    pub fn evict(&self, address: &Address) -> Result<(), LindaError> {
+        let mut members = self.lock()?;
+        let index = match members.iter().position(|member| member.address == *address) {
+            Some(index) => index,
+            None => return Err(LindaError::NotMember(address.clone())),
+        };
+        if members.len() == 1 {
+            return Err(LindaError::Unsupported(
+                "evicting the last node of the ring",
+            ));
+        }
+
+        let count = members.len();
+        request(&members[index], Admin::Evict, EVICT_PATIENCE).and_then(expect_done)?;
+        let evicted = members.remove(index);
+        self.metrics.left();
+        let previous = &members[(index + count - 2) % (count - 1)];
+        let next = members[index % (count - 1)].address.clone();
+        tracing::info!(evicted = %evicted.address, previous = %previous.address, next = %next, "Closing the ring");
+        request(previous, Admin::Relink(next), PATIENCE).and_then(expect_done)
+    }
+
+    // Lets a node leave at its own request. The last node takes its tuples with it.
+    fn release(&self, address: &Address, id: u64) -> Result<(), LindaError> {
+        let mut members = self.lock()?;
+        if members.len() > 1 {
+            drop(members);
+            return self.evict(address);
+        }
+        match members.iter().position(|member| member.address == *address) {
+            Some(index) => {
+                let last = members.remove(index);
+                self.metrics.left();
+                let reply = MessageType::Reply(id, Reply::Done);
+                if let Err(e) = last.control.notify(reply) {
+                    tracing::warn!(node = %address, error = e.describe(), "Failed to answer node");
+                }
+                tracing::info!(node = %address, "The last node left the ring");
+                self.emptied.notify_all();
+                Ok(())
+            }
+            None => Err(LindaError::NotMember(address.clone())),
+        }
+    }
+
+    // Returns once every node has left the ring.
+    pub fn wait_until_empty(&self) -> Result<(), LindaError> {
+        let mut members = self.lock()?;
+        while !members.is_empty() {
+            members = self
+                .emptied
+                .wait(members)
+                .map_err(|e| LindaError::MutexLockFailure(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    // Tells a member that is still in the ring why it could not leave.
+    fn refuse(&self, address: &Address, id: u64, e: LindaError) {
+        let members = match self.lock() {
+            Ok(members) => members,
+            Err(_) => return,
+        };
+        if let Some(member) = members.iter().find(|member| member.address == *address) {
+            let reply = MessageType::Reply(id, Reply::Failed(e.describe()));
+            if let Err(e) = member.control.notify(reply) {
+                tracing::warn!(node = %address, error = e.describe(), "Failed to answer node");
+            }
+        }
+    }
+}
+
+fn request<Output: 'static + io::Write + marker::Send>(
+    member: &Member<Output>,
+    admin: Admin,
+    patience: time::Duration,
+) -> Result<Reply, LindaError> {
+    let call = member
+        .control
+        .call(|id| MessageType::Admin(id, admin), Some(patience));
+    call.map(|(reply, _)| reply)
+}
+
+fn expect_count(reply: Reply) -> Result<usize, LindaError> {
+    match reply {
+        Reply::Count(count) => Ok(count),
+        Reply::Failed(message) => Err(LindaError::ServerFailure(message)),
+        reply => Err(unexpected(&reply)),
+    }
+}
+
+fn expect_done(reply: Reply) -> Result<(), LindaError> {
+    match reply {
+        Reply::Done => Ok(()),
+        Reply::Failed(message) => Err(LindaError::ServerFailure(message)),
+        reply => Err(unexpected(&reply)),
+    }
+}
+
+// Runs one command against the ring; the answer ends with an empty line so that a script
+// driving the control socket knows when to stop reading.
+pub fn execute<Output: 'static + io::Write + marker::Send>(
+    ring: &Ring<Output>,
+    control: Control,
+) -> String {
+    let mut text = String::new();
+    let result = match control {
+        Control::Members => ring.members().map(|members| {
+            for (address, count) in members {
+                let _ = match count {
+                    Ok(count) => writeln!(text, "{address}  {count} tuples"),
+                    Err(e) => writeln!(text, "{address}  unreachable: {}", e.describe()),
+                };
+            }
+        }),
+        Control::Health => ring.health().map(|members| {
+            let answering = members.iter().filter(|(_, health)| health.is_ok()).count();
+            for (address, health) in &members {
+                let _ = match health {
+                    Ok(elapsed) => writeln!(text, "{address}  ok ({elapsed:.3?})"),
+                    Err(e) => writeln!(text, "{address}  unreachable: {}", e.describe()),
+                };
+            }
+            let state = match answering == members.len() {
+                true => "healthy",
+                false => "degraded",
+            };
+            let _ = writeln!(
+                text,
+                "ring {state}: {answering}/{} nodes answering",
+                members.len()
+            );
+        }),
+        Control::Purge(template) => ring.purge(&template).map(|members| {
+            let mut total = 0;
+            for (address, count) in members {
+                let _ = match count {
+                    Ok(count) => {
+                        total += count;
+                        writeln!(text, "{address}  purged {count}")
+                    }
+                    Err(e) => writeln!(text, "{address}  failed: {}", e.describe()),
+                };
+            }
+            let _ = writeln!(text, "purged {total} tuples matching {template}");
+        }),
+        Control::Evict(address) => ring.evict(&address).map(|_| {
+            let _ = writeln!(text, "evicted {address}");
+        }),
+        Control::Help => {
+            let _ = writeln!(text, "{USAGE}");
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        let _ = writeln!(text, "error: {}", e.describe());
+    }
+    text.push('\n');
+
+    text
+}
+
+// Serves the control socket; it is not authenticated, so it should only be reachable by
+// the operator, e.g. as a Unix socket or on the loopback interface.
+pub fn serve<Output: 'static + io::Write + marker::Send>(
+    listener: Listener,
+    ring: sync::Arc<Ring<Output>>,
+) -> io::Result<()> {
+    loop {
+        let stream = match listener.accept() {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!(error = %e, "Incoming control connection failed - skipping");
+                continue;
+            }
+        };
+
+        let ring = ring.clone();
+        thread::spawn(move || {
+            let input = match stream.try_clone() {
+                Ok(input) => io::BufReader::new(input),
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to clone control stream - skipping");
+                    return;
+                }
+            };
+            let mut output = stream;
+            for line in input.lines() {
+                let line = match line {
+                    Ok(line) if line.trim().is_empty() => continue,
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                tracing::info!(command = line.trim(), "Control command");
+                let answer = match line.parse() {
+                    Ok(control) => execute(&ring, control),
+                    Err(e) => format!("error: {e}\n\n"),
+                };
+                if output
+                    .write_all(answer.as_bytes())
+                    .and_then(|_| output.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        harness::{address, ring, template, tuple, LinkWriter, Network},
+        transport::{Stream, Transport},
+        TupleSpace,
+    };
+
+    type Served = thread::JoinHandle<Result<Departure, LindaError>>;
+
+    // Registers every harness node with a server over real sockets; the nodes open links to
+    // new successors through the network.
+    fn control_ring(
+        network: &Network,
+        nodes: &[sync::Arc<Linda<LinkWriter>>],
+        metrics: &sync::Arc<ServerMetrics>,
+    ) -> (sync::Arc<Ring<Stream>>, Vec<ArcMutex<Stream>>, Vec<Served>) {
+        let listener = Listener::bind(&Transport::Tcp.local_address()).unwrap();
+        let server = listener.local_address().unwrap();
+        let (mut members, mut controls, mut served) = (Vec::new(), Vec::new(), Vec::new());
+        for (i, node) in nodes.iter().enumerate() {
+            let stream = Stream::connect(&server).unwrap();
+            let input = stream.try_clone().unwrap();
+            let control = sync::Arc::new(sync::Mutex::new(stream));
+            let (node, writer, dial) = (node.clone(), control.clone(), network.dialer());
+            served.push(thread::spawn(move || {
+                serve_node(input, &writer, &node, dial)
+            }));
+            controls.push(control);
+            let accepted = listener.accept().unwrap();
+            members.push((address(i), accepted.try_clone().unwrap(), accepted));
+            metrics.joined();
+        }
+
+        (
+            Ring::new(server, members, metrics.clone()),
+            controls,
+            served,
+        )
+    }
+
+    #[test]
+    fn parse_control() {
+        assert_eq!("Members".parse(), Ok(Control::Members));
+        assert_eq!(" health \r".parse(), Ok(Control::Health));
+        assert_eq!(
+            "evict 127.0.0.1:4000".parse(),
+            Ok(Control::Evict("127.0.0.1:4000".parse().unwrap()))
+        );
+        assert_eq!("purge (int: 3)".parse(), Ok(Control::Purge(template(3))));
+        assert_eq!(
+            "purge".parse::<Control>(),
+            Err(String::from(MISSING_ARGUMENT))
+        );
+        assert_eq!(
+            "members all".parse::<Control>(),
+            Err(String::from(UNKNOWN_COMMAND))
+        );
+        assert!("purge (int: ?)".parse::<Control>().is_err());
+    }
+
+    // Every harness node gets a control connection over a real socket.
+    #[test]
+    fn administer_nodes() {
+        let (network, nodes) = ring(2);
+        for i in 0..2 {
+            nodes[0].out(Tuple::from_vec(vec![Value::int(i)])).unwrap();
+            network.hop(0);
+            network.hop(1);
+        }
+
+        let listener = Listener::bind(&Transport::Tcp.local_address()).unwrap();
+        let server = listener.local_address().unwrap();
+        let mut members = Vec::new();
+        for (i, node) in nodes.into_iter().enumerate() {
+            let stream = Stream::connect(&server).unwrap();
+            let input = stream.try_clone().unwrap();
+            thread::spawn(move || {
+                let no_links = |_: &Address| Err(io::ErrorKind::Unsupported.into());
+                serve_node(
+                    input,
+                    &sync::Arc::new(sync::Mutex::new(stream)),
+                    &node,
+                    no_links,
+                )
+            });
+            let control = listener.accept().unwrap();
+            members.push((address(i), control.try_clone().unwrap(), control));
+        }
+
+        let ring = Ring::new(server, members, sync::Arc::default());
+        assert_eq!(
+            execute(&ring, Control::Members),
+            "127.0.0.1:1000  2 tuples\n127.0.0.1:1001  0 tuples\n\n"
+        );
+        assert!(execute(&ring, Control::Health).ends_with("ring healthy: 2/2 nodes answering\n\n"));
+        assert_eq!(
+            execute(&ring, Control::Purge(template(1))),
+            "127.0.0.1:1000  purged 1\n127.0.0.1:1001  purged 0\n\
+             purged 1 tuples matching (int: 1)\n\n"
+        );
+        assert_eq!(
+            execute(&ring, Control::Evict(address(5))),
+            "error: 127.0.0.1:1005 is not a member of the ring\n\n"
+        );
+    }
+    // The operator evicts the middle node of three; the first one then sends to the last.
+    #[test]
+    fn evict_live_member() {
+        let (network, nodes) = ring(3);
+        let nodes: Vec<_> = nodes.into_iter().map(sync::Arc::new).collect();
+        nodes[1].out(tuple(1)).unwrap();
+        for link in [1, 2, 0] {
+            network.hop(link);
+        }
+        let metrics = sync::Arc::default();
+        let (ring, _controls, mut served) = control_ring(&network, &nodes, &metrics);
+
+        let operator = ring.clone();
+        let evicting = thread::spawn(move || execute(&operator, Control::Evict(address(1))));
+        network.wait_for_frames(1, 2);
+        assert_eq!(network.hop(1), Message::handoff(tuple(1), address(1)));
+        assert_eq!(network.hop(1), Message::leave(address(1)));
+        network.hop(2);
+        network.hop(0);
+        assert_eq!(
+            served.remove(1).join().unwrap().unwrap(),
+            Departure::Evicted
+        );
+        assert_eq!(evicting.join().unwrap(), "evicted 127.0.0.1:1001\n\n");
+        assert_eq!(metrics.snapshot(None).members, 2);
+        assert_eq!(network.hop(1), Message::hello(address(0)));
+        // Once relinked, the first node no longer sends to the evicted one, whose worker has
+        // read everything and stops.
+        nodes[1].shutdown().unwrap();
+
+        nodes[0].out(tuple(2)).unwrap();
+        assert_eq!(network.hop(1), Message::value(tuple(2), address(0)));
+        network.hop(2);
+        let requester = nodes[0].clone();
+        let handle = thread::spawn(move || requester.input(template(1), PATIENCE));
+        network.hop(1);
+        assert_eq!(network.hop(2), Message::value(tuple(1), address(2)));
+        assert_eq!(handle.join().unwrap().unwrap(), tuple(1));
+        assert_eq!(nodes[0].inp(&template(2)).unwrap(), tuple(2));
+    }
+
+    // A node that leaves asks the server, which evicts it and links its predecessor past it.
+    #[test]
+    fn nodes_leave_through_the_server() {
+        let (network, nodes) = ring(3);
+        let nodes: Vec<_> = nodes.into_iter().map(sync::Arc::new).collect();
+        nodes[0].out(tuple(1)).unwrap();
+        for link in 0..network.size() {
+            network.hop(link);
+        }
+        let metrics = sync::Arc::default();
+        let (ring, controls, mut served) = control_ring(&network, &nodes, &metrics);
+
+        depart(&controls[0], address(0)).unwrap();
+        network.hop(0);
+        network.hop(0);
+        network.hop(1);
+        network.hop(2);
+        assert_eq!(
+            served.remove(0).join().unwrap().unwrap(),
+            Departure::Evicted
+        );
+        // The eviction holds the ring until the predecessor is relinked.
+        assert_eq!(
+            execute(&ring, Control::Members),
+            "127.0.0.1:1001  1 tuples\n127.0.0.1:1002  0 tuples\n\n"
+        );
+        assert_eq!(metrics.snapshot(None).members, 2);
+        assert_eq!(network.hop(0), Message::hello(address(2)));
+        nodes[0].shutdown().unwrap();
+
+        let requester = nodes[2].clone();
+        let handle = thread::spawn(move || requester.input(template(1), PATIENCE));
+        network.hop(0);
+        network.hop(1);
+        assert_eq!(handle.join().unwrap().unwrap(), tuple(1));
+    }
+
+    // The server lets the last node go and stops once the ring is empty.
+    #[test]
+    fn last_node_empties_the_ring() {
+        let (network, nodes) = ring(1);
+        let nodes: Vec<_> = nodes.into_iter().map(sync::Arc::new).collect();
+        let metrics = sync::Arc::default();
+        let (ring, controls, mut served) = control_ring(&network, &nodes, &metrics);
+        assert_eq!(
+            execute(&ring, Control::Evict(address(0))),
+            "error: This tuple space does not support evicting the last node of the ring\n\n"
+        );
+
+        depart(&controls[0], address(0)).unwrap();
+        assert_eq!(served.remove(0).join().unwrap().unwrap(), Departure::Alone);
+        ring.wait_until_empty().unwrap();
+        assert_eq!(execute(&ring, Control::Members), "\n");
+        assert_eq!(metrics.snapshot(None).members, 0);
+        // Its tuples have nowhere to go but back to itself.
+        let last = nodes[0].clone();
+        let withdrawing = thread::spawn(move || last.withdraw());
+        assert_eq!(network.hop(0), Message::leave(address(0)));
+        withdrawing.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use std::{fs, io, path};
+
+use ring::{hmac, rand};
+
+const NONCE_LENGTH: usize = 32;
+const CONNECTOR: &[u8] = b"linda connector";
+const ACCEPTOR: &[u8] = b"linda acceptor";
+const AUTH_FAILED: &str = "Peer failed to authenticate - check the shared secret!";
+const EMPTY_SECRET: &str = "Shared secret must not be empty!";
+
+// Pre-shared key both ends of a connection prove to know before any message is exchanged.
+// The acceptor and the connector answer each other's challenge, so neither side can be
+// impersonated without the key.
+#[derive(Clone)]
+pub struct SharedKey {
+    key: hmac::Key,
+}
+
+fn rejected() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, AUTH_FAILED)
+}
+
+// A peer that hangs up in the middle of the handshake did not know how to answer it.
+fn hung_up(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::ConnectionReset => rejected(),
+        _ => e,
+    }
+}
+
+impl SharedKey {
+    pub fn new(secret: &[u8]) -> SharedKey {
+        SharedKey {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+
+    // Trailing whitespace is ignored, so the file may end with a newline.
+    pub fn load(path: &path::Path) -> io::Result<SharedKey> {
+        let secret = fs::read(path)?;
+        let secret = secret.trim_ascii_end();
+        if secret.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, EMPTY_SECRET));
+        }
+
+        Ok(SharedKey::new(secret))
+    }
+
+    pub fn from_flag(secret: Option<String>) -> Result<Option<SharedKey>, String> {
+        match secret {
+            Some(path) => match SharedKey::load(path::Path::new(&path)) {
+                Ok(key) => Ok(Some(key)),
+                Err(e) => Err(format!("Failed to load shared secret from {path}! {e}")),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn sign(&self, role: &[u8], nonce: &[u8]) -> hmac::Tag {
+        let mut context = hmac::Context::with_key(&self.key);
+        context.update(role);
+        context.update(nonce);
+        context.sign()
+    }
+
+    fn verify(&self, role: &[u8], nonce: &[u8], tag: &[u8]) -> io::Result<()> {
+        let mut data = role.to_vec();
+        data.extend_from_slice(nonce);
+        hmac::verify(&self.key, &data, tag).map_err(|_| rejected())
+    }
+}
+
+fn nonce() -> io::Result<[u8; NONCE_LENGTH]> {
+    let mut nonce = [0; NONCE_LENGTH];
+    rand::SecureRandom::fill(&rand::SystemRandom::new(), &mut nonce)
+        .map_err(|_| io::Error::other("Failed to generate challenge!"))?;
+    Ok(nonce)
+}
+
+fn read_tag<Stream: io::Read>(stream: &mut Stream) -> io::Result<Vec<u8>> {
+    let mut tag = vec![0; hmac::HMAC_SHA256.digest_algorithm().output_len()];
+    stream.read_exact(&mut tag)?;
+    Ok(tag)
+}
+
+// Challenges the peer that opened the connection; without a key the stream is left untouched.
+pub fn accept<Stream: io::Read + io::Write>(
+    key: Option<&SharedKey>,
+    mut stream: Stream,
+) -> io::Result<Stream> {
+    if let Some(key) = key {
+        challenge(key, &mut stream).map_err(hung_up)?;
+    }
+
+    Ok(stream)
+}
+
+fn challenge<Stream: io::Read + io::Write>(key: &SharedKey, stream: &mut Stream) -> io::Result<()> {
+    let challenge = nonce()?;
+    stream.write_all(&challenge)?;
+    stream.flush()?;
+
+    let tag = read_tag(stream)?;
+    key.verify(CONNECTOR, &challenge, &tag)?;
+
+    let mut counter = [0; NONCE_LENGTH];
+    stream.read_exact(&mut counter)?;
+    stream.write_all(key.sign(ACCEPTOR, &counter).as_ref())?;
+    stream.flush()
+}
+
+pub fn connect<Stream: io::Read + io::Write>(
+    key: Option<&SharedKey>,
+    mut stream: Stream,
+) -> io::Result<Stream> {
+    if let Some(key) = key {
+        respond(key, &mut stream).map_err(hung_up)?;
+    }
+
+    Ok(stream)
+}
+
+fn respond<Stream: io::Read + io::Write>(key: &SharedKey, stream: &mut Stream) -> io::Result<()> {
+    let mut challenge = [0; NONCE_LENGTH];
+    stream.read_exact(&mut challenge)?;
+
+    let counter = nonce()?;
+    stream.write_all(key.sign(CONNECTOR, &challenge).as_ref())?;
+    stream.write_all(&counter)?;
+    stream.flush()?;
+
+    let tag = read_tag(stream)?;
+    key.verify(ACCEPTOR, &counter, &tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net, thread};
+
+    use super::*;
+    use crate::message::Message;
+
+    fn pair() -> (net::TcpStream, net::TcpStream) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let right = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        (listener.accept().unwrap().0, right)
+    }
+
+    fn handshake(
+        acceptor: Option<SharedKey>,
+        connector: Option<SharedKey>,
+    ) -> (io::Result<net::TcpStream>, io::Result<net::TcpStream>) {
+        let (left, right) = pair();
+        let handle = thread::spawn(move || accept(acceptor.as_ref(), left));
+        let connected = connect(connector.as_ref(), right);
+        (handle.join().unwrap(), connected)
+    }
+
+    #[test]
+    fn shared_secret() {
+        let key = SharedKey::new(b"secret");
+        let (accepted, connected) = handshake(Some(key.clone()), Some(key));
+
+        let mut accepted = accepted.unwrap();
+        Message::from_address("127.0.0.1:1".parse().unwrap())
+            .send(&mut connected.unwrap())
+            .unwrap();
+        assert!(Message::recv(&mut accepted).is_ok());
+    }
+
+    #[test]
+    fn reject_wrong_secret() {
+        let (accepted, connected) = handshake(
+            Some(SharedKey::new(b"secret")),
+            Some(SharedKey::new(b"guess")),
+        );
+
+        let e = accepted.err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(e.to_string(), AUTH_FAILED);
+        assert!(connected.is_err());
+    }
+
+    #[test]
+    fn reject_unauthenticated_peer() {
+        let (left, mut right) = pair();
+        let handle = thread::spawn(move || accept(Some(&SharedKey::new(b"secret")), left));
+        Message::from_address("127.0.0.1:1".parse().unwrap())
+            .send(&mut right)
+            .unwrap();
+        drop(right);
+
+        let e = handle.join().unwrap().err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
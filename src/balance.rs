@@ -0,0 +1,329 @@
+use std::{str, sync, time};
+
+use crate::{
+    partition::{HashRing, Partition},
+    transport::Address,
+    tuple::{Serializable, Tuple, Value},
+    utils::{read_le_u8, read_le_usize},
+};
+
+const INVALID_PLACEMENT: &str =
+    "Placement needs to be one of 'local', 'round-robin', 'least-loaded' or 'hash'!";
+// How often a node that places tuples elsewhere surveys the ring when it does not rebalance.
+const CENSUS_INTERVAL: time::Duration = time::Duration::from_secs(5);
+
+// Where a node stores the tuples it produces once they have been around the ring.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Placement {
+    #[default]
+    Local,
+    RoundRobin,
+    LeastLoaded,
+    // By the first field, so equal keys end up on the same node.
+    Hash,
+}
+
+// `census` is how often the node surveys the ring for its members and their loads. Until the
+// first survey returns, tuples stay where they were produced.
+// A partitioned node stores the tuples by the owner of their key instead.
+#[derive(Clone, Debug, Default)]
+pub struct Balance {
+    pub placement: Placement,
+    pub census: Option<time::Duration>,
+    pub rebalance: bool,
+    pub partition: Option<Partition>,
+}
+
+// A node as a census found it. Nodes owning a partition name the endpoint their tuples can be
+// reached at directly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    pub address: Address,
+    pub load: usize,
+    pub endpoint: Option<Address>,
+}
+
+// The ring as of the last census, in ring order starting with this node. Placements since
+// then are added to the loads.
+#[derive(Debug, Default)]
+struct View {
+    members: Vec<Member>,
+    owners: HashRing,
+    next: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct Balancer {
+    placement: Placement,
+    rebalance: bool,
+    partition: Option<Partition>,
+    view: sync::Mutex<View>,
+}
+
+impl str::FromStr for Placement {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "local" => Ok(Placement::Local),
+            "round-robin" => Ok(Placement::RoundRobin),
+            "least-loaded" => Ok(Placement::LeastLoaded),
+            "hash" => Ok(Placement::Hash),
+            _ => Err(INVALID_PLACEMENT),
+        }
+    }
+}
+
+impl Balance {
+    // Rebalancing every `rebalance`, placing tuples elsewhere or owning a partition needs
+    // regular surveys.
+    pub fn new(
+        placement: Placement,
+        rebalance: Option<time::Duration>,
+        partition: Option<Partition>,
+    ) -> Balance {
+        let census = match (placement, rebalance, &partition) {
+            (_, Some(interval), _) => Some(interval),
+            (Placement::Local, None, None) => None,
+            _ => Some(CENSUS_INTERVAL),
+        };
+
+        Balance {
+            placement,
+            census,
+            rebalance: rebalance.is_some(),
+            partition,
+        }
+    }
+}
+
+impl Balancer {
+    pub fn new(balance: &Balance) -> Balancer {
+        Balancer {
+            placement: balance.placement,
+            rebalance: balance.rebalance,
+            partition: balance.partition.clone(),
+            view: sync::Mutex::new(View::default()),
+        }
+    }
+
+    pub fn endpoint(&self) -> Option<Address> {
+        self.partition
+            .as_ref()
+            .map(|partition| partition.endpoint.clone())
+    }
+
+    pub fn update(&self, members: Vec<Member>) {
+        if let Ok(mut view) = self.view.lock() {
+            view.owners = HashRing::new(&members);
+            view.members = members;
+        }
+    }
+
+    // Forgets a member that left the ring, so that nothing is sent its way until the next
+    // census.
+    pub fn depart(&self, address: &Address) {
+        if let Ok(mut view) = self.view.lock() {
+            view.members.retain(|member| member.address != *address);
+            view.owners = HashRing::new(&view.members);
+        }
+    }
+
+    // The member owning the tuples with `key`, once a census found any owners.
+    pub fn owner(&self, key: &Value) -> Option<Member> {
+        let view = self.view.lock().ok()?;
+        let owner = view.owners.owner(key)?;
+
+        Some(view.members[owner].clone())
+    }
+
+    // The member to store `tuple`, or `None` to keep it here.
+    pub fn target(&self, tuple: &Tuple<Value>) -> Option<Address> {
+        let mut view = self.view.lock().ok()?;
+        if let Some(key) = self.key(tuple) {
+            let owner = view.owners.owner(key)?;
+            return Some(view.members[owner].address.clone());
+        }
+
+        let count = view.members.len();
+        let index = match self.placement {
+            _ if count < 2 => return None,
+            Placement::Local => return None,
+            Placement::RoundRobin => {
+                view.next = (view.next + 1) % count;
+                view.next
+            }
+            Placement::LeastLoaded => (0..count).min_by_key(|&i| view.members[i].load)?,
+            // Every node has to pick the same member, wherever it is in the ring.
+            Placement::Hash => {
+                let hash = fnv1a(&tuple.iter().next()?.to_bytes());
+                let mut order: Vec<usize> = (0..count).collect();
+                order.sort_by_key(|&i| view.members[i].address.to_string());
+                order[(hash % count as u64) as usize]
+            }
+        };
+        view.members[index].load += 1;
+
+        Some(view.members[index].address.clone())
+    }
+
+    fn key<'a>(&self, tuple: &'a Tuple<Value>) -> Option<&'a Value> {
+        tuple.get(self.partition.as_ref()?.key)
+    }
+
+    // Takes the tuples that belong elsewhere out of `tuples`: those owned by another member
+    // of a partitioned ring, otherwise this node's surplus.
+    pub fn moving(
+        &self,
+        tuples: &mut Vec<Tuple<Value>>,
+        node: &Address,
+    ) -> Vec<(Tuple<Value>, Address)> {
+        if self.partition.is_none() {
+            let mut moving = vec![];
+            for (target, count) in self.surplus(tuples.len()) {
+                let at = tuples.len().saturating_sub(count);
+                moving.extend(
+                    tuples
+                        .split_off(at)
+                        .into_iter()
+                        .map(|t| (t, target.clone())),
+                );
+            }
+            return moving;
+        }
+
+        let mut moving = vec![];
+        let mut kept = vec![];
+        for tuple in tuples.drain(..) {
+            match self.key(&tuple).and_then(|key| self.owner(key)) {
+                Some(owner) if owner.address != *node => moving.push((tuple, owner.address)),
+                _ => kept.push(tuple),
+            }
+        }
+        *tuples = kept;
+
+        moving
+    }
+
+    // How many tuples this node should hand to which member, if it holds clearly more than
+    // its share. Small differences are left alone so tuples do not bounce between nodes.
+    pub fn surplus(&self, local: usize) -> Vec<(Address, usize)> {
+        let view = match self.view.lock() {
+            Ok(view) if self.rebalance && view.members.len() > 1 => view,
+            _ => return vec![],
+        };
+        let others = &view.members[1..];
+        let share =
+            (local + others.iter().map(|member| member.load).sum::<usize>()) / view.members.len();
+        if local <= share + (share / 4).max(1) {
+            return vec![];
+        }
+
+        let mut excess = local - share;
+        let mut surplus = vec![];
+        for member in others {
+            let count = share.saturating_sub(member.load).min(excess);
+            if count > 0 {
+                surplus.push((member.address.clone(), count));
+                excess -= count;
+            }
+        }
+
+        surplus
+    }
+}
+
+impl Serializable for Member {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.address.to_bytes();
+        bytes.append(&mut self.load.to_le_bytes().to_vec());
+        match &self.endpoint {
+            Some(endpoint) => {
+                bytes.push(1);
+                bytes.append(&mut endpoint.to_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &mut &[u8]) -> Option<Member> {
+        Some(Member {
+            address: Address::from_bytes(bytes)?,
+            load: read_le_usize(bytes)?,
+            endpoint: match read_le_u8(bytes)? {
+                0 => None,
+                1 => Some(Address::from_bytes(bytes)?),
+                _ => return None,
+            },
+        })
+    }
+}
+
+// A hash that every node computes the same way, unlike the standard library's.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::members;
+
+    fn balancer(placement: Placement, loads: &[usize]) -> Balancer {
+        let balancer = Balancer::new(&Balance {
+            placement,
+            census: None,
+            rebalance: true,
+            partition: None,
+        });
+        balancer.update(members(loads));
+        balancer
+    }
+
+    #[test]
+    fn choose_target() {
+        let tuple = Tuple::from_vec(vec![Value::int(7)]);
+        let nodes = members(&[0, 0, 0]);
+
+        let round_robin = balancer(Placement::RoundRobin, &[0, 0, 0]);
+        let targets: Vec<_> = (0..4).map(|_| round_robin.target(&tuple)).collect();
+        let expected = [1, 2, 0, 1].map(|i| Some(nodes[i].address.clone()));
+        assert_eq!(targets, expected);
+
+        let least_loaded = balancer(Placement::LeastLoaded, &[2, 1, 0]);
+        assert_eq!(least_loaded.target(&tuple), Some(nodes[2].address.clone()));
+        assert_eq!(least_loaded.target(&tuple), Some(nodes[1].address.clone()));
+
+        // Another node sees the ring starting with itself but hashes to the same member.
+        let hash = balancer(Placement::Hash, &[0, 0, 0]);
+        let mut rotated = members(&[0, 0, 0]);
+        rotated.rotate_left(1);
+        let other = balancer(Placement::Hash, &[]);
+        other.update(rotated);
+        assert_eq!(hash.target(&tuple), other.target(&tuple));
+        assert_eq!(hash.target(&Tuple::new()), None);
+
+        assert_eq!(balancer(Placement::Local, &[0, 0]).target(&tuple), None);
+        assert_eq!(balancer(Placement::RoundRobin, &[0]).target(&tuple), None);
+        assert_eq!("Least-Loaded".parse(), Ok(Placement::LeastLoaded));
+        assert_eq!("random".parse::<Placement>(), Err(INVALID_PLACEMENT));
+    }
+
+    #[test]
+    fn hand_over_surplus() {
+        let nodes = members(&[0, 0, 0, 0]);
+        let surplus = balancer(Placement::Local, &[9, 4, 0, 3]).surplus(9);
+        assert_eq!(
+            surplus,
+            vec![(nodes[2].address.clone(), 4), (nodes[3].address.clone(), 1)]
+        );
+
+        assert_eq!(balancer(Placement::Local, &[2, 0]).surplus(2), vec![]);
+        assert_eq!(balancer(Placement::Local, &[5, 4, 4]).surplus(5), vec![]);
+        assert_eq!(balancer(Placement::Local, &[0, 0]).surplus(40).len(), 1);
+    }
+}
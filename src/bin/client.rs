@@ -1,133 +1,782 @@
 use linda::{
+    acl::{Access, Policy, ANONYMOUS},
+    admin::{self, Departure},
+    auth::{self, SharedKey},
+    balance::{Balance, Placement},
+    exporter, help, logging,
     message::*,
+    output::{Format, Outcome, Record},
     parser::*,
+    partition::{Partition, Partitioned, Peer},
+    remote::RemoteSpace,
+    script::{self, Script},
+    standalone,
+    stats::ServerMetrics,
+    tls::{self, TlsConfig},
+    transport::{Address, Listener, Stream, Transport},
     tuple::{Tuple, Value},
     utils::*,
     *,
 };
-use std::{env, io, net, time};
+use rustyline::{
+    completion::Completer, error::ReadlineError, history::DefaultHistory, Context, Editor, Helper,
+    Highlighter, Hinter, Validator,
+};
+use std::{env, fs, io, net, path, process, sync, thread, time};
+
+const NO_STATS: &str = "The standalone server keeps no statistics for its clients.";
+const NO_METRICS: &str = "Only ring nodes serve metrics; use the server's --metrics instead!";
+// The server waits for an evicted node to hand off its tuples before it closes the ring.
+const DEPARTURE_TIMEOUT: time::Duration = time::Duration::from_secs(15);
+const NO_DEPARTURE: &str = "The server did not let the node leave in time!";
+const INVALID_REBALANCE: &str =
+    "The rebalancing interval needs to be a positive number of seconds!";
+const NO_PLACEMENT: &str = "Only ring nodes place tuples; the standalone server holds them all!";
+const INVALID_PARTITION: &str = "The partition key needs to be the index of a tuple field!";
+const NO_REBALANCE: &str = "Partitioned nodes keep tuples with their owners and cannot rebalance!";
+
+enum Mode {
+    Interactive,
+    Script(String),
+    Batch,
+}
+
+enum Status {
+    Continue,
+    Exit,
+    Failed,
+}
+
+struct Options {
+    server: Address,
+    transport: Transport,
+    standalone: bool,
+    mode: Mode,
+    format: Format,
+    tls: Option<TlsConfig>,
+    key: Option<SharedKey>,
+    policy: Policy,
+    metrics: Option<net::SocketAddr>,
+    placement: Placement,
+    rebalance: Option<time::Duration>,
+    partition: Option<usize>,
+}
 
 fn main() {
-    let server_socket = init();
-    let (prev_client, next_client) = connect_to_server(server_socket);
+    let options = init();
+    let security = Security {
+        tls: options.tls.as_ref(),
+        key: options.key.as_ref(),
+    };
+    let mut departure = None;
+    let (space, address) = if options.standalone {
+        connect_to_standalone(&options.server, security)
+    } else {
+        let (local, next_client, control) =
+            connect_to_server(&options.server, options.transport, security);
+        let endpoint = options
+            .partition
+            .map(|key| bind_partition(options.transport, key));
+        let partition = endpoint.as_ref().map(|(_, partition)| partition.clone());
+        let balance = Balance::new(options.placement, options.rebalance, partition);
+        let (space, address) = join_ring(&local, next_client, security, options.policy, balance);
+        let space = sync::Arc::new(space);
+        if let Some(metrics) = options.metrics {
+            let monitor = space.monitor();
+            let render = move || {
+                monitor
+                    .stats()
+                    .map_or_else(String::new, |s| exporter::node(&s))
+            };
+            if let Err(e) = exporter::spawn(metrics, render) {
+                error(&format!("Failed to serve metrics at {metrics}! {e}"));
+            }
+        }
+        let (tls, key) = (options.tls.clone(), options.key.clone());
+        let (control, evicted) = administer(local, control, space.clone(), tls, key);
+        departure = Some((space.clone(), control, evicted));
+        match endpoint {
+            Some((listener, partition)) => {
+                let (tls, key) = (options.tls, options.key);
+                let space = serve_partition(listener, partition, space, tls, key);
+                (space as sync::Arc<dyn TupleSpace>, address)
+            }
+            None => (space as sync::Arc<dyn TupleSpace>, address),
+        }
+    };
 
-    client_loop(prev_client, next_client)
+    let status = client_loop(&*space, address.clone(), options.mode, options.format);
+    // Leaving hands the tuples this node holds over to the rest of the ring. Only once the
+    // predecessor sends past the node is there nothing left for it to relay.
+    let left = match departure {
+        Some((node, control, evicted)) => match depart(&control, &evicted, address) {
+            Some(Departure::Evicted) => space.shutdown(),
+            Some(Departure::Alone) | None => node.withdraw(),
+        },
+        None => space.shutdown(),
+    };
+    if let Err(e) = left {
+        tracing::error!(
+            error = e.describe(),
+            "Failed to leave the tuple space cleanly"
+        );
+    }
+    if let Status::Failed = status {
+        process::exit(1);
+    }
 }
 
-fn init() -> net::SocketAddr {
+// TLS and the shared secret are layered in this order on every connection the client makes.
+#[derive(Clone, Copy)]
+struct Security<'a> {
+    tls: Option<&'a TlsConfig>,
+    key: Option<&'a SharedKey>,
+}
+
+impl Security<'_> {
+    fn connect(&self, address: &Address) -> io::Result<Stream> {
+        auth::connect(self.key, tls::connect(self.tls, address)?)
+    }
+
+    fn accept(&self, stream: Stream) -> io::Result<Stream> {
+        auth::accept(self.key, tls::accept(self.tls, stream)?)
+    }
+}
+
+fn init() -> Options {
     let mut args = env::args();
     let prog_name = args.next().unwrap();
+    let usage = format!(
+        "Usage:\n{prog_name} [--standalone] [--transport tcp|unix] [--script $FILE | --batch] \
+         [--output text|json] [--cert $FILE --key $FILE --ca $FILE] \
+         [--secret $FILE] [--policy $FILE] [--log $FILTER] [--metrics $ADDRESS] \
+         [--placement local|round-robin|least-loaded|hash] [--rebalance $SECONDS] \
+         [--partition $FIELD] $SERVER_ADDRESS"
+    );
 
-    if args.len() < 1 {
-        error(&format!("Usage:\n{prog_name} $SERVER_ADDRESS"));
+    let mut mode = Mode::Interactive;
+    let mut format = Format::Text;
+    let mut transport = Transport::Tcp;
+    let mut standalone = false;
+    let mut server = None;
+    let (mut cert, mut key, mut ca) = (None, None, None);
+    let mut secret = None;
+    let mut policy = Policy::default();
+    let mut log = None;
+    let mut metrics = None;
+    let mut placement = Placement::Local;
+    let mut rebalance = None;
+    let mut partition = None;
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--standalone" => standalone = true,
+            "--transport" => match args.next().map(|val| val.parse()) {
+                Some(Ok(val)) => transport = val,
+                Some(Err(e)) => error(e),
+                None => error(&usage),
+            },
+            "--script" => match args.next() {
+                Some(path) => mode = Mode::Script(path),
+                None => error(&usage),
+            },
+            "--batch" => mode = Mode::Batch,
+            "--output" => match args.next().map(|val| val.parse()) {
+                Some(Ok(val)) => format = val,
+                Some(Err(e)) => error(e),
+                None => error(&usage),
+            },
+            "--cert" => match args.next() {
+                Some(val) => cert = Some(val),
+                None => error(&usage),
+            },
+            "--key" => match args.next() {
+                Some(val) => key = Some(val),
+                None => error(&usage),
+            },
+            "--ca" => match args.next() {
+                Some(val) => ca = Some(val),
+                None => error(&usage),
+            },
+            "--secret" => match args.next() {
+                Some(val) => secret = Some(val),
+                None => error(&usage),
+            },
+            "--policy" => match args.next().map(|val| Policy::load(path::Path::new(&val))) {
+                Some(Ok(val)) => policy = val,
+                Some(Err(e)) => error(&e),
+                None => error(&usage),
+            },
+            "--log" => match args.next() {
+                Some(val) => log = Some(val),
+                None => error(&usage),
+            },
+            "--metrics" => match args.next().map(|val| val.parse()) {
+                Some(Ok(val)) => metrics = Some(val),
+                Some(Err(e)) => error(&format!("Incorrect metrics address! {e}")),
+                None => error(&usage),
+            },
+            "--placement" => match args.next().map(|val| val.parse()) {
+                Some(Ok(val)) => placement = val,
+                Some(Err(e)) => error(e),
+                None => error(&usage),
+            },
+            "--rebalance" => match args.next().map(|val| val.parse::<u64>()) {
+                Some(Ok(secs)) if secs > 0 => rebalance = Some(time::Duration::from_secs(secs)),
+                Some(_) => error(INVALID_REBALANCE),
+                None => error(&usage),
+            },
+            "--partition" => match args.next().map(|val| val.parse()) {
+                Some(Ok(key)) => partition = Some(key),
+                Some(Err(_)) => error(INVALID_PARTITION),
+                None => error(&usage),
+            },
+            _ if server.is_none() => server = Some(arg),
+            _ => error(&usage),
+        }
     }
 
-    match args.next().unwrap().parse() {
-        Ok(addr) => net::SocketAddr::new(addr, SERVER_PORT),
-        Err(e) => error(&format!("Incorrect server address! {e}")),
+    if let Err(e) = logging::init(log.as_deref()) {
+        error(&e);
+    }
+    let server = match server {
+        Some(server) => server,
+        None => error(&usage),
+    };
+    if standalone && metrics.is_some() {
+        error(NO_METRICS);
+    }
+    if standalone && (placement != Placement::Local || rebalance.is_some() || partition.is_some()) {
+        error(NO_PLACEMENT);
+    }
+    if partition.is_some() && rebalance.is_some() {
+        error(NO_REBALANCE);
+    }
+    let tls = match TlsConfig::from_flags(cert, key, ca) {
+        Ok(tls) => tls,
+        Err(e) => error(&e),
+    };
+    let key = match SharedKey::from_flag(secret) {
+        Ok(key) => key,
+        Err(e) => error(&e),
+    };
+    match transport.parse_address(&server) {
+        Ok(server) => Options {
+            server,
+            transport,
+            standalone,
+            mode,
+            format,
+            tls,
+            key,
+            policy,
+            metrics,
+            placement,
+            rebalance,
+            partition,
+        },
+        Err(e) => error(&e),
     }
 }
 
-fn connect_to_server(server_socket: net::SocketAddr) -> (net::TcpListener, net::SocketAddr) {
-    let listener = match net::TcpListener::bind("127.0.0.1:0") {
+// The connection the server sends the next address over stays open for its requests.
+fn connect_to_server(
+    server: &Address,
+    transport: Transport,
+    security: Security,
+) -> (Listener, Address, Stream) {
+    let listener = match Listener::bind(&transport.local_address()) {
         Ok(list) => list,
         Err(e) => error(&format!("Failed to bind! {e}")),
     };
-    let port = match listener.local_addr() {
-        Ok(addr) => addr.port(),
+    let address = match listener.local_address() {
+        Ok(addr) => addr,
         Err(e) => error(&format!("Failed to receive local address! {e}")),
     };
-    println!("Client listening on port {port}");
+    tracing::info!("Client listening at {address}");
 
-    let client_socket = match net::TcpStream::connect(server_socket) {
+    match security.connect(server) {
         Ok(mut stream) => {
-            let msg = Message::value(
-                Tuple::from_vec(vec![Value::int(port as i32)]),
-                server_socket,
-            );
-            if let Err(e) = msg.send(&mut stream) {
-                error(&format!("Failed to send to server! {e:?}"));
-            }
-            match stream.local_addr() {
-                Ok(addr) => addr,
-                Err(e) => error(&format!("Failed to obtain local address! {e}")),
+            if let Err(e) = Message::from_address(address.clone()).send(&mut stream) {
+                error(&format!("Failed to send to server! {}", e.describe()));
             }
         }
-        Err(e) => error(&format!("Connection to {server_socket} failed! {e}")),
+        Err(e) => error(&format!("Connection to {server} failed! {e}")),
     };
-    println!("Connected from {client_socket} to server {server_socket}");
+    tracing::info!("Registered {address} with server {server}");
 
-    let (mut stream, _) = match listener.accept() {
+    let mut stream = match listener.accept().and_then(|stream| security.accept(stream)) {
         Ok(res) => res,
         Err(e) => error(&format!("Failed to accept incoming connection! {e}")),
     };
 
-    (listener, get_socket(&mut stream))
+    let next = get_address(&mut stream);
+    (listener, next, stream)
 }
 
-fn get_socket(stream: &mut net::TcpStream) -> net::SocketAddr {
+fn get_address(stream: &mut Stream) -> Address {
     match Message::recv(stream) {
-        Ok(msg) => msg.ip,
-        Err(e) => error(&format!("Failed to obtain socket! {e:?}")),
+        Ok(msg) => msg.node,
+        Err(e) => error(&format!("Failed to obtain address! {}", e.describe())),
     }
 }
 
-fn client_loop(local: net::TcpListener, next: net::SocketAddr) {
-    let next = match net::TcpStream::connect(next) {
-        Ok(str) => str,
-        Err(e) => error(&format!("Failed to connect to next in ring! {e}")),
+fn connect_to_standalone(
+    server: &Address,
+    security: Security,
+) -> (sync::Arc<dyn TupleSpace>, Address) {
+    let stream = match security.connect(server) {
+        Ok(stream) => stream,
+        Err(e) => error(&format!("Connection to {server} failed! {e}")),
     };
-    let (prev, _) = match local.accept() {
+    let input = match stream.try_clone() {
+        Ok(input) => input,
+        Err(e) => error(&format!("Failed to clone server stream! {e}")),
+    };
+    let address = match stream.client_address() {
+        Ok(addr) => addr,
+        Err(e) => error(&format!("Failed to obtain local address! {e}")),
+    };
+    tracing::info!("Connected from {address} to standalone server {server}");
+
+    let space = sync::Arc::new(RemoteSpace::new(input, stream, address.clone()));
+    (space, address)
+}
+
+// Nodes are known by the identity their certificate names, so without TLS every node is
+// anonymous to the policies.
+fn join_ring(
+    local: &Listener,
+    next: Address,
+    security: Security,
+    policy: Policy,
+    balance: Balance,
+) -> (Linda<Stream>, Address) {
+    // Every node connects to its successor before accepting its predecessor, so the TLS
+    // handshakes have to run side by side or the ring waits on itself.
+    let (next, prev) = thread::scope(|scope| {
+        let next = scope.spawn(|| security.connect(&next));
+        let prev = local.accept().and_then(|stream| security.accept(stream));
+        (next.join(), prev)
+    });
+    let mut next = match next {
+        Ok(Ok(str)) => str,
+        Ok(Err(e)) => error(&format!("Failed to connect to next in ring! {e}")),
+        Err(_) => error("Failed to connect to next in ring!"),
+    };
+    let prev = match prev {
         Ok(str) => str,
         Err(e) => error(&format!("Failed to accept incoming stream! {e}")),
     };
 
-    let linda = Linda::new(prev, next, local.local_addr().unwrap());
-    loop {
-        let command = match get_command() {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!("{e}");
-                continue;
+    let address = local.local_address().unwrap();
+    if let Err(e) = Message::hello(address.clone()).send(&mut next) {
+        error(&format!("Failed to greet next in ring! {}", e.describe()));
+    }
+    let identity = security
+        .tls
+        .and_then(TlsConfig::identity)
+        .unwrap_or(ANONYMOUS);
+    let access = Access {
+        predecessor: String::from(prev.peer_identity().unwrap_or(ANONYMOUS)),
+        ..Access::new(String::from(identity), policy)
+    };
+    (
+        Linda::with_balance(prev, next, address.clone(), access, balance),
+        address,
+    )
+}
+
+// The tuples a partitioned node owns are served to the other nodes like a standalone server's.
+fn bind_partition(transport: Transport, key: usize) -> (Listener, Partition) {
+    let listener = match Listener::bind(&transport.partition_address()) {
+        Ok(listener) => listener,
+        Err(e) => error(&format!("Failed to bind the partition endpoint! {e}")),
+    };
+    match listener.local_address() {
+        Ok(endpoint) => (listener, Partition { key, endpoint }),
+        Err(e) => error(&format!("Failed to receive the partition endpoint! {e}")),
+    }
+}
+
+fn serve_partition(
+    listener: Listener,
+    partition: Partition,
+    space: sync::Arc<Linda<Stream>>,
+    tls: Option<TlsConfig>,
+    key: Option<SharedKey>,
+) -> sync::Arc<Partitioned<Stream>> {
+    tracing::info!(endpoint = %partition.endpoint, key = partition.key, "Serving partition");
+    let (owned, serve_tls, serve_key) = (space.partition(), tls.clone(), key.clone());
+    thread::spawn(move || {
+        let metrics = sync::Arc::new(ServerMetrics::default());
+        let owned = sync::Arc::new(owned);
+        if let Err(e) = standalone::serve(listener, owned, serve_tls, serve_key, metrics) {
+            tracing::error!(error = %e, "Stopped serving the partition");
+        }
+    });
+
+    let dial = move |endpoint: &Address| -> io::Result<Peer> {
+        let security = Security {
+            tls: tls.as_ref(),
+            key: key.as_ref(),
+        };
+        let stream = security.connect(endpoint)?;
+        let input = stream.try_clone()?;
+        let address = stream.client_address()?;
+        Ok(sync::Arc::new(RemoteSpace::new(input, stream, address)))
+    };
+    sync::Arc::new(Partitioned::new(space, partition.key, dial))
+}
+
+// Answers the server's requests in the background and takes the links of new predecessors,
+// which connect once a node before this one has been evicted. Returns the connection to the
+// server and the outcome of serving it, which arrives once the node is evicted.
+fn administer(
+    local: Listener,
+    control: Stream,
+    space: sync::Arc<Linda<Stream>>,
+    tls: Option<TlsConfig>,
+    key: Option<SharedKey>,
+) -> (
+    sync::Arc<sync::Mutex<Stream>>,
+    sync::mpsc::Receiver<Result<Departure, LindaError>>,
+) {
+    let (link_tls, link_key) = (tls.clone(), key.clone());
+    let links = space.clone();
+    thread::spawn(move || {
+        let security = Security {
+            tls: link_tls.as_ref(),
+            key: link_key.as_ref(),
+        };
+        loop {
+            match local.accept().and_then(|stream| security.accept(stream)) {
+                Ok(stream) => {
+                    let identity = String::from(stream.peer_identity().unwrap_or(ANONYMOUS));
+                    if links.relink_input(stream, identity).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => tracing::warn!(error = %e, "Rejected link from a new predecessor"),
             }
+        }
+    });
+
+    let input = match control.try_clone() {
+        Ok(input) => input,
+        Err(e) => error(&format!(
+            "Failed to share the connection to the server! {e}"
+        )),
+    };
+    let control = sync::Arc::new(sync::Mutex::new(control));
+    let (evicted, outcome) = sync::mpsc::channel();
+    let writer = control.clone();
+    thread::spawn(move || {
+        let security = Security {
+            tls: tls.as_ref(),
+            key: key.as_ref(),
         };
+        let result = admin::serve_node(input, &writer, &space, |next| security.connect(next));
+        match &result {
+            Ok(_) => tracing::info!("Left the ring - the tuple space is closed"),
+            Err(e) => tracing::debug!(error = e.describe(), "Lost the connection to the server"),
+        }
+        let _ = evicted.send(result);
+    });
+    (control, outcome)
+}
 
-        println!("Got command: {:?}", command);
+// Asks the server to close the ring behind this node, which evicts it. Without the server the
+// node still hands off its tuples, but its predecessor is left linked to it.
+fn depart(
+    control: &sync::Arc<sync::Mutex<Stream>>,
+    evicted: &sync::mpsc::Receiver<Result<Departure, LindaError>>,
+    address: Address,
+) -> Option<Departure> {
+    let outcome = admin::depart(control, address).and_then(|()| {
+        evicted
+            .recv_timeout(DEPARTURE_TIMEOUT)
+            .unwrap_or_else(|_| Err(LindaError::ServerFailure(String::from(NO_DEPARTURE))))
+    });
+    match outcome {
+        Ok(departure) => Some(departure),
+        Err(e) => {
+            tracing::warn!(
+                error = e.describe(),
+                "Leaving without the server closing the ring"
+            );
+            None
+        }
+    }
+}
 
-        match match command {
-            Command::Exit => break,
-            Command::Help => {
-                println!("Under construction!");
-                break;
+fn client_loop(space: &dyn TupleSpace, address: Address, mode: Mode, format: Format) -> Status {
+    match mode {
+        Mode::Interactive => {
+            interactive(space, address, format);
+            Status::Exit
+        }
+        Mode::Script(path) => match fs::File::open(&path) {
+            Ok(file) => run_script(space, io::BufReader::new(file), format),
+            Err(e) => error(&format!("Failed to open script {path}! {e}")),
+        },
+        Mode::Batch => run_script(space, io::stdin().lock(), format),
+    }
+}
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct CommandHelper;
+
+impl Completer for CommandHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(help::complete(line, pos))
+    }
+}
+
+fn history_path() -> Option<path::PathBuf> {
+    match env::var_os("LINDA_HISTORY") {
+        Some(path) => Some(path::PathBuf::from(path)),
+        None => env::var_os("HOME").map(|home| path::Path::new(&home).join(".linda_history")),
+    }
+}
+
+fn interactive(space: &dyn TupleSpace, address: Address, format: Format) {
+    let mut editor = match Editor::<CommandHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(e) => error(&format!("Failed to initialize line editor! {e}")),
+    };
+    editor.set_helper(Some(CommandHelper));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        if let Err(e) = editor.load_history(path) {
+            if !matches!(e, ReadlineError::Io(ref e) if e.kind() == io::ErrorKind::NotFound) {
+                tracing::warn!(error = %e, "Failed to load history from {}", path.display());
             }
-            Command::In(tuple, timeout) => {
-                linda.input(tuple, time::Duration::from_secs(timeout as u64))
+        }
+    }
+
+    let prompt = format!("linda@{address}> ");
+    loop {
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to read command");
+                break;
             }
-            Command::Inp(tuple) => linda.inp(&tuple),
-            Command::Out(tuple) => {
-                if let Err(e) = linda.out(tuple) {
-                    eprintln!("{e:?}");
-                }
-                continue;
+        };
+
+        let mut parser = Parser::new(&line);
+        if parser.at_end() {
+            continue;
+        }
+        if let Err(e) = editor.add_history_entry(&line) {
+            tracing::warn!(error = %e, "Failed to add command to history");
+        }
+
+        let command = parser.parse();
+        if let Status::Exit = run_command(space, format, None, &line, command) {
+            break;
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            tracing::warn!(error = %e, "Failed to save history to {}", path.display());
+        }
+    }
+}
+
+fn run_script(space: &dyn TupleSpace, input: impl io::BufRead, format: Format) -> Status {
+    for line in Script::new(input) {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => error(&format!("Failed to read script! {e}")),
+        };
+
+        match run_command(space, format, Some(line.number), &line.text, line.command) {
+            Status::Continue => (),
+            status => return status,
+        }
+    }
+
+    Status::Exit
+}
+
+fn run_command(
+    space: &dyn TupleSpace,
+    format: Format,
+    line: Option<usize>,
+    input: &str,
+    command: Result<Command, ParseError>,
+) -> Status {
+    let start = time::Instant::now();
+    let mut record = Record {
+        line,
+        input,
+        command: None,
+        outcome: Outcome::Done,
+        elapsed: time::Duration::ZERO,
+    };
+
+    let command = match command {
+        Ok(command) => command,
+        Err(e) => {
+            record.outcome = Outcome::Invalid(&e);
+            report(format, record);
+            return Status::Failed;
+        }
+    };
+    record.command = Some(command.name());
+
+    let fails_on_missing = matches!(command, Command::In(..) | Command::Rd(..));
+    match command {
+        Command::Exit => Status::Exit,
+        Command::Stats => {
+            let stats = space.stats();
+            record.outcome = match &stats {
+                Some(stats) => Outcome::Stats(stats),
+                None => Outcome::Text(NO_STATS),
+            };
+            report(format, record);
+            Status::Continue
+        }
+        Command::Ls(template) => {
+            let result = space.list(template.as_ref());
+            record.elapsed = start.elapsed();
+            record.outcome = match &result {
+                Ok(tuples) => Outcome::Tuples(tuples),
+                Err(e) => Outcome::Failed(e),
+            };
+            report(format, record);
+            status(&result)
+        }
+        Command::Count(template) => {
+            let result = space.count(template.as_ref());
+            record.elapsed = start.elapsed();
+            record.outcome = match &result {
+                Ok(count) => Outcome::Count(*count),
+                Err(e) => Outcome::Failed(e),
+            };
+            report(format, record);
+            status(&result)
+        }
+        command @ (Command::Dump(_) | Command::Load(_)) => {
+            let result = transfer(space, command);
+            record.elapsed = start.elapsed();
+            record.outcome = match &result {
+                Ok(text) => Outcome::Text(text),
+                Err(e) => Outcome::Failed(e),
+            };
+            report(format, record);
+            status(&result)
+        }
+        Command::Help(topic) => {
+            let text = help::help(topic.as_deref());
+            record.outcome = Outcome::Text(&text);
+            report(format, record);
+            Status::Continue
+        }
+        command => {
+            let result = execute(space, command);
+            record.elapsed = start.elapsed();
+            record.outcome = match &result {
+                Ok(Some(tuple)) => Outcome::Tuple(tuple),
+                Ok(None) => Outcome::Done,
+                Err(e) => Outcome::Failed(e),
+            };
+            report(format, record);
+
+            match result {
+                Err(LindaError::NoTuple { .. }) if !fails_on_missing => Status::Continue,
+                Err(_) => Status::Failed,
+                Ok(_) => Status::Continue,
             }
-            Command::Rd(tuple, timeout) => {
-                linda.read(tuple, time::Duration::from_secs(timeout as u64))
+        }
+    }
+}
+
+// Dumps copies of the local tuples into a file, or puts the tuples of a dump back out.
+fn transfer(space: &dyn TupleSpace, command: Command) -> Result<String, LindaError> {
+    match command {
+        Command::Dump(path) => {
+            let tuples = space.list(None)?;
+            script::dump(&path, &tuples)?;
+            Ok(format!("Dumped {} tuples to {path}", tuples.len()))
+        }
+        Command::Load(path) => {
+            let tuples = script::load(&path)?;
+            let count = tuples.len();
+            for tuple in tuples {
+                space.out(tuple)?;
             }
-            Command::Rdp(tuple) => linda.rdp(&tuple),
-        } {
-            Ok(tuple) => println!("Received: {tuple:?}"),
-            Err(e) => eprintln!("Error: {e:?}"),
-        };
+            Ok(format!("Loaded {count} tuples from {path}"))
+        }
+        _ => Ok(String::new()),
     }
 }
 
-fn get_command() -> Result<Command, &'static str> {
-    let mut input = String::new();
-    match io::stdin().read_line(&mut input) {
-        Ok(_) => {
-            let mut parser = Parser::new(&input);
-            parser.parse()
+fn status<T>(result: &Result<T, LindaError>) -> Status {
+    match result {
+        Ok(_) => Status::Continue,
+        Err(_) => Status::Failed,
+    }
+}
+
+fn report(format: Format, record: Record) {
+    if format == Format::Json {
+        println!("{}", record.to_json());
+        return;
+    }
+
+    let prefix = match record.line {
+        Some(line) => format!("line {line}: {} => ", record.input),
+        None => String::new(),
+    };
+    match record.outcome {
+        Outcome::Done if record.line.is_some() => println!("{prefix}ok"),
+        Outcome::Done => (),
+        Outcome::Tuple(tuple) if record.line.is_some() => println!("{prefix}{tuple}"),
+        Outcome::Tuple(tuple) => println!("Received: {tuple}"),
+        Outcome::Tuples(tuples) => {
+            for tuple in tuples {
+                println!("{prefix}{tuple}");
+            }
         }
-        Err(_) => Err("Failed to read command - try again!"),
+        Outcome::Count(count) => println!("{prefix}{count}"),
+        Outcome::Text(text) => println!("{text}"),
+        Outcome::Stats(stats) => println!("{stats}"),
+        Outcome::Failed(e) => eprintln!("{prefix}Error: {}", e.describe()),
+        Outcome::Invalid(e) => match record.line {
+            Some(line) => eprintln!("line {line}: {e}"),
+            None => eprintln!("{e}"),
+        },
+    }
+}
+
+fn execute(space: &dyn TupleSpace, command: Command) -> Result<Option<Tuple<Value>>, LindaError> {
+    match command {
+        Command::In(tuple, Timeout::After(timeout)) => space.input(tuple, timeout).map(Some),
+        Command::In(tuple, Timeout::Forever) => space.input_blocking(tuple).map(Some),
+        Command::Inp(tuple) => space.inp(&tuple).map(Some),
+        Command::Out(tuple) => space.out(tuple).map(|_| None),
+        Command::Rd(tuple, Timeout::After(timeout)) => space.read(tuple, timeout).map(Some),
+        Command::Rd(tuple, Timeout::Forever) => space.read_blocking(tuple).map(Some),
+        Command::Rdp(tuple) => space.rdp(&tuple).map(Some),
+        Command::Ls(_)
+        | Command::Count(_)
+        | Command::Dump(_)
+        | Command::Load(_)
+        | Command::Stats
+        | Command::Help(_)
+        | Command::Exit => Ok(None),
     }
 }
@@ -1,91 +1,350 @@
-use linda::{message::*, tuple::*, utils::*};
-use std::{env, net};
+use linda::{
+    admin::{self, Ring},
+    auth::{self, SharedKey},
+    exporter, logging,
+    message::*,
+    standalone,
+    stats::ServerMetrics,
+    tls::{self, TlsConfig},
+    transport::{Address, Listener, Stream, Transport},
+    utils::*,
+    LocalSpace,
+};
+use std::{env, net, sync, thread, time};
+
+// Clients register one after another, so a peer that stalls during registration must not
+// hold up the ones behind it for long.
+const HANDSHAKE_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+const NO_ADMIN: &str = "Only a ring server has a control socket; use a client instead!";
+
+enum Mode {
+    Ring(usize),
+    Standalone,
+}
+
+struct Options {
+    mode: Mode,
+    address: Address,
+    tls: Option<TlsConfig>,
+    key: Option<SharedKey>,
+    metrics: Option<net::SocketAddr>,
+    admin: Option<Address>,
+}
 
 fn main() {
-    let num_clients = init();
-    println!("Starting server for {num_clients} clients");
-    let clients = collect_clients(num_clients);
-    send_connection_info(&clients);
+    let options = init();
+    let (tls, key) = (options.tls.as_ref(), options.key.as_ref());
+    let metrics = sync::Arc::new(ServerMetrics::default());
+    match options.mode {
+        Mode::Ring(num_clients) => {
+            let snapshot = metrics.clone();
+            serve_metrics(options.metrics, move || {
+                exporter::server(&snapshot.snapshot(None))
+            });
+            tracing::info!("Starting server for {num_clients} clients");
+            // Binding first reports a taken control address before any client joins.
+            let control = options.admin.as_ref().map(bind_control);
+            let clients = collect_clients(&options.address, num_clients, tls, key, &metrics);
+            let controls = send_connection_info(&clients, tls, key);
+            // The ring lives as long as the server, so that nodes can leave it without the
+            // operator.
+            let ring = Ring::new(options.address.clone(), controls, metrics);
+            if let Some(listener) = control {
+                let ring = ring.clone();
+                thread::spawn(move || {
+                    if let Err(e) = admin::serve(listener, ring) {
+                        tracing::error!(error = %e, "Control socket failed");
+                    }
+                });
+            }
+            // The server stays up for the operator to manage the ring and scrape its metrics
+            // until every node has left.
+            tracing::info!("Ring is complete - serving until the last node leaves");
+            if let Err(e) = ring.wait_until_empty() {
+                error(&format!("Lost track of the ring! {}", e.describe()));
+            }
+            tracing::info!("Every node left the ring - stopping");
+        }
+        Mode::Standalone => run_standalone(&options, metrics),
+    }
+}
+
+fn serve_metrics(
+    address: Option<net::SocketAddr>,
+    render: impl Fn() -> String + Send + Sync + 'static,
+) {
+    if let Some(address) = address {
+        if let Err(e) = exporter::spawn(address, render) {
+            error(&format!("Failed to serve metrics at {address}! {e}"));
+        }
+    }
+}
+
+fn bind_control(address: &Address) -> Listener {
+    let listener = match Listener::bind(address) {
+        Ok(listener) => listener,
+        Err(e) => error(&format!("Bind to control address {address} failed! {e}")),
+    };
+    tracing::info!("Control socket at {address} - try 'help'");
+
+    listener
 }
 
-fn init() -> usize {
+fn init() -> Options {
     let mut args = env::args();
     let prog_name = args.next().unwrap();
-    match args.next() {
-        Some(val) => match val.parse() {
-            Ok(val) => val,
-            Err(_) => {
-                error(&format!(
-                    "Expected positive integer as first argument, got: {val}"
-                ));
-            }
+    let usage = format!(
+        "Usage:\n{prog_name} [--transport tcp|unix] [--address $ADDRESS] \
+         [--cert $FILE --key $FILE --ca $FILE] [--secret $FILE] [--log $FILTER] [--metrics $ADDRESS] \
+         [--admin $ADDRESS] ($NUMBER_OF_CLIENTS | --standalone)"
+    );
+
+    let mut mode = None;
+    let mut transport = Transport::Tcp;
+    let mut address = None;
+    let (mut cert, mut key, mut ca) = (None, None, None);
+    let mut secret = None;
+    let mut log = None;
+    let mut metrics = None;
+    let mut admin = None;
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--standalone" => mode = Some(Mode::Standalone),
+            "--transport" => match args.next().map(|val| val.parse()) {
+                Some(Ok(val)) => transport = val,
+                Some(Err(e)) => error(e),
+                None => error(&usage),
+            },
+            "--address" => match args.next() {
+                Some(val) => address = Some(val),
+                None => error(&usage),
+            },
+            "--cert" => match args.next() {
+                Some(val) => cert = Some(val),
+                None => error(&usage),
+            },
+            "--key" => match args.next() {
+                Some(val) => key = Some(val),
+                None => error(&usage),
+            },
+            "--ca" => match args.next() {
+                Some(val) => ca = Some(val),
+                None => error(&usage),
+            },
+            "--secret" => match args.next() {
+                Some(val) => secret = Some(val),
+                None => error(&usage),
+            },
+            "--log" => match args.next() {
+                Some(val) => log = Some(val),
+                None => error(&usage),
+            },
+            "--metrics" => match args.next().map(|val| val.parse()) {
+                Some(Ok(val)) => metrics = Some(val),
+                Some(Err(e)) => error(&format!("Incorrect metrics address! {e}")),
+                None => error(&usage),
+            },
+            "--admin" => match args.next() {
+                Some(val) => admin = Some(val),
+                None => error(&usage),
+            },
+            _ if mode.is_none() => match arg.parse() {
+                Ok(val) => mode = Some(Mode::Ring(val)),
+                Err(_) => error(&format!(
+                    "Expected positive integer as first argument, got: {arg}"
+                )),
+            },
+            _ => error(&usage),
+        }
+    }
+
+    if let Err(e) = logging::init(log.as_deref()) {
+        error(&e);
+    }
+    let mode = match mode {
+        Some(mode) => mode,
+        None => error(&usage),
+    };
+    let address = match address {
+        Some(address) => match transport.parse_address(&address) {
+            Ok(address) => address,
+            Err(e) => error(&e),
         },
-        None => error(&format!("Usage:\n{prog_name} $NUMBER_OF_CLIENTS")),
+        None => transport.server_address(),
+    };
+    // Only ring nodes take requests from the server.
+    let admin = match (admin, &mode) {
+        (Some(admin), Mode::Ring(_)) => match transport.parse_address(&admin) {
+            Ok(admin) => Some(admin),
+            Err(e) => error(&e),
+        },
+        (Some(_), Mode::Standalone) => error(NO_ADMIN),
+        (None, _) => None,
+    };
+    let tls = match TlsConfig::from_flags(cert, key, ca) {
+        Ok(tls) => tls,
+        Err(e) => error(&e),
+    };
+    let key = match SharedKey::from_flag(secret) {
+        Ok(key) => key,
+        Err(e) => error(&e),
+    };
+
+    Options {
+        mode,
+        address,
+        tls,
+        key,
+        metrics,
+        admin,
+    }
+}
+
+fn bind(address: &Address) -> Listener {
+    let listener = match Listener::bind(address) {
+        Ok(val) => val,
+        Err(e) => error(&format!("Bind to local address {address} failed! {e}")),
+    };
+    tracing::info!("Listening at {}", listener.local_address().unwrap());
+
+    listener
+}
+
+fn run_standalone(options: &Options, metrics: sync::Arc<ServerMetrics>) {
+    tracing::info!("Starting standalone tuple space server");
+    let space = sync::Arc::new(LocalSpace::new());
+    let (snapshot, tuples) = (metrics.clone(), space.clone());
+    serve_metrics(options.metrics, move || {
+        exporter::server(&snapshot.snapshot(Some(tuples.len())))
+    });
+
+    let listener = bind(&options.address);
+    let (tls, key) = (options.tls.clone(), options.key.clone());
+    if let Err(e) = standalone::serve(listener, space, tls, key, metrics) {
+        error(&format!("Server failed! {e}"));
     }
 }
 
-fn get_port(stream: &mut net::TcpStream) -> Result<u16, String> {
-    let incorrect_msg_error = Err(String::from("No port in incoming message - skipping!"));
+fn get_address(stream: &mut Stream) -> Result<Address, String> {
     match Message::recv(stream) {
         Ok(msg) => match msg.tuple {
-            MessageType::Value(tuple) => match tuple.first() {
-                Some(Value::Int(Some(val))) => Ok(*val as u16),
-                _ => incorrect_msg_error,
-            },
-            _ => incorrect_msg_error,
+            MessageType::Value(_) => Ok(msg.node),
+            _ => Err(String::from("No address in incoming message - skipping!")),
         },
-        Err(e) => Err(String::from(format!(
-            "Failed to receive port - skipping: {e:?}!",
+        Err(e) => Err(format!(
+            "Failed to receive address - skipping: {}!",
+            e.describe()
+        )),
+    }
+}
+
+// The server dials every client back at the address it registers with, so over TCP only the
+// port the client listens at is taken from its report and the host is the one it connected
+// from. A Unix client connects from an unnamed socket and can only be known by the path it
+// reports.
+fn registered_address(stream: &Stream, reported: Address) -> Result<Address, String> {
+    match (stream.peer_address(), reported) {
+        (Ok(Address::Tcp(peer)), Address::Tcp(reported)) => Ok(Address::Tcp(net::SocketAddr::new(
+            peer.ip(),
+            reported.port(),
         ))),
+        #[cfg(unix)]
+        (Err(e), Address::Unix(path)) if e.kind() == std::io::ErrorKind::AddrNotAvailable => {
+            if path.is_absolute() {
+                Ok(Address::Unix(path))
+            } else {
+                Err(String::from(
+                    "Anonymous Unix peer reported no socket path - skipping!",
+                ))
+            }
+        }
+        (Ok(peer), reported) => Err(format!("Peer {peer} registered as {reported} - skipping!")),
+        (Err(e), _) => Err(format!("Failed to obtain peer address - skipping: {e}!")),
     }
 }
 
-fn collect_clients(num: usize) -> Vec<net::SocketAddr> {
-    let localhost = net::SocketAddrV4::new(net::Ipv4Addr::LOCALHOST, SERVER_PORT);
-    let listener = match net::TcpListener::bind(localhost) {
-        Ok(val) => val,
-        Err(e) => error(&format!("Bind to local address {localhost} failed! {e}")),
-    };
-    println!("Listening at {}", listener.local_addr().unwrap());
+fn collect_clients(
+    address: &Address,
+    num: usize,
+    tls: Option<&TlsConfig>,
+    key: Option<&SharedKey>,
+    metrics: &ServerMetrics,
+) -> Vec<Address> {
+    let listener = bind(address);
 
-    let mut clients = Vec::new();
-    clients.reserve(num);
+    let mut clients = Vec::with_capacity(num);
     let mut index = 0;
 
     while index < num {
         match listener.accept() {
-            Ok((mut stream, addr)) => {
-                index += 1;
-                let port = match get_port(&mut stream) {
-                    Ok(p) => p,
+            Ok(stream) => {
+                if let Err(e) = stream.set_timeout(Some(HANDSHAKE_TIMEOUT)) {
+                    metrics.failed();
+                    tracing::warn!(error = %e, "Failed to set handshake deadline - skipping client");
+                    continue;
+                }
+                // Only peers that registered successfully take up one of the places.
+                let stream = match tls::accept(tls, stream) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        metrics.rejected();
+                        tracing::warn!(error = %e, "TLS handshake failed - skipping client");
+                        continue;
+                    }
+                };
+                let mut stream = match auth::accept(key, stream) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        metrics.rejected();
+                        tracing::warn!(error = %e, "Rejected client");
+                        continue;
+                    }
+                };
+                let client_addr = match get_address(&mut stream)
+                    .and_then(|reported| registered_address(&stream, reported))
+                {
+                    Ok(addr) => addr,
                     Err(e) => {
-                        eprintln!("{e}");
+                        metrics.rejected();
+                        tracing::warn!("{e}");
                         continue;
                     }
                 };
-                let client_addr = net::SocketAddr::new(addr.ip(), port);
-                println!("[{index}/{num}] Adding client {client_addr}.");
+                index += 1;
+                metrics.received();
+                metrics.joined();
+                tracing::info!(client = %client_addr, "[{index}/{num}] Adding client");
                 clients.push(client_addr);
             }
-            Err(e) => eprintln!("Incoming connection failed - skipping client! {e}"),
+            Err(e) => {
+                metrics.failed();
+                tracing::warn!(error = %e, "Incoming connection failed - skipping client")
+            }
         }
     }
 
     clients
 }
 
-fn send_connection_info(clients: &[net::SocketAddr]) {
+// Returns the connections to the clients, which carry the server's requests from now on.
+fn send_connection_info(
+    clients: &[Address],
+    tls: Option<&TlsConfig>,
+    key: Option<&SharedKey>,
+) -> Vec<(Address, Stream, Stream)> {
+    let mut controls = Vec::with_capacity(clients.len());
     if clients.is_empty() {
-        return;
+        return controls;
     }
 
     let mut next = clients.iter();
-    if let None = next.next() {
+    if next.next().is_none() {
         next = clients.iter();
     }
 
     for addr in clients.iter() {
-        let mut stream = match net::TcpStream::connect(addr) {
+        let mut stream = match tls::connect(tls, addr).and_then(|stream| auth::connect(key, stream))
+        {
             Ok(val) => val,
             Err(e) => error(&format!("Connection to client {addr} failed - {e}!")),
         };
@@ -94,8 +353,17 @@ fn send_connection_info(clients: &[net::SocketAddr]) {
             Some(addr) => addr,
             None => clients.first().unwrap(),
         };
-        if let Err(e) = Message::from_ip(next_ip.clone()).send(&mut stream) {
-            error(&format!("Write to client {addr} failed - {e:?}!"));
+        if let Err(e) = Message::from_address(next_ip.clone()).send(&mut stream) {
+            error(&format!(
+                "Write to client {addr} failed - {}!",
+                e.describe()
+            ));
+        }
+        match stream.try_clone() {
+            Ok(input) => controls.push((addr.clone(), input, stream)),
+            Err(e) => error(&format!("Failed to clone stream to client {addr}! {e}")),
         }
     }
+
+    controls
 }
@@ -0,0 +1,318 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, Read, Write},
+    net, sync, thread, time,
+};
+
+use crate::stats::{Histogram, ServerStats, Stats, LATENCY_BOUNDS};
+
+const METRICS_PATH: &str = "/metrics";
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+// A scrape has this long to send its request, which is never larger than this.
+const READ_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+const MAX_REQUEST: u64 = 8 * 1024;
+
+// Metrics in the Prometheus text exposition format.
+#[derive(Default)]
+pub struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {name} {help}");
+        let _ = writeln!(self.text, "# TYPE {name} {kind}");
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, value: u64) {
+        self.header(name, "counter", help);
+        let _ = writeln!(self.text, "{name} {value}");
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: u64) {
+        self.header(name, "gauge", help);
+        let _ = writeln!(self.text, "{name} {value}");
+    }
+
+    // One counter split by the values of a single label.
+    pub fn labelled_counter(
+        &mut self,
+        name: &str,
+        help: &str,
+        label: &str,
+        values: &[(&str, u64)],
+    ) {
+        self.header(name, "counter", help);
+        for (label_value, value) in values {
+            let _ = writeln!(self.text, "{name}{{{label}=\"{label_value}\"}} {value}");
+        }
+    }
+
+    // Prometheus buckets are cumulative and measured in seconds.
+    pub fn histogram(&mut self, name: &str, help: &str, histogram: &Histogram) {
+        self.header(name, "histogram", help);
+        let mut count = 0;
+        for (i, observed) in histogram.buckets.iter().enumerate() {
+            count += observed;
+            let bound = match LATENCY_BOUNDS.get(i) {
+                Some(bound) => bound.as_secs_f64().to_string(),
+                None => String::from("+Inf"),
+            };
+            let _ = writeln!(self.text, "{name}_bucket{{le=\"{bound}\"}} {count}");
+        }
+        let _ = writeln!(self.text, "{name}_sum {}", histogram.sum.as_secs_f64());
+        let _ = writeln!(self.text, "{name}_count {count}");
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+pub fn node(stats: &Stats) -> String {
+    let mut exposition = Exposition::default();
+    exposition.gauge(
+        "linda_local_tuples",
+        "Tuples held by this node.",
+        stats.local_tuples as u64,
+    );
+    exposition.counter(
+        "linda_messages_received_total",
+        "Messages received from the previous node.",
+        stats.received,
+    );
+    exposition.counter(
+        "linda_messages_forwarded_total",
+        "Messages passed on unchanged to the next node.",
+        stats.forwarded,
+    );
+    exposition.labelled_counter(
+        "linda_requests_satisfied_total",
+        "Operations of this node that found a tuple, by where it was stored.",
+        "source",
+        &[
+            ("local", stats.satisfied_locally),
+            ("remote", stats.satisfied_remotely),
+        ],
+    );
+    exposition.counter(
+        "linda_request_timeouts_total",
+        "Operations of this node that timed out.",
+        stats.timeouts,
+    );
+    exposition.histogram(
+        "linda_input_duration_seconds",
+        "Time taken by successful in operations.",
+        &stats.input_latency,
+    );
+    exposition.counter(
+        "linda_errors_total",
+        "Messages that could not be received, handled or passed on.",
+        stats.errors,
+    );
+    exposition.counter(
+        "linda_ring_departures_total",
+        "Nodes this node saw leave the ring.",
+        stats.departures,
+    );
+    exposition.labelled_counter(
+        "linda_tuples_placed_total",
+        "Tuples this node sent to another node to store, by why it did.",
+        "reason",
+        &[("placement", stats.placed), ("rebalance", stats.rebalanced)],
+    );
+    exposition.counter(
+        "linda_operations_routed_total",
+        "Operations sent straight to the node owning their key.",
+        stats.routed,
+    );
+
+    exposition.finish()
+}
+
+pub fn server(stats: &ServerStats) -> String {
+    let mut exposition = Exposition::default();
+    exposition.gauge(
+        "linda_server_members",
+        "Nodes registered for the ring or clients connected to the standalone server.",
+        stats.members,
+    );
+    exposition.counter(
+        "linda_server_rejected_total",
+        "Peers that failed the handshake or the registration.",
+        stats.rejected,
+    );
+    exposition.counter(
+        "linda_messages_received_total",
+        "Messages received from nodes or clients.",
+        stats.received,
+    );
+    exposition.counter(
+        "linda_errors_total",
+        "Connections or messages the server failed to handle.",
+        stats.errors,
+    );
+    if let Some(tuples) = stats.tuples {
+        exposition.gauge(
+            "linda_local_tuples",
+            "Tuples held by the standalone server.",
+            tuples as u64,
+        );
+    }
+
+    exposition.finish()
+}
+
+// Answers every scrape of `/metrics` with freshly rendered metrics, each connection on its own
+// thread so that a slow scraper holds up no other.
+pub fn serve(
+    listener: net::TcpListener,
+    render: impl Fn() -> String + Send + Sync + 'static,
+) -> io::Result<()> {
+    let render = sync::Arc::new(render);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!(error = %e, "Incoming metrics connection failed - skipping");
+                continue;
+            }
+        };
+        let render = render.clone();
+        thread::spawn(move || {
+            if let Err(e) = respond(stream, &*render) {
+                tracing::warn!(error = %e, "Failed to answer metrics request");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// Binds the metrics listener and serves it in the background; returns the bound address.
+pub fn spawn(
+    address: net::SocketAddr,
+    render: impl Fn() -> String + Send + Sync + 'static,
+) -> io::Result<net::SocketAddr> {
+    let listener = net::TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+    tracing::info!("Serving metrics at http://{address}{METRICS_PATH}");
+    thread::spawn(move || {
+        if let Err(e) = serve(listener, render) {
+            tracing::error!(error = %e, "Metrics listener failed");
+        }
+    });
+
+    Ok(address)
+}
+
+fn respond(mut stream: net::TcpStream, render: &impl Fn() -> String) -> io::Result<()> {
+    let request = Request {
+        stream: stream.try_clone()?,
+        deadline: time::Instant::now() + READ_TIMEOUT,
+    };
+    let mut reader = io::BufReader::new(request.take(MAX_REQUEST));
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The headers are of no interest, but a client may wait until they have been read.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(METRICS_PATH)) => ("200 OK", render()),
+        (Some("GET"), _) => ("404 Not Found", format!("Metrics are at {METRICS_PATH}\n")),
+        _ => (
+            "405 Method Not Allowed",
+            String::from("Only GET is supported\n"),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+// Reads the request of a scrape, giving up once the whole request has taken too long.
+struct Request {
+    stream: net::TcpStream,
+    deadline: time::Instant,
+}
+
+impl Read for Request {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self
+            .deadline
+            .saturating_duration_since(time::Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn scrape(address: net::SocketAddr, path: &str) -> String {
+        let mut stream = net::TcpStream::connect(address).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn expose_histogram() {
+        let mut buckets = vec![0; LATENCY_BOUNDS.len() + 1];
+        buckets[0] = 2;
+        buckets[LATENCY_BOUNDS.len()] = 1;
+        let stats = Stats {
+            input_latency: Histogram {
+                buckets,
+                sum: time::Duration::from_millis(6001),
+            },
+            ..Stats::default()
+        };
+
+        let text = node(&stats);
+        assert!(text.contains("# TYPE linda_input_duration_seconds histogram\n"));
+        assert!(text.contains("linda_input_duration_seconds_bucket{le=\"0.001\"} 2\n"));
+        assert!(text.contains("linda_input_duration_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(text.contains("linda_input_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("linda_input_duration_seconds_sum 6.001\n"));
+        assert!(text.contains("linda_requests_satisfied_total{source=\"remote\"} 0\n"));
+    }
+
+    #[test]
+    fn scrape_metrics() {
+        let stats = ServerStats {
+            members: 3,
+            tuples: Some(5),
+            ..ServerStats::default()
+        };
+        let address = spawn(net::SocketAddr::from(([127, 0, 0, 1], 0)), move || {
+            server(&stats)
+        })
+        .unwrap();
+
+        // A scraper that never sends its request holds up no other.
+        let _idle = net::TcpStream::connect(address).unwrap();
+        let response = scrape(address, METRICS_PATH);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.ends_with("linda_local_tuples 5\n"));
+        assert!(response.contains("\nlinda_server_members 3\n"));
+
+        assert!(scrape(address, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
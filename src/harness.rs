@@ -0,0 +1,960 @@
+use std::{collections, io, mem, net, sync, time};
+
+use crate::{
+    acl::{Access, Policy},
+    balance::Balance,
+    message::Message,
+    transport::Address,
+    tuple::Serializable,
+    utils::read_le_usize,
+    Linda,
+};
+
+const WAIT_LIMIT: time::Duration = time::Duration::from_secs(5);
+const SIZE_LENGTH: usize = mem::size_of::<usize>();
+
+#[derive(Default)]
+struct Link {
+    partial: Vec<u8>,
+    in_flight: collections::VecDeque<Vec<u8>>,
+    readable: collections::VecDeque<u8>,
+    reader_waiting: bool,
+    // Once every writer is gone and the last frame has been read, the reader sees the end.
+    writers: usize,
+}
+
+#[derive(Default)]
+struct State {
+    links: Vec<Link>,
+    closed: bool,
+}
+
+type Shared = sync::Arc<(sync::Mutex<State>, sync::Condvar)>;
+
+pub struct LinkWriter {
+    shared: Shared,
+    link: usize,
+}
+
+pub struct LinkReader {
+    shared: Shared,
+    link: usize,
+}
+
+// Link `i` carries frames from node `i` to node `i + 1`. Every frame written to a link stays
+// in flight until the test delivers or drops it, so delays and reordering are expressed by
+// delivering other frames first.
+pub struct Network {
+    shared: Shared,
+    size: usize,
+}
+
+pub fn address(node: usize) -> Address {
+    Address::Tcp(net::SocketAddr::from(([127, 0, 0, 1], 1000 + node as u16)))
+}
+
+// Values and members the tests of every module build on.
+#[cfg(test)]
+mod fixtures {
+    use std::net;
+
+    use super::address;
+    use crate::{
+        balance::Member,
+        transport::Address,
+        tuple::{ComparisonOperator, Request, Tuple, Value},
+    };
+
+    // Where the node serves its partition.
+    pub(crate) fn endpoint(node: usize) -> Address {
+        Address::Tcp(net::SocketAddr::from(([127, 0, 0, 1], 2000 + node as u16)))
+    }
+
+    pub(crate) fn tuple(i: i32) -> Tuple<Value> {
+        Tuple::from_vec(vec![Value::int(i)])
+    }
+
+    pub(crate) fn template(i: i32) -> Tuple<Request> {
+        Tuple::from_vec(vec![Request::new(Value::int(i), ComparisonOperator::EQ)])
+    }
+
+    pub(crate) fn any_int() -> Tuple<Request> {
+        Tuple::from_vec(vec![Request::new(
+            Value::Int(None),
+            ComparisonOperator::ANY,
+        )])
+    }
+
+    // Members at the addresses of the harness nodes, with the given loads.
+    pub(crate) fn members(loads: &[usize]) -> Vec<Member> {
+        loads
+            .iter()
+            .enumerate()
+            .map(|(i, load)| Member {
+                address: address(i),
+                load: *load,
+                endpoint: None,
+            })
+            .collect()
+    }
+
+    // Members that each serve their partition at an endpoint of their own.
+    pub(crate) fn partition_members(count: usize) -> Vec<Member> {
+        let mut members = members(&vec![0; count]);
+        for (i, member) in members.iter_mut().enumerate() {
+            member.endpoint = Some(endpoint(i));
+        }
+        members
+    }
+}
+
+#[cfg(test)]
+pub(crate) use fixtures::*;
+
+pub fn ring(size: usize) -> (Network, Vec<Linda<LinkWriter>>) {
+    secured_ring(size, |node| {
+        Access::new(address(node).to_string(), Policy::default())
+    })
+}
+
+pub fn secured_ring(
+    size: usize,
+    access: impl Fn(usize) -> Access,
+) -> (Network, Vec<Linda<LinkWriter>>) {
+    build_ring(size, access, |_| Balance::default())
+}
+
+// The tests take every census themselves, so `balance` should not schedule any.
+pub fn balanced_ring(
+    size: usize,
+    balance: impl Fn(usize) -> Balance,
+) -> (Network, Vec<Linda<LinkWriter>>) {
+    let access = |node| Access::new(address(node).to_string(), Policy::default());
+    build_ring(size, access, balance)
+}
+
+fn build_ring(
+    size: usize,
+    access: impl Fn(usize) -> Access,
+    balance: impl Fn(usize) -> Balance,
+) -> (Network, Vec<Linda<LinkWriter>>) {
+    // Every node has already named itself to its successor.
+    let link = |node| {
+        let mut hello = vec![];
+        Message::hello(address(node)).send(&mut hello).unwrap();
+        Link {
+            readable: hello.into(),
+            writers: 1,
+            ..Link::default()
+        }
+    };
+    let state = State {
+        links: (0..size).map(link).collect(),
+        closed: false,
+    };
+    let shared = sync::Arc::new((sync::Mutex::new(state), sync::Condvar::new()));
+
+    let nodes = (0..size)
+        .map(|node| {
+            let input = LinkReader {
+                shared: shared.clone(),
+                link: (node + size - 1) % size,
+            };
+            let output = LinkWriter {
+                shared: shared.clone(),
+                link: node,
+            };
+            Linda::with_balance(input, output, address(node), access(node), balance(node))
+        })
+        .collect();
+
+    (Network { shared, size }, nodes)
+}
+
+impl io::Write for LinkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (state, changed) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        if state.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+
+        let link = &mut state.links[self.link];
+        link.partial.extend_from_slice(buf);
+        while link.partial.len() >= SIZE_LENGTH {
+            let size = read_le_usize(&mut &link.partial[..]).unwrap();
+            if link.partial.len() < SIZE_LENGTH + size {
+                break;
+            }
+            let rest = link.partial.split_off(SIZE_LENGTH + size);
+            let frame = mem::replace(&mut link.partial, rest);
+            link.in_flight.push_back(frame);
+        }
+        changed.notify_all();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Read for LinkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (state, changed) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        loop {
+            let readable = &mut state.links[self.link].readable;
+            if !readable.is_empty() {
+                let len = buf.len().min(readable.len());
+                for (byte, value) in buf.iter_mut().zip(readable.drain(..len)) {
+                    *byte = value;
+                }
+                return Ok(len);
+            }
+            let link = &state.links[self.link];
+            if state.closed || (link.writers == 0 && link.in_flight.is_empty()) {
+                return Ok(0);
+            }
+            state.links[self.link].reader_waiting = true;
+            changed.notify_all();
+            state = changed.wait(state).unwrap();
+            state.links[self.link].reader_waiting = false;
+        }
+    }
+}
+
+impl Drop for LinkWriter {
+    fn drop(&mut self) {
+        let (state, changed) = &*self.shared;
+        if let Ok(mut state) = state.lock() {
+            state.links[self.link].writers -= 1;
+        }
+        changed.notify_all();
+    }
+}
+
+// A node that left the ring no longer reads, which counts as having handled its input.
+impl Drop for LinkReader {
+    fn drop(&mut self) {
+        let (state, changed) = &*self.shared;
+        if let Ok(mut state) = state.lock() {
+            state.links[self.link].reader_waiting = true;
+        }
+        changed.notify_all();
+    }
+}
+
+impl Network {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn in_flight(&self, link: usize) -> usize {
+        self.shared.0.lock().unwrap().links[link].in_flight.len()
+    }
+
+    pub fn wait_for_frames(&self, link: usize, count: usize) {
+        let (state, changed) = &*self.shared;
+        let deadline = time::Instant::now() + WAIT_LIMIT;
+        let mut state = state.lock().unwrap();
+        while state.links[link].in_flight.len() < count {
+            let now = time::Instant::now();
+            assert!(now < deadline, "expected {count} frames on link {link}");
+            state = changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    pub fn peek(&self, link: usize, index: usize) -> Message {
+        let state = self.shared.0.lock().unwrap();
+        let frame = &state.links[link].in_flight[index];
+        Message::from_bytes(&mut &frame[SIZE_LENGTH..]).unwrap()
+    }
+
+    // Delivers the `index`-th frame in flight on `link` and waits until the receiving node
+    // has handled it and asks for more input.
+    pub fn deliver(&self, link: usize, index: usize) -> Message {
+        let (state, changed) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        let frame = state.links[link].in_flight.remove(index).unwrap();
+        state.links[link].readable.extend(frame.iter());
+        state.links[link].reader_waiting = false;
+        changed.notify_all();
+
+        let deadline = time::Instant::now() + WAIT_LIMIT;
+        while !state.links[link].reader_waiting || !state.links[link].readable.is_empty() {
+            let now = time::Instant::now();
+            assert!(
+                now < deadline,
+                "node behind link {link} did not handle the frame"
+            );
+            state = changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+
+        Message::from_bytes(&mut &frame[SIZE_LENGTH..]).unwrap()
+    }
+
+    pub fn drop_frame(&self, link: usize, index: usize) -> Message {
+        let mut state = self.shared.0.lock().unwrap();
+        let frame = state.links[link].in_flight.remove(index).unwrap();
+
+        Message::from_bytes(&mut &frame[SIZE_LENGTH..]).unwrap()
+    }
+
+    // Waits for the next frame on `link` and delivers it.
+    pub fn hop(&self, link: usize) -> Message {
+        self.wait_for_frames(link, 1);
+        self.deliver(link, 0)
+    }
+
+    // Connects to a node by its address the way a relinked predecessor does, writing to the
+    // link the node reads.
+    pub fn dialer(&self) -> impl Fn(&Address) -> io::Result<LinkWriter> + Send + 'static {
+        let (shared, size) = (self.shared.clone(), self.size);
+        move |target| match (0..size).find(|&node| address(node) == *target) {
+            Some(node) => {
+                let link = (node + size - 1) % size;
+                shared.0.lock().unwrap().links[link].writers += 1;
+                Ok(LinkWriter {
+                    shared: shared.clone(),
+                    link,
+                })
+            }
+            None => Err(io::ErrorKind::ConnectionRefused.into()),
+        }
+    }
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        let (state, changed) = &*self.shared;
+        if let Ok(mut state) = state.lock() {
+            state.closed = true;
+        }
+        changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{
+        acl::Right,
+        balance::{Member, Placement},
+        message::MessageType,
+        partition::{Partition, Partitioned, Peer},
+        tuple::*,
+        LindaError, TupleSpace,
+    };
+
+    #[test]
+    fn tuple_returns_to_origin() {
+        let (network, nodes) = ring(3);
+        nodes[0].out(tuple(1)).unwrap();
+
+        for link in 0..network.size() {
+            let msg = network.hop(link);
+            assert_eq!(msg, Message::value(tuple(1), address(0)));
+        }
+
+        assert_eq!(network.in_flight(0), 0);
+        assert_eq!(nodes[0].rdp(&any_int()).unwrap(), tuple(1));
+        assert!(matches!(
+            nodes[1].inp(&any_int()),
+            Err(LindaError::NoTuple { .. })
+        ));
+    }
+
+    #[test]
+    fn request_travels_to_holder() {
+        let (network, mut nodes) = ring(3);
+        nodes[2].out(tuple(7)).unwrap();
+        network.hop(2);
+        network.hop(0);
+        network.hop(1);
+
+        let requester = nodes.remove(0);
+        let handle = thread::spawn(move || requester.input(any_int(), WAIT_LIMIT));
+
+        network.wait_for_frames(0, 1);
+        let request = network.peek(0, 0);
+        assert_eq!(request.node, address(0));
+        assert!(matches!(request.tuple, MessageType::Request(..)));
+        network.hop(0);
+        network.hop(1);
+
+        let reply = network.hop(2);
+        assert_eq!(reply, Message::value(tuple(7), address(2)));
+        assert_eq!(handle.join().unwrap().unwrap(), tuple(7));
+
+        let holder = &nodes[1];
+        assert!(matches!(
+            holder.inp(&any_int()),
+            Err(LindaError::NoTuple { .. })
+        ));
+    }
+
+    #[test]
+    fn dropped_request_times_out() {
+        let (network, mut nodes) = ring(2);
+        nodes[1].out(tuple(3)).unwrap();
+        network.hop(1);
+        network.hop(0);
+
+        let requester = nodes.remove(0);
+        let holder = nodes.remove(0);
+        let handle =
+            thread::spawn(move || requester.input(any_int(), time::Duration::from_millis(100)));
+
+        network.wait_for_frames(0, 1);
+        assert!(matches!(
+            network.drop_frame(0, 0).tuple,
+            MessageType::Request(..)
+        ));
+        assert!(matches!(
+            handle.join().unwrap(),
+            Err(LindaError::Timeout { .. })
+        ));
+        assert_eq!(holder.inp(&any_int()).unwrap(), tuple(3));
+    }
+
+    #[test]
+    fn reordered_delivery() {
+        let (network, nodes) = ring(2);
+        nodes[0].out(tuple(1)).unwrap();
+        nodes[0].out(tuple(2)).unwrap();
+        network.wait_for_frames(0, 2);
+
+        // Hold back the first tuple until the second one went around the ring.
+        assert_eq!(network.deliver(0, 1), Message::value(tuple(2), address(0)));
+        network.hop(1);
+        network.hop(0);
+        network.hop(1);
+
+        let first = nodes[0].inp(&any_int()).unwrap();
+        assert_eq!(first, tuple(2));
+        assert_eq!(nodes[0].inp(&any_int()).unwrap(), tuple(1));
+    }
+
+    #[test]
+    fn denied_request_returns_to_requester() {
+        let policy: Policy = "\
+scheduler in,out (string: \"job\", int: *)
+* rd (string: \"job\", int: *)"
+            .parse()
+            .unwrap();
+        let identities = ["scheduler", "worker"];
+        let (network, mut nodes) = secured_ring(2, |node| Access {
+            predecessor: String::from(identities[1 - node]),
+            ..Access::new(String::from(identities[node]), policy.clone())
+        });
+        let job = Tuple::from_vec(vec![Value::string(String::from("job")), Value::int(1)]);
+        let any_job = Tuple::from_vec(vec![
+            Request::new(Value::string(String::from("job")), ComparisonOperator::EQ),
+            Request::new(Value::Int(None), ComparisonOperator::ANY),
+        ]);
+        nodes[0].out(job.clone()).unwrap();
+        network.hop(0);
+        network.hop(1);
+
+        let worker = nodes.remove(1);
+        assert!(matches!(
+            worker.out(job.clone()),
+            Err(LindaError::AccessDenied(_))
+        ));
+
+        let template = any_job.clone();
+        let handle = thread::spawn(move || (worker.input(template, WAIT_LIMIT), worker));
+        let MessageType::Request(_, right, identity) = network.hop(1).tuple else {
+            panic!("expected a request");
+        };
+        assert_eq!((right, identity), (Right::In, None));
+        assert!(matches!(network.hop(0).tuple, MessageType::Denied(_)));
+        let MessageType::Request(_, _, identity) = network.hop(0).tuple else {
+            panic!("expected a request");
+        };
+        assert_eq!(identity.as_deref(), Some("worker"));
+        let (result, worker) = handle.join().unwrap();
+        assert!(matches!(result, Err(LindaError::AccessDenied(identity)) if identity == "worker"));
+
+        // Reading leaves the tuple with its holder, so the reader cannot take it afterwards.
+        let template = any_job.clone();
+        let handle = thread::spawn(move || (worker.read(template, WAIT_LIMIT), worker));
+        network.hop(1);
+        assert_eq!(network.hop(0), Message::copy(job.clone(), address(1)));
+        let (result, worker) = handle.join().unwrap();
+        assert_eq!(result.unwrap(), job);
+        assert!(matches!(
+            worker.inp(&any_job),
+            Err(LindaError::NoTuple { .. })
+        ));
+        assert_eq!(nodes[0].rdp(&any_job).unwrap(), job);
+    }
+
+    // A holder refusing the request does not end the search for a tuple the requester may take.
+    #[test]
+    fn denied_holder_is_skipped() {
+        let guarded: Policy = "\
+scheduler in (string: \"job\", int: *)
+* out,rd (string: \"job\", int: *)"
+            .parse()
+            .unwrap();
+        let (network, nodes) = secured_ring(3, |node| {
+            let policy = if node == 1 {
+                guarded.clone()
+            } else {
+                Policy::default()
+            };
+            Access::new(String::from("worker"), policy)
+        });
+        let job = |id| Tuple::from_vec(vec![Value::string(String::from("job")), Value::int(id)]);
+        for (node, id) in [(1, 1), (2, 2)] {
+            nodes[node].out(job(id)).unwrap();
+            for link in 0..network.size() {
+                network.hop((node + link) % network.size());
+            }
+        }
+        let any_job = Tuple::from_vec(vec![
+            Request::new(Value::string(String::from("job")), ComparisonOperator::EQ),
+            Request::new(Value::Int(None), ComparisonOperator::ANY),
+        ]);
+
+        let requester = &nodes[0];
+        thread::scope(|scope| {
+            let handle = scope.spawn(|| requester.input(any_job.clone(), WAIT_LIMIT));
+            network.hop(0);
+            assert!(matches!(network.hop(1).tuple, MessageType::Denied(_)));
+            assert!(matches!(network.hop(1).tuple, MessageType::Request(..)));
+            network.hop(2);
+            assert_eq!(network.hop(2), Message::value(job(2), address(2)));
+            assert_eq!(handle.join().unwrap().unwrap(), job(2));
+        });
+        assert_eq!(nodes[1].rdp(&any_job).unwrap(), job(1));
+    }
+
+    // The holder judges a request by the identity its link authenticated, whatever the
+    // requester claims to be.
+    #[test]
+    fn requests_go_by_the_linked_identity() {
+        let policy: Policy = "scheduler in,out,rd (int: *)".parse().unwrap();
+        let (network, nodes) = secured_ring(2, |_| Access {
+            predecessor: String::from("worker"),
+            ..Access::new(String::from("scheduler"), policy.clone())
+        });
+        nodes[0].out(tuple(1)).unwrap();
+        network.hop(0);
+        network.hop(1);
+
+        let claimant = &nodes[1];
+        thread::scope(|scope| {
+            let handle = scope.spawn(|| claimant.input(any_int(), WAIT_LIMIT));
+            network.hop(1);
+            assert!(matches!(network.hop(0).tuple, MessageType::Denied(_)));
+            network.hop(0);
+            assert!(matches!(
+                handle.join().unwrap(),
+                Err(LindaError::AccessDenied(_))
+            ));
+        });
+        assert_eq!(nodes[0].rdp(&any_int()).unwrap(), tuple(1));
+    }
+
+    // A request its origin stamped itself is judged by the identity of the link it came over.
+    #[test]
+    fn forged_identity_is_replaced() {
+        let policy: Policy = "scheduler in,out,rd (int: *)".parse().unwrap();
+        let (network, nodes) = secured_ring(2, |_| Access {
+            predecessor: String::from("worker"),
+            ..Access::new(String::from("scheduler"), policy.clone())
+        });
+        nodes[0].out(tuple(1)).unwrap();
+        network.hop(0);
+        network.hop(1);
+
+        let forger = &nodes[1];
+        thread::scope(|scope| {
+            let handle = scope.spawn(|| forger.input(any_int(), WAIT_LIMIT));
+            network.wait_for_frames(1, 1);
+            network.drop_frame(1, 0);
+            let mut link = network.dialer()(&address(0)).unwrap();
+            let scheduler = Some(String::from("scheduler"));
+            Message::request(any_int(), Right::In, scheduler, address(1))
+                .send(&mut link)
+                .unwrap();
+            drop(link);
+            network.hop(1);
+            assert!(matches!(network.hop(0).tuple, MessageType::Denied(_)));
+            let MessageType::Request(_, _, identity) = network.hop(0).tuple else {
+                panic!("expected a request");
+            };
+            assert_eq!(identity.as_deref(), Some("worker"));
+            assert!(matches!(
+                handle.join().unwrap(),
+                Err(LindaError::AccessDenied(_))
+            ));
+        });
+        assert_eq!(nodes[0].rdp(&any_int()).unwrap(), tuple(1));
+    }
+
+    #[test]
+    fn shutdown_hands_off_tuples() {
+        let (network, mut nodes) = ring(3);
+        nodes[0].out(tuple(5)).unwrap();
+        for link in 0..network.size() {
+            network.hop(link);
+        }
+
+        let leaving = nodes.remove(0);
+        let handle = thread::spawn(move || (leaving.shutdown(), leaving));
+        network.wait_for_frames(0, 2);
+        assert_eq!(network.hop(0), Message::handoff(tuple(5), address(0)));
+        assert_eq!(network.hop(0), Message::leave(address(0)));
+        network.hop(1);
+        network.hop(2);
+        assert_eq!(nodes[0].inp(&any_int()).unwrap(), tuple(5));
+        // The node relays until the server links its predecessor past it, which leaves a ring
+        // of two over links 1 and 0.
+        assert!(!handle.is_finished());
+        let successor = network.dialer()(&address(1)).unwrap();
+        nodes[1].relink_output(successor).unwrap();
+
+        let (result, leaving) = handle.join().unwrap();
+        result.unwrap();
+        assert!(matches!(leaving.out(tuple(6)), Err(LindaError::ShutDown)));
+        assert!(matches!(leaving.rdp(&any_int()), Err(LindaError::ShutDown)));
+
+        nodes[1].out(tuple(7)).unwrap();
+        assert_eq!(network.hop(0), Message::value(tuple(7), address(2)));
+        network.hop(1);
+        nodes[0].out(tuple(8)).unwrap();
+        network.hop(1);
+        network.hop(0);
+        assert_eq!(network.in_flight(2), 0);
+
+        let requester = nodes.remove(0);
+        assert_eq!(requester.inp(&any_int()).unwrap(), tuple(8));
+        let handle = thread::spawn(move || requester.input(any_int(), WAIT_LIMIT));
+        network.hop(1);
+        assert_eq!(network.hop(0), Message::value(tuple(7), address(2)));
+        assert_eq!(handle.join().unwrap().unwrap(), tuple(7));
+    }
+
+    // Without a ring to take its tuples, shutting down fails for as long as the node holds any.
+    #[test]
+    fn shutdown_fails_again_without_a_ring() {
+        let (network, nodes) = ring(2);
+        nodes[0].out(tuple(1)).unwrap();
+        network.hop(0);
+        network.hop(1);
+        // The predecessor of the first node goes away without anyone taking its place.
+        let elsewhere = network.dialer()(&address(1)).unwrap();
+        nodes[1].relink_output(elsewhere).unwrap();
+
+        let deadline = time::Instant::now() + WAIT_LIMIT;
+        while !nodes[0].disconnected.load(sync::atomic::Ordering::SeqCst) {
+            assert!(
+                time::Instant::now() < deadline,
+                "predecessor is still linked"
+            );
+            std::thread::yield_now();
+        }
+
+        assert!(matches!(
+            nodes[0].shutdown(),
+            Err(LindaError::WorkerDisconnected)
+        ));
+        assert!(matches!(
+            nodes[0].shutdown(),
+            Err(LindaError::WorkerDisconnected)
+        ));
+    }
+
+    #[test]
+    fn stats_count_traffic() {
+        let (network, mut nodes) = ring(2);
+        nodes[0].out(tuple(1)).unwrap();
+        network.hop(0);
+        network.hop(1);
+        nodes[0].out(tuple(2)).unwrap();
+        network.hop(0);
+        network.hop(1);
+        assert_eq!(nodes[0].inp(&any_int()).unwrap(), tuple(1));
+
+        let requester = nodes.remove(1);
+        let handle = thread::spawn(move || (requester.input(any_int(), WAIT_LIMIT), requester));
+        network.hop(1);
+        network.hop(0);
+        let (result, requester) = handle.join().unwrap();
+        assert_eq!(result.unwrap(), tuple(2));
+
+        let holder = nodes[0].stats().unwrap();
+        assert_eq!(
+            (
+                holder.local_tuples,
+                holder.forwarded,
+                holder.satisfied_locally
+            ),
+            (0, 0, 1)
+        );
+        let requester = requester.stats().unwrap();
+        assert_eq!((requester.forwarded, requester.satisfied_remotely), (2, 1));
+        assert_eq!(requester.input_latency.count(), 1);
+    }
+
+    #[test]
+    fn list_without_taking() {
+        let (network, nodes) = ring(2);
+        for i in 1..=2 {
+            nodes[0].out(tuple(i)).unwrap();
+            network.hop(0);
+            network.hop(1);
+        }
+        let two = Tuple::from_vec(vec![Request::new(Value::int(2), ComparisonOperator::EQ)]);
+
+        assert_eq!(nodes[0].list(None).unwrap(), vec![tuple(1), tuple(2)]);
+        assert_eq!(nodes[0].list(Some(&two)).unwrap(), vec![tuple(2)]);
+        assert_eq!(nodes[0].count(Some(&two)).unwrap(), 1);
+        assert_eq!(nodes[1].count(None).unwrap(), 0);
+        assert_eq!(nodes[0].stats().unwrap().local_tuples, 2);
+    }
+
+    #[test]
+    fn place_round_robin() {
+        let balance = Balance {
+            placement: Placement::RoundRobin,
+            ..Balance::default()
+        };
+        let (network, nodes) = balanced_ring(3, |_| balance.clone());
+        nodes[0].census().unwrap();
+        network.hop(0);
+        network.hop(1);
+        let census = network.hop(2);
+        let members = (0..3)
+            .map(|node| Member {
+                address: address(node),
+                load: 0,
+                endpoint: None,
+            })
+            .collect();
+        assert_eq!(census, Message::census(members, address(0)));
+
+        // Every tuple goes around once before it moves on to the next member in turn.
+        for (i, target) in [(1, 1), (2, 2), (3, 0)] {
+            nodes[0].out(tuple(i)).unwrap();
+            for link in 0..network.size() {
+                network.hop(link);
+            }
+            for link in 0..target {
+                let msg = network.hop(link);
+                assert_eq!(msg, Message::placed(tuple(i), address(target), address(0)));
+            }
+        }
+        assert_eq!(network.in_flight(0), 0);
+
+        for (node, i) in [(0, 3), (1, 1), (2, 2)] {
+            assert_eq!(nodes[node].list(None).unwrap(), vec![tuple(i)]);
+        }
+        assert_eq!(nodes[0].stats().unwrap().placed, 2);
+    }
+
+    #[test]
+    fn rebalance_surplus() {
+        let balance = Balance {
+            rebalance: true,
+            ..Balance::default()
+        };
+        let (network, nodes) = balanced_ring(3, |_| balance.clone());
+        for i in 1..=6 {
+            nodes[0].out(tuple(i)).unwrap();
+            network.hop(0);
+            network.hop(1);
+            network.hop(2);
+        }
+        nodes[2].out(tuple(7)).unwrap();
+        network.hop(2);
+        network.hop(0);
+        network.hop(1);
+
+        // Node 2 already holds one tuple, so it takes one less than node 1.
+        nodes[0].census().unwrap();
+        network.hop(0);
+        network.hop(1);
+        network.hop(2);
+        for _ in 0..3 {
+            network.hop(0);
+        }
+        network.hop(1);
+
+        let loads: Vec<_> = nodes.iter().map(|node| node.count(None).unwrap()).collect();
+        assert_eq!(loads, vec![3, 2, 2]);
+        assert_eq!(network.in_flight(0) + network.in_flight(1), 0);
+        assert_eq!(nodes[0].stats().unwrap().rebalanced, 3);
+    }
+
+    type PartitionedRing = (
+        Network,
+        Vec<sync::Arc<Linda<LinkWriter>>>,
+        Vec<Partitioned<LinkWriter>>,
+    );
+
+    // A partitioned ring whose members dial each other's tuples in process.
+    fn partitioned_ring(size: usize) -> PartitionedRing {
+        let (network, nodes) = balanced_ring(size, |node| Balance {
+            partition: Some(Partition {
+                key: 0,
+                endpoint: endpoint(node),
+            }),
+            ..Balance::default()
+        });
+        let nodes: Vec<_> = nodes.into_iter().map(sync::Arc::new).collect();
+        let spaces = nodes
+            .iter()
+            .map(|node| {
+                let members = nodes.clone();
+                Partitioned::new(node.clone(), 0, move |target: &Address| {
+                    let owner = (0..size).find(|i| endpoint(*i) == *target).unwrap();
+                    Ok(sync::Arc::new(members[owner].partition()) as Peer)
+                })
+            })
+            .collect();
+
+        (network, nodes, spaces)
+    }
+
+    fn census(network: &Network, node: usize) {
+        for link in (0..network.size()).map(|i| (node + i) % network.size()) {
+            network.hop(link);
+        }
+    }
+
+    fn owned_by(node: &Linda<LinkWriter>, owner: usize) -> i32 {
+        (0..)
+            .find(|i| node.owner(&Value::int(*i)).unwrap().address == address(owner))
+            .unwrap()
+    }
+
+    #[test]
+    fn route_to_owner() {
+        let (network, nodes, spaces) = partitioned_ring(3);
+        for (i, node) in nodes.iter().enumerate() {
+            node.census().unwrap();
+            census(&network, i);
+        }
+        let (one, two) = (owned_by(&nodes[0], 1), owned_by(&nodes[0], 2));
+
+        // Keyed tuples and templates skip the ring altogether.
+        spaces[0].out(tuple(one)).unwrap();
+        assert_eq!(nodes[1].list(None).unwrap(), vec![tuple(one)]);
+        assert_eq!(spaces[2].rdp(&template(one)).unwrap(), tuple(one));
+        assert_eq!(
+            spaces[0].input(template(one), WAIT_LIMIT).unwrap(),
+            tuple(one)
+        );
+        assert!((0..3).all(|link| network.in_flight(link) == 0));
+        assert_eq!(nodes[0].stats().unwrap().routed, 2);
+
+        // A tuple that reaches its owner around the ring wakes up operations waiting there.
+        let handle = thread::scope(|scope| {
+            let waiting = scope.spawn(|| spaces[0].input(template(two), WAIT_LIMIT));
+            nodes[1].out(tuple(two)).unwrap();
+            census(&network, 1);
+            assert_eq!(
+                network.hop(1),
+                Message::placed(tuple(two), address(2), address(1))
+            );
+            waiting.join().unwrap()
+        });
+        assert_eq!(handle.unwrap(), tuple(two));
+
+        // Without a bound key the ring is swept as before.
+        spaces[0].out(tuple(one)).unwrap();
+        let handle = thread::scope(|scope| {
+            let sweeping = scope.spawn(|| spaces[0].input(any_int(), WAIT_LIMIT));
+            assert!(matches!(network.hop(0).tuple, MessageType::Request(..)));
+            assert_eq!(network.hop(1), Message::value(tuple(one), address(1)));
+            network.hop(2);
+            sweeping.join().unwrap()
+        });
+        assert_eq!(handle.unwrap(), tuple(one));
+    }
+
+    // Delivers every frame until the ring is quiet.
+    fn settle(network: &Network) {
+        while let Some(link) = (0..network.size()).find(|&link| network.in_flight(link) > 0) {
+            network.deliver(link, 0);
+        }
+    }
+
+    // An owner that is leaving refuses routed operations, which then take the ring to whoever
+    // owns the key from now on.
+    #[test]
+    fn out_during_shutdown() {
+        let (network, nodes, spaces) = partitioned_ring(3);
+        for (i, node) in nodes.iter().enumerate() {
+            node.census().unwrap();
+            census(&network, i);
+        }
+        let one = owned_by(&nodes[0], 1);
+        spaces[0].out(tuple(one)).unwrap();
+
+        let leaving = nodes[1].clone();
+        let withdrawing = thread::spawn(move || leaving.withdraw());
+        network.wait_for_frames(1, 2);
+        assert_eq!(network.peek(1, 0), Message::handoff(tuple(one), address(1)));
+        assert_eq!(
+            nodes[0].owner(&Value::int(one)).unwrap().address,
+            address(1)
+        );
+        spaces[0].out(tuple(one)).unwrap();
+        assert_ne!(
+            nodes[0].owner(&Value::int(one)).unwrap().address,
+            address(1)
+        );
+        assert!(matches!(
+            spaces[1].rdp(&template(one)),
+            Err(LindaError::ShutDown)
+        ));
+
+        settle(&network);
+        withdrawing.join().unwrap().unwrap();
+        assert_ne!(
+            nodes[2].owner(&Value::int(one)).unwrap().address,
+            address(1)
+        );
+        let held: Vec<_> = [0, 2]
+            .iter()
+            .flat_map(|&node| nodes[node].list(None).unwrap())
+            .collect();
+        assert_eq!(held, vec![tuple(one), tuple(one)]);
+    }
+
+    #[test]
+    fn rehome_after_census() {
+        let (network, nodes, spaces) = partitioned_ring(2);
+        // Before the first census nobody is known to own anything.
+        for i in 0..4 {
+            spaces[0].out(tuple(i)).unwrap();
+            census(&network, 0);
+        }
+        assert_eq!(nodes[0].count(None).unwrap(), 4);
+
+        nodes[0].census().unwrap();
+        census(&network, 0);
+        let strays: Vec<_> = (0..4)
+            .filter(|i| nodes[0].owner(&Value::int(*i)).unwrap().address == address(1))
+            .collect();
+        assert!(!strays.is_empty() && strays.len() < 4);
+        for stray in &strays {
+            assert_eq!(
+                network.hop(0),
+                Message::placed(tuple(*stray), address(1), address(0))
+            );
+        }
+        assert_eq!(nodes[1].count(None).unwrap(), strays.len());
+        assert_eq!(nodes[0].count(None).unwrap(), 4 - strays.len());
+        assert_eq!(nodes[0].stats().unwrap().rebalanced, strays.len() as u64);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::utils::COMMANDS;
+
+const TEMPLATE_COMMANDS: [&str; 8] = ["in", "rd", "read", "inp", "rdp", "readp", "ls", "count"];
+pub const TYPE_NAMES: [&str; 3] = ["int", "float", "string"];
+
+const OVERVIEW: &str = "\
+Commands:
+  out TUPLE              put TUPLE into the tuple space
+  in TEMPLATE [TIMEOUT]  remove a tuple matching TEMPLATE, waiting up to TIMEOUT
+  rd TEMPLATE [TIMEOUT]  copy a tuple matching TEMPLATE, waiting up to TIMEOUT
+  inp TEMPLATE           remove a tuple matching TEMPLATE if one is stored locally
+  rdp TEMPLATE           copy a tuple matching TEMPLATE if one is stored locally
+  ls [TEMPLATE]          list the tuples stored locally, or those matching TEMPLATE
+  count [TEMPLATE]       count the tuples stored locally, or those matching TEMPLATE
+  dump FILE              write the tuples stored locally to FILE
+  load FILE              put the tuples from a dump FILE into the tuple space
+  stats                  show the counters of this node
+  help [COMMAND]         show this overview or the syntax of COMMAND
+  exit                   leave the client
+
+Everything after '#' up to the end of the line is a comment.
+Type 'help out' for the tuple syntax and 'help in' for the template syntax.";
+
+const TUPLE: &str = "\
+TUPLE is a parenthesized, comma separated list of values, e.g. (1, 2.5, \"x\").
+  int     decimal (-42, 1_000), hexadecimal (0x2A), octal (0o52) or binary (0b101010)
+          literal between -2147483648 and 2147483647
+  float   literal with a fraction or an exponent (2.5, .5, 4., 1e-3), inf or nan
+  string  double quoted with escapes \\n \\r \\t \\0 \\\\ \\\" \\' \\u{1F600},
+          or single quoted without escapes ('C:\\temp')
+  TYPE: * a value of TYPE without contents, e.g. string: *";
+
+const TEMPLATE: &str = "\
+TEMPLATE is a parenthesized, comma separated list of fields, e.g. (int: >= 3, string: *).
+Each field is TYPE: [OPERATOR] VALUE or TYPE: *, where
+  TYPE      is one of int, float or string
+  OPERATOR  is one of == != < <= > >= and defaults to ==; a field matches
+            a tuple value v when VALUE OPERATOR v holds
+  *         matches any value of TYPE
+VALUE uses the same syntax as values in tuples (see 'help out').";
+
+const TIMEOUT: &str = "\
+TIMEOUT is a duration such as 500ms, 2s, 1m or 1h (a bare number means seconds)
+or forever; without TIMEOUT the command waits until a matching tuple appears.";
+
+pub fn help(topic: Option<&str>) -> String {
+    match topic {
+        Some("out") => format!("out TUPLE\nPuts TUPLE into the tuple space.\n\n{TUPLE}"),
+        Some("in") => format!(
+            "in TEMPLATE [TIMEOUT]\nRemoves a tuple matching TEMPLATE from the tuple space.\n\n\
+             {TEMPLATE}\n{TIMEOUT}"
+        ),
+        Some("rd") => format!(
+            "rd TEMPLATE [TIMEOUT]\nReads a tuple matching TEMPLATE without removing it.\n\n\
+             {TEMPLATE}\n{TIMEOUT}"
+        ),
+        Some("inp") => format!(
+            "inp TEMPLATE\nRemoves a tuple matching TEMPLATE if this node holds one.\n\n{TEMPLATE}"
+        ),
+        Some("rdp") => format!(
+            "rdp TEMPLATE\nReads a tuple matching TEMPLATE if this node holds one.\n\n{TEMPLATE}"
+        ),
+        Some("ls") => format!(
+            "ls [TEMPLATE]\nLists the tuples this node holds without removing them, only those \
+             matching TEMPLATE if given.\n\n{TEMPLATE}"
+        ),
+        Some("count") => format!(
+            "count [TEMPLATE]\nCounts the tuples this node holds, only those matching TEMPLATE \
+             if given.\n\n{TEMPLATE}"
+        ),
+        Some("dump") => String::from(
+            "dump FILE\nWrites the tuples this node holds to FILE as 'out' commands, which 'load' \
+             or --script can read back. FILE needs quotes if it contains spaces.",
+        ),
+        Some("load") => String::from(
+            "load FILE\nPuts every tuple of FILE into the tuple space. FILE may only contain \
+             'out' commands and comments, like the files written by 'dump'.",
+        ),
+        Some("stats") => String::from(
+            "stats\nShows how many tuples this node holds, how many messages it passed on, how \
+             many requests were satisfied locally or by other nodes, how many timed out and how \
+             long 'in' took.",
+        ),
+        Some("help") => String::from("help [COMMAND]\nShows an overview or the syntax of COMMAND."),
+        Some("exit") => String::from("exit\nLeaves the client."),
+        _ => String::from(OVERVIEW),
+    }
+}
+
+pub fn complete(line: &str, pos: usize) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+        .rfind(|c: char| c.is_whitespace() || c == '(' || c == ',')
+        .map_or(0, |i| i + 1);
+    let prefix = before[start..].to_lowercase();
+
+    let words: Vec<String> = before[..start]
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    let candidates: &[&str] = match &words[..] {
+        [] => &COMMANDS,
+        [command] if command == "help" => &COMMANDS,
+        [command, ..] if TEMPLATE_COMMANDS.contains(&&command[..]) => {
+            let field_start = before[..start].trim_end().ends_with(['(', ',']);
+            if field_start {
+                &TYPE_NAMES
+            } else {
+                &[]
+            }
+        }
+        _ => &[],
+    };
+
+    let matches = candidates
+        .iter()
+        .filter(|candidate| candidate.starts_with(&prefix))
+        .map(|candidate| String::from(*candidate))
+        .collect();
+
+    (start, matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_commands() {
+        assert_eq!(
+            complete("i", 1),
+            (0, vec![String::from("in"), String::from("inp")])
+        );
+        assert_eq!(complete("  ex", 4), (2, vec![String::from("exit")]));
+        assert_eq!(
+            complete("help r", 6),
+            (5, vec![String::from("rd"), String::from("rdp")])
+        );
+        assert_eq!(complete("", 0).1.len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn complete_type_names() {
+        assert_eq!(complete("in (i", 5), (4, vec![String::from("int")]));
+        assert_eq!(
+            complete("rdp (int: 1, ", 13),
+            (
+                13,
+                vec![
+                    String::from("int"),
+                    String::from("float"),
+                    String::from("string")
+                ]
+            )
+        );
+        assert_eq!(complete("in (int: s", 10), (9, vec![]));
+        assert_eq!(complete("out (f", 6), (5, vec![]));
+    }
+
+    #[test]
+    fn help_topics() {
+        for command in COMMANDS {
+            assert!(help(Some(command)).starts_with(command));
+        }
+        assert_eq!(help(None), OVERVIEW);
+    }
+}
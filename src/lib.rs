@@ -1,34 +1,149 @@
+use acl::{Access, Policy, Right};
+use balance::{Balance, Balancer, Member};
 use message::{LindaError, Message, MessageType};
-use std::{io, marker, net, sync, thread, time};
+use stats::{Metrics, Monitor, Stats};
+use std::{collections, io, marker, mem, sync, thread, time};
 
+use transport::Address;
 use tuple::*;
 
+pub use space::{LocalSpace, TupleSpace};
+
+extern crate self as linda;
+
+pub mod acl;
+pub mod admin;
+pub mod auth;
+pub mod balance;
+pub mod exporter;
+#[cfg(any(test, feature = "harness"))]
+pub mod harness;
+pub mod help;
+pub mod logging;
 pub mod message;
+pub mod output;
 pub mod parser;
+pub mod partition;
+pub mod remote;
+pub mod script;
+pub mod space;
+pub mod standalone;
+pub mod stats;
+pub mod tls;
+pub mod transport;
 pub mod tuple;
+pub mod typed;
 pub mod utils;
 
 type MessageSender = sync::mpsc::Sender<Message>;
 type MessageRecv = sync::mpsc::Receiver<Message>;
-type ValueSender = sync::mpsc::Sender<Tuple<Value>>;
-type ValueRecv = sync::mpsc::Receiver<Tuple<Value>>;
+type ValueSender = sync::mpsc::Sender<Result<Tuple<Value>, LindaError>>;
+type ValueRecv = sync::mpsc::Receiver<Result<Tuple<Value>, LindaError>>;
 type ArcMutex<T> = sync::Arc<sync::Mutex<T>>;
-type LocalTuples = ArcMutex<Vec<Tuple<Value>>>;
+type LocalTuples = sync::Arc<Tuples>;
+type Flag = sync::Arc<sync::atomic::AtomicBool>;
+type Link = Box<dyn io::Read + marker::Send>;
 
-pub struct Linda<Output> {
-    rx: ValueRecv,
+const SHUTDOWN_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+const SHUTDOWN_POLL: time::Duration = time::Duration::from_millis(10);
+const RING_UNRESPONSIVE: &str = "Ring did not confirm that this node left!";
+const PREDECESSOR_UNRESPONSIVE: &str = "Predecessor kept sending to this node after it left!";
+// How long a worker whose predecessor disconnected waits for a replacement link.
+const RELINK_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+pub struct Linda<Output: io::Write> {
+    // The worker follows one request at a time, so waiting operations take turns.
+    rx: sync::Mutex<ValueRecv>,
     tx: MessageSender,
-    output_stream: ArcMutex<Output>,
+    output_stream: ArcMutex<Successor<Output>>,
     local_tuples: LocalTuples,
-    ip: net::SocketAddr,
+    node: Address,
+    access: sync::Arc<Access>,
+    closed: Flag,
+    left: Flag,
+    disconnected: Flag,
+    links: sync::mpsc::Sender<(Link, String)>,
+    worker: sync::Mutex<Option<thread::JoinHandle<()>>>,
+    metrics: sync::Arc<Metrics>,
+    balancer: sync::Arc<Balancer>,
+    // Dropping it stops the periodic census.
+    surveyor: sync::Mutex<Option<sync::mpsc::Sender<()>>>,
 }
 
-fn print_debug(debug_msg: &str) {
-    if cfg!(debug_assertions) {
-        eprintln!("{debug_msg}");
+// The tuples a node holds. Everyone adding some signals `added`, so operations served straight
+// from the node's own tuples can wait for them.
+#[derive(Debug, Default)]
+pub(crate) struct Tuples {
+    list: sync::Mutex<Vec<Tuple<Value>>>,
+    added: sync::Condvar,
+    // Set under the lock when the node leaves, so that nothing is served from or added to
+    // the tuples once they are being handed off.
+    withdrawn: sync::atomic::AtomicBool,
+}
+
+impl Tuples {
+    fn lock(&self) -> sync::LockResult<sync::MutexGuard<'_, Vec<Tuple<Value>>>> {
+        self.list.lock()
+    }
+
+    fn withdrawn(&self) -> bool {
+        self.withdrawn.load(sync::atomic::Ordering::SeqCst)
+    }
+
+    // Hands the tuples matching `template` that `access` may read to `collect`.
+    fn readable<T>(
+        &self,
+        template: Option<&Tuple<Request>>,
+        access: &Access,
+        collect: impl FnOnce(&mut dyn Iterator<Item = &Tuple<Value>>) -> T,
+    ) -> Result<T, LindaError> {
+        match self.lock() {
+            Ok(guard) => Ok(collect(
+                &mut matching(&guard, template).filter(|tuple| access.allows(Right::Rd, tuple)),
+            )),
+            Err(e) => Err(LindaError::MutexLockFailure(e.to_string())),
+        }
+    }
+
+    // Stops serving the tuples and wakes everyone waiting for one.
+    fn withdraw(&self) {
+        self.withdrawn.store(true, sync::atomic::Ordering::SeqCst);
+        self.added.notify_all();
     }
 }
 
+// The link to the next node. A node that left the ring takes it down once it has nothing
+// more to relay, so the successor moves on to the link from its new predecessor.
+struct Successor<Output>(Option<Output>);
+
+impl<Output: io::Write> io::Write for Successor<Output> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.0 {
+            Some(output) => output.write(buf),
+            None => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.0 {
+            Some(output) => output.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// Logs ring and client traffic with the fields needed to follow a tuple or a request.
+fn trace_message(event: &str, msg: &Message) {
+    tracing::debug!(
+        kind = msg.tuple.name(),
+        origin = %msg.node,
+        id = msg.tuple.id(),
+        tuple = msg.tuple.value().map(tracing::field::display),
+        template = msg.tuple.template().map(tracing::field::display),
+        "{event}"
+    );
+}
+
 fn satisfies(request: &Tuple<Request>, value: &Tuple<Value>) -> bool {
     if request.len() == value.len() {
         for (req, val) in request.iter().zip(value.iter()) {
@@ -43,193 +158,880 @@ fn satisfies(request: &Tuple<Request>, value: &Tuple<Value>) -> bool {
 }
 
 fn send<Output: io::Write>(output: &ArcMutex<Output>, msg: Message) -> Result<(), LindaError> {
-    print_debug(&format!("Sending: {msg:?}"));
+    trace_message("Sending", &msg);
     match output.lock() {
         Ok(mut guard) => msg.send(&mut *guard),
         Err(e) => Err(LindaError::MutexLockFailure(e.to_string())),
     }
 }
 
-fn add_tuple(local_tuples: &LocalTuples, tuple: Tuple<Value>) -> Result<(), LindaError> {
-    match local_tuples.lock() {
-        Ok(mut guard) => Ok(guard.push(tuple)),
-        Err(e) => Err(LindaError::MutexLockFailure(e.to_string())),
+// Keeps a tuple that ended up at this node, unless the node is leaving the ring; then the
+// tuple is passed on. Checking under the lock keeps `shutdown` from missing a tuple.
+fn store<Output: io::Write>(
+    local_tuples: &LocalTuples,
+    closed: &Flag,
+    output: &ArcMutex<Output>,
+    tuple: Tuple<Value>,
+    node: &Address,
+) -> Result<(), LindaError> {
+    let mut guard = match local_tuples.lock() {
+        Ok(guard) => guard,
+        Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+    };
+    if closed.load(sync::atomic::Ordering::SeqCst) {
+        drop(guard);
+        return send(output, Message::handoff(tuple, node.clone()));
     }
+    guard.push(tuple);
+    local_tuples.added.notify_all();
+
+    Ok(())
 }
 
-fn find_tuple(local_tuples: &LocalTuples, request: &Tuple<Request>) -> Option<Tuple<Value>> {
-    match local_tuples.lock() {
-        Ok(mut guard) => {
-            let mut i = 0;
-            for tuple in guard.iter() {
-                if satisfies(request, tuple) {
-                    break;
-                }
-                i += 1;
-            }
-            if i < guard.len() {
-                Some(guard.remove(i))
-            } else {
-                None
-            }
+// Stores a tuple that came back to the node that produced it on the member the placement
+// policy chooses.
+fn place<Output: io::Write>(
+    balancer: &Balancer,
+    local_tuples: &LocalTuples,
+    closed: &Flag,
+    output: &ArcMutex<Output>,
+    tuple: Tuple<Value>,
+    node: &Address,
+    metrics: &Metrics,
+) -> Result<(), LindaError> {
+    match balancer.target(&tuple) {
+        Some(target) if target != *node && !closed.load(sync::atomic::Ordering::SeqCst) => {
+            metrics.placed();
+            send(output, Message::placed(tuple, target, node.clone()))
         }
-        Err(e) => {
-            if cfg!(debug_assertions) {
-                eprintln!("{e}");
-            }
-            None
+        _ => store(local_tuples, closed, output, tuple, node),
+    }
+}
+
+// Hands the tuples that belong elsewhere to their new home: their owner in a partitioned
+// ring, otherwise the members the last census found below their share.
+fn rebalance<Output: io::Write>(
+    balancer: &Balancer,
+    local_tuples: &LocalTuples,
+    output: &ArcMutex<Output>,
+    node: &Address,
+    metrics: &Metrics,
+) -> Result<(), LindaError> {
+    let mut guard = match local_tuples.lock() {
+        Ok(guard) => guard,
+        Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+    };
+    let moving = balancer.moving(&mut guard, node);
+    drop(guard);
+
+    if !moving.is_empty() {
+        tracing::info!(tuples = moving.len(), "Moving tuples to other nodes");
+    }
+    for (tuple, target) in moving {
+        metrics.rebalanced();
+        send(output, Message::placed(tuple, target, node.clone()))?;
+    }
+
+    Ok(())
+}
+
+// This node as the census passing it counts it.
+fn member(local_tuples: &LocalTuples, node: &Address, balancer: &Balancer) -> Member {
+    Member {
+        address: node.clone(),
+        load: local_tuples.lock().map_or(0, |guard| guard.len()),
+        endpoint: balancer.endpoint(),
+    }
+}
+
+// Starts a census of the ring with this node as the first member.
+fn survey<Output: io::Write>(
+    output: &ArcMutex<Output>,
+    local_tuples: &LocalTuples,
+    node: &Address,
+    balancer: &Balancer,
+) -> Result<(), LindaError> {
+    let members = vec![member(local_tuples, node, balancer)];
+    send(output, Message::census(members, node.clone()))
+}
+
+// Passes a message on to the next node unchanged.
+fn forward<Output: io::Write>(output: &ArcMutex<Output>, msg: Message, metrics: &Metrics) {
+    metrics.forwarded();
+    if let Err(e) = send(output, msg) {
+        metrics.failed();
+        tracing::error!(error = e.describe(), "Failed to pass on message");
+    }
+}
+
+// The tuples matching `template` without taking them, or all of them without a template.
+fn matching<'a>(
+    tuples: &'a [Tuple<Value>],
+    template: Option<&'a Tuple<Request>>,
+) -> impl Iterator<Item = &'a Tuple<Value>> {
+    tuples
+        .iter()
+        .filter(move |tuple| template.is_none_or(|template| satisfies(template, tuple)))
+}
+
+// Takes the first matching tuple `identity` has `right` to, or copies it for reading.
+// Matching tuples that are all refused make the lookup fail with `AccessDenied` rather than
+// find nothing.
+fn claim(
+    tuples: &mut Vec<Tuple<Value>>,
+    request: &Tuple<Request>,
+    policy: &Policy,
+    identity: &str,
+    right: Right,
+) -> Result<Option<Tuple<Value>>, LindaError> {
+    let mut denied = false;
+    for i in 0..tuples.len() {
+        if !satisfies(request, &tuples[i]) {
+            continue;
+        }
+        if policy.allows(identity, right, &tuples[i]) {
+            return Ok(Some(match right {
+                Right::In => tuples.remove(i),
+                _ => tuples[i].clone(),
+            }));
         }
+        denied = true;
+    }
+
+    match denied {
+        true => Err(LindaError::AccessDenied(String::from(identity))),
+        false => Ok(None),
     }
 }
 
-fn worker<Input: io::Read, Output: io::Write>(
-    mut input: Input,
-    output: ArcMutex<Output>,
+fn find_tuple(
+    local_tuples: &LocalTuples,
+    request: &Tuple<Request>,
+    policy: &Policy,
+    identity: &str,
+    right: Right,
+    node: &Address,
+) -> Result<Tuple<Value>, LindaError> {
+    let mut guard = match local_tuples.lock() {
+        Ok(guard) => guard,
+        Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+    };
+
+    match claim(&mut guard, request, policy, identity, right)? {
+        Some(tuple) => Ok(tuple),
+        None => Err(LindaError::NoTuple {
+            template: request.clone(),
+            node: Some(node.clone()),
+        }),
+    }
+}
+
+fn answer(tx: &ValueSender, result: Result<Tuple<Value>, LindaError>, metrics: &Metrics) {
+    if let Err(e) = tx.send(result) {
+        metrics.failed();
+        tracing::warn!(error = %e, "Failed to hand result to the waiting operation");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn worker<Output: io::Write>(
+    mut input: Link,
+    links: sync::mpsc::Receiver<(Link, String)>,
+    output: ArcMutex<Successor<Output>>,
     local_tuples: LocalTuples,
     tx: ValueSender,
     rx: MessageRecv,
-    ip: net::SocketAddr,
+    node: Address,
+    access: sync::Arc<Access>,
+    closed: Flag,
+    left: Flag,
+    disconnected: Flag,
+    metrics: sync::Arc<Metrics>,
+    balancer: sync::Arc<Balancer>,
 ) {
-    let mut request = None;
-    loop {
+    let _span = tracing::info_span!("node", address = %node).entered();
+    let mut request: Option<(Tuple<Request>, Right)> = None;
+    // Whether a holder refused the pending request. It fails once it has been around the
+    // ring without anyone answering it.
+    let mut refused = false;
+    // Nodes that left the ring: their tuples are kept by whoever sees them first and their
+    // requests are dropped.
+    let mut departed = collections::HashSet::new();
+    // The identity the current input link authenticated its sender as, and the address the
+    // sender announced on it.
+    let mut predecessor = access.predecessor.clone();
+    let mut previous: Option<Address> = None;
+    'relay: loop {
         let msg = match Message::recv(&mut input) {
             Ok(msg) => msg,
+            // A node that left relays until its predecessor sends past it.
+            Err(LindaError::IoFailure(_)) if left.load(sync::atomic::Ordering::SeqCst) => {
+                tracing::debug!(
+                    "Predecessor stopped sending after this node left - stopping worker"
+                );
+                break;
+            }
+            // An evicted predecessor closes its link once the node before it sends here
+            // directly; everything it relayed has been read by then.
+            Err(LindaError::IoFailure(_))
+                if relinked(&mut input, &mut predecessor, &links, &disconnected) =>
+            {
+                previous = None;
+                continue;
+            }
             Err(e) => {
-                eprintln!("Failed to receive message in worker thread - aborting!");
-                print_debug(&format!("ERROR: {e:?}"));
+                metrics.failed();
+                tracing::error!(
+                    error = e.describe(),
+                    "Failed to receive message - stopping worker"
+                );
                 break;
             }
         };
-        print_debug(&format!("Received: {msg:?}"));
-        match rx.try_recv() {
-            Ok(msg) => match msg.tuple {
-                MessageType::Request(req) => request = Some(req),
-                _ => eprintln!("Wrong message type received! Skipping."),
+        trace_message("Received", &msg);
+        metrics.received();
+        if let MessageType::Hello = msg.tuple {
+            previous = Some(msg.node);
+            continue;
+        }
+        // Requests leave their origin without an identity, so the first node they reach
+        // vouches for whoever sent them over its input link. Whatever identity the origin
+        // claims itself is replaced; until the predecessor names itself, every request is
+        // taken to come from it.
+        let first_hop = previous
+            .as_ref()
+            .is_none_or(|previous| *previous == msg.node);
+        let msg = match msg.tuple {
+            MessageType::Request(template, right, identity)
+                if msg.node != node && (first_hop || identity.is_none()) =>
+            {
+                Message::request(template, right, Some(predecessor.clone()), msg.node)
+            }
+            tuple => Message {
+                tuple,
+                node: msg.node,
             },
-            Err(sync::mpsc::TryRecvError::Disconnected) => {
-                eprintln!("Disconnected from main thread!");
-                break;
+        };
+        loop {
+            match rx.try_recv() {
+                Ok(msg) => match msg.tuple {
+                    MessageType::Request(req, right, _) => {
+                        request = Some((req, right));
+                        refused = false;
+                    }
+                    // The node's own tuples were refused.
+                    MessageType::Denied(_) => refused = true,
+                    _ => {
+                        metrics.failed();
+                        tracing::warn!(kind = msg.tuple.name(), "Unexpected message - skipping")
+                    }
+                },
+                Err(sync::mpsc::TryRecvError::Disconnected) => {
+                    tracing::debug!("Tuple space was dropped - stopping worker");
+                    break 'relay;
+                }
+                Err(sync::mpsc::TryRecvError::Empty) => break,
+            }
+        }
+        tracing::trace!(
+            request = request
+                .as_ref()
+                .map(|(req, _)| tracing::field::display(req)),
+            "Pending request"
+        );
+        // A tuple passing by answers a pending read and goes on its way.
+        if let (
+            Some((req, Right::Rd)),
+            MessageType::Value(val) | MessageType::Handoff(val) | MessageType::Placed(val, _),
+        ) = (&request, &msg.tuple)
+        {
+            if satisfies(req, val) && access.allows(Right::Rd, val) {
+                answer(&tx, Ok(val.clone()), &metrics);
+                request = None;
             }
-            Err(sync::mpsc::TryRecvError::Empty) => (),
         }
-        print_debug(&format!("Request: {request:?}"));
         match msg.tuple {
-            MessageType::Value(val) if msg.ip == ip => {
-                if let Err(e) = add_tuple(&local_tuples, val) {
-                    print_debug(&format!("ERROR: {e:?}"));
+            MessageType::Value(val) if msg.node == node => {
+                if let Err(e) = place(
+                    &balancer,
+                    &local_tuples,
+                    &closed,
+                    &output,
+                    val,
+                    &node,
+                    &metrics,
+                ) {
+                    metrics.failed();
+                    tracing::error!(error = e.describe(), "Failed to store tuple");
                 }
             }
-            MessageType::Value(val) if matches!(request, Some(ref req) if satisfies(&req, &val)) => {
-                if let Err(e) = tx.send(val) {
-                    print_debug(&format!("ERROR: {e}"));
-                }
+            MessageType::Value(val) | MessageType::Handoff(val) | MessageType::Placed(val, _)
+                if matches!(request, Some((ref req, Right::In))
+                    if satisfies(req, &val) && access.allows(Right::In, &val)) =>
+            {
+                answer(&tx, Ok(val), &metrics);
                 request = None;
             }
-            MessageType::Value(_) => {
-                if let Err(e) = send(&output, msg) {
-                    print_debug(&format!("ERROR: {e:?}"));
+            MessageType::Handoff(val) => {
+                if let Err(e) = store(&local_tuples, &closed, &output, val, &node) {
+                    metrics.failed();
+                    tracing::error!(error = e.describe(), "Failed to store tuple");
                 }
             }
-            MessageType::Request(_) if msg.ip == ip => (),
-            MessageType::Request(ref request) => match find_tuple(&local_tuples, request) {
-                Some(value) => {
-                    if let Err(e) = send(&output, Message::value(value, ip)) {
-                        print_debug(&format!("ERROR: {e:?}"));
-                    }
+            MessageType::Value(val) if departed.contains(&msg.node) => {
+                if let Err(e) = store(&local_tuples, &closed, &output, val, &node) {
+                    metrics.failed();
+                    tracing::error!(error = e.describe(), "Failed to store tuple");
                 }
-                None => {
-                    if let Err(e) = send(&output, msg) {
-                        print_debug(&format!("ERROR: {e:?}"));
-                    }
+            }
+            MessageType::Value(_) => forward(&output, msg, &metrics),
+            // Kept by the chosen member, or wherever the tuple is once that member is gone.
+            MessageType::Placed(val, target)
+                if target == node || msg.node == node || departed.contains(&target) =>
+            {
+                if let Err(e) = store(&local_tuples, &closed, &output, val, &node) {
+                    metrics.failed();
+                    tracing::error!(error = e.describe(), "Failed to store tuple");
                 }
-            },
+            }
+            MessageType::Placed(..) => forward(&output, msg, &metrics),
+            // Dropped if nobody waits for it any more.
+            MessageType::Copy(val) if msg.node == node => {
+                if matches!(request, Some((ref req, Right::Rd)) if satisfies(req, &val)) {
+                    answer(&tx, Ok(val), &metrics);
+                    request = None;
+                }
+            }
+            MessageType::Copy(_) if departed.contains(&msg.node) => (),
+            MessageType::Copy(_) => forward(&output, msg, &metrics),
+            MessageType::Census(mut members) if msg.node == node => {
+                if let Some(own) = members.first_mut() {
+                    own.load = local_tuples.lock().map_or(own.load, |guard| guard.len());
+                }
+                balancer.update(members);
+                if closed.load(sync::atomic::Ordering::SeqCst) {
+                    continue;
+                }
+                if let Err(e) = rebalance(&balancer, &local_tuples, &output, &node, &metrics) {
+                    metrics.failed();
+                    tracing::error!(error = e.describe(), "Failed to rebalance tuples");
+                }
+            }
+            MessageType::Census(_) if departed.contains(&msg.node) => (),
+            // A node that left only relays, so it is no longer counted.
+            MessageType::Census(mut members) => {
+                if !closed.load(sync::atomic::Ordering::SeqCst) {
+                    members.push(member(&local_tuples, &node, &balancer));
+                }
+                if let Err(e) = send(&output, Message::census(members, msg.node)) {
+                    metrics.failed();
+                    tracing::error!(error = e.describe(), "Failed to pass on census");
+                }
+            }
+            // Every holder has seen the request by now.
+            MessageType::Request(ref template, ..) if msg.node == node => {
+                if refused && matches!(request, Some((ref req, _)) if req == template) {
+                    answer(
+                        &tx,
+                        Err(LindaError::AccessDenied(access.identity.clone())),
+                        &metrics,
+                    );
+                    request = None;
+                }
+            }
+            MessageType::Request(..) if departed.contains(&msg.node) => (),
+            MessageType::Request(ref template, right, Some(ref identity)) => {
+                let policy = &access.policy;
+                let reply =
+                    match find_tuple(&local_tuples, template, policy, identity, right, &node) {
+                        Ok(value) if right == Right::In => Message::value(value, node.clone()),
+                        Ok(value) => Message::copy(value, msg.node.clone()),
+                        // A node further along the ring may hold a tuple the requester may have.
+                        Err(LindaError::AccessDenied(_)) => {
+                            let denied = Message::denied(template.clone(), msg.node.clone());
+                            if let Err(e) = send(&output, denied) {
+                                metrics.failed();
+                                tracing::error!(error = e.describe(), "Failed to refuse request");
+                            }
+                            forward(&output, msg, &metrics);
+                            continue;
+                        }
+                        Err(_) => {
+                            forward(&output, msg, &metrics);
+                            continue;
+                        }
+                    };
+                if let Err(e) = send(&output, reply) {
+                    metrics.failed();
+                    tracing::error!(error = e.describe(), "Failed to reply to request");
+                }
+            }
+            MessageType::Denied(ref denied) if msg.node == node => {
+                if matches!(request, Some((ref req, _)) if req == denied) {
+                    refused = true;
+                }
+            }
+            MessageType::Denied(_) if departed.contains(&msg.node) => (),
+            MessageType::Denied(_) => forward(&output, msg, &metrics),
+            // The ring no longer sends anything here, except what is still on its way or
+            // arrives before the predecessor is relinked; that is relayed as before.
+            MessageType::Leave if msg.node == node => {
+                left.store(true, sync::atomic::Ordering::SeqCst);
+            }
+            // Keys the node owned go to the others right away rather than after the next
+            // census.
+            MessageType::Leave => {
+                balancer.depart(&msg.node);
+                departed.insert(msg.node.clone());
+                metrics.departed();
+                forward(&output, msg, &metrics);
+            }
+            _ => {
+                metrics.failed();
+                tracing::warn!(kind = msg.tuple.name(), "Unexpected message - skipping")
+            }
+        }
+    }
+
+    if left.load(sync::atomic::Ordering::SeqCst) {
+        if let Ok(mut guard) = output.lock() {
+            guard.0 = None;
         }
     }
 }
 
+fn relinked(
+    input: &mut Link,
+    predecessor: &mut String,
+    links: &sync::mpsc::Receiver<(Link, String)>,
+    disconnected: &Flag,
+) -> bool {
+    disconnected.store(true, sync::atomic::Ordering::SeqCst);
+    match links.recv_timeout(RELINK_TIMEOUT) {
+        Ok((link, identity)) => {
+            tracing::info!("Predecessor disconnected - continuing with the new link");
+            disconnected.store(false, sync::atomic::Ordering::SeqCst);
+            *input = link;
+            *predecessor = identity;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
 impl<Output: 'static + io::Write + marker::Send> Linda<Output> {
     pub fn new<Input: 'static + io::Read + marker::Send>(
         input_stream: Input,
         output_stream: Output,
-        ip: net::SocketAddr,
+        node: Address,
+    ) -> Linda<Output> {
+        let access = Access::new(node.to_string(), Policy::default());
+        Linda::with_access(input_stream, output_stream, node, access)
+    }
+
+    pub fn with_access<Input: 'static + io::Read + marker::Send>(
+        input_stream: Input,
+        output_stream: Output,
+        node: Address,
+        access: Access,
+    ) -> Linda<Output> {
+        Linda::with_balance(
+            input_stream,
+            output_stream,
+            node,
+            access,
+            Balance::default(),
+        )
+    }
+
+    pub fn with_balance<Input: 'static + io::Read + marker::Send>(
+        input_stream: Input,
+        output_stream: Output,
+        node: Address,
+        access: Access,
+        balance: Balance,
     ) -> Linda<Output> {
         let (tx_to_worker, rx_to_worker) = sync::mpsc::channel();
         let (tx_from_worker, rx_from_worker) = sync::mpsc::channel();
+        let (links, links_rx) = sync::mpsc::channel();
 
-        let output_stream = sync::Arc::new(sync::Mutex::new(output_stream));
+        let output_stream = sync::Arc::new(sync::Mutex::new(Successor(Some(output_stream))));
         let os_clone = output_stream.clone();
 
-        let tuples = sync::Arc::new(sync::Mutex::new(vec![]));
+        let tuples = LocalTuples::default();
         let tuples_clone = tuples.clone();
-        thread::spawn(move || {
+        let node_clone = node.clone();
+        let access = sync::Arc::new(access);
+        let access_clone = access.clone();
+        let closed = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+        let closed_clone = closed.clone();
+        let left = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+        let left_clone = left.clone();
+        let disconnected = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+        let disconnected_clone = disconnected.clone();
+        let metrics = sync::Arc::new(Metrics::default());
+        let metrics_clone = metrics.clone();
+        let balancer = sync::Arc::new(Balancer::new(&balance));
+        let balancer_clone = balancer.clone();
+        let worker = thread::spawn(move || {
             worker(
-                input_stream,
+                Box::new(input_stream),
+                links_rx,
                 os_clone,
                 tuples_clone,
                 tx_from_worker,
                 rx_to_worker,
-                ip.clone(),
+                node_clone,
+                access_clone,
+                closed_clone,
+                left_clone,
+                disconnected_clone,
+                metrics_clone,
+                balancer_clone,
             )
         });
+        let surveyor = balance.census.map(|interval| {
+            let (surveyor, stop) = sync::mpsc::channel();
+            let (output, tuples, node) = (output_stream.clone(), tuples.clone(), node.clone());
+            let balancer = balancer.clone();
+            thread::spawn(move || loop {
+                if let Err(e) = survey(&output, &tuples, &node, &balancer) {
+                    tracing::debug!(error = e.describe(), "Failed to start census - stopping");
+                    break;
+                }
+                if stop.recv_timeout(interval) != Err(sync::mpsc::RecvTimeoutError::Timeout) {
+                    break;
+                }
+            });
+            surveyor
+        });
 
         Linda {
-            rx: rx_from_worker,
+            rx: sync::Mutex::new(rx_from_worker),
             tx: tx_to_worker,
             output_stream,
             local_tuples: tuples,
-            ip,
+            node,
+            access,
+            closed,
+            left,
+            disconnected,
+            links,
+            worker: sync::Mutex::new(Some(worker)),
+            metrics,
+            balancer,
+            surveyor: sync::Mutex::new(surveyor),
         }
     }
 
-    pub fn out(&self, tuple: Tuple<Value>) -> Result<(), LindaError> {
-        let msg = Message::value(tuple, self.ip.clone());
-        send(&self.output_stream, msg)
+    fn take(&self, tuple: &Tuple<Request>, right: Right) -> Result<Tuple<Value>, LindaError> {
+        self.check_open()?;
+        let identity = &self.access.identity;
+        let policy = &self.access.policy;
+        let tuple = find_tuple(
+            &self.local_tuples,
+            tuple,
+            policy,
+            identity,
+            right,
+            &self.node,
+        )?;
+        self.metrics.satisfied_locally();
+
+        Ok(tuple)
     }
 
-    pub fn input(
+    fn wait_for(
         &self,
         tuple: Tuple<Request>,
-        timeout: time::Duration,
+        timeout: Option<time::Duration>,
+        right: Right,
     ) -> Result<Tuple<Value>, LindaError> {
-        if let Ok(tuple) = self.inp(&tuple) {
-            return Ok(tuple);
-        }
+        let start = time::Instant::now();
+        // Another node may still hold a tuple this one is allowed to have.
+        let refused = match self.take(&tuple, right) {
+            Ok(tuple) => return Ok(tuple),
+            Err(LindaError::AccessDenied(_)) => true,
+            Err(_) => false,
+        };
 
-        let msg = Message::request(tuple, self.ip.clone());
+        let rx = match self.rx.lock() {
+            Ok(guard) => guard,
+            Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+        };
+        let msg = Message::request(tuple.clone(), right, None, self.node.clone());
         if let Err(e) = self.tx.send(msg.clone()) {
             return Err(LindaError::ChannelSendFailure(e));
         }
+        if refused {
+            let denied = Message::denied(tuple.clone(), self.node.clone());
+            if let Err(e) = self.tx.send(denied) {
+                return Err(LindaError::ChannelSendFailure(e));
+            }
+        }
 
         send(&self.output_stream, msg)?;
 
-        match self.rx.recv_timeout(timeout) {
-            Ok(tuple) => Ok(tuple),
-            Err(_) => Err(LindaError::Timeout),
+        let result = match timeout {
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(result) => result,
+                Err(sync::mpsc::RecvTimeoutError::Timeout) => {
+                    self.metrics.timed_out();
+                    Err(LindaError::Timeout {
+                        template: tuple,
+                        node: Some(self.node.clone()),
+                        elapsed: start.elapsed(),
+                    })
+                }
+                Err(sync::mpsc::RecvTimeoutError::Disconnected) => {
+                    Err(LindaError::WorkerDisconnected)
+                }
+            },
+            None => match rx.recv() {
+                Ok(result) => result,
+                Err(_) => Err(LindaError::WorkerDisconnected),
+            },
+        };
+        if result.is_ok() {
+            self.metrics.satisfied_remotely();
         }
+
+        result
     }
 
-    pub fn read(
+    // Like `wait_for`, but records how long taking the tuple took.
+    fn take_waiting(
         &self,
         tuple: Tuple<Request>,
-        timeout: time::Duration,
+        timeout: Option<time::Duration>,
     ) -> Result<Tuple<Value>, LindaError> {
-        let tuple = self.input(tuple, timeout)?;
-        add_tuple(&self.local_tuples, tuple.clone())?;
+        let start = time::Instant::now();
+        let tuple = self.wait_for(tuple, timeout, Right::In)?;
+        self.metrics.input_took(start.elapsed());
 
         Ok(tuple)
     }
+}
+
+impl<Output: io::Write> Linda<Output> {
+    pub fn monitor(&self) -> Monitor {
+        Monitor::new(self.metrics.clone(), self.local_tuples.clone())
+    }
 
-    pub fn inp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
-        match find_tuple(&self.local_tuples, &tuple) {
-            Some(tuple) => Ok(tuple),
-            None => Err(LindaError::NoTuple),
+    // Removes the local tuples matching `template` regardless of the access policy, which
+    // only governs the nodes' own operations.
+    pub fn purge(&self, template: &Tuple<Request>) -> Result<usize, LindaError> {
+        match self.local_tuples.lock() {
+            Ok(mut guard) => {
+                let before = guard.len();
+                guard.retain(|tuple| !satisfies(template, tuple));
+                Ok(before - guard.len())
+            }
+            Err(e) => Err(LindaError::MutexLockFailure(e.to_string())),
         }
     }
 
-    pub fn rdp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
-        let tuple = self.inp(tuple)?;
-        add_tuple(&self.local_tuples, tuple.clone())?;
+    // Sends to `output` from now on; dropping the old link tells the previous successor that
+    // nothing more will arrive.
+    pub fn relink_output(&self, output: Output) -> Result<(), LindaError> {
+        match self.output_stream.lock() {
+            Ok(mut guard) => {
+                guard.0 = Some(output);
+                Ok(())
+            }
+            Err(e) => Err(LindaError::MutexLockFailure(e.to_string())),
+        }
+    }
 
-        Ok(tuple)
+    // Queues a link from a new predecessor, which is read once the current one disconnects.
+    // `identity` is what the link authenticated the predecessor as.
+    pub fn relink_input(
+        &self,
+        input: impl io::Read + marker::Send + 'static,
+        identity: String,
+    ) -> Result<(), LindaError> {
+        self.links
+            .send((Box::new(input), identity))
+            .map_err(|_| LindaError::WorkerDisconnected)
+    }
+
+    // Surveys the ring now rather than waiting for the next periodic census.
+    pub fn census(&self) -> Result<(), LindaError> {
+        self.check_open()?;
+        survey(
+            &self.output_stream,
+            &self.local_tuples,
+            &self.node,
+            &self.balancer,
+        )
+    }
+
+    // The tuples this node owns in a partitioned ring, for serving them to the other nodes.
+    // Its policy applies to them as to the rest of its tuples; the node's own operations go
+    // by its identity, those of other nodes by the one they connected with.
+    pub fn partition(&self) -> LocalSpace {
+        LocalSpace::sharing(self.local_tuples.clone(), (*self.access).clone())
+    }
+
+    pub(crate) fn owner(&self, key: &Value) -> Option<Member> {
+        self.balancer.owner(key)
+    }
+
+    // Hands the local tuples over and waits until the ring has seen this node leave, but not
+    // for the worker, which relays until the predecessor sends past this node. An evicted node
+    // answers the server this way, since the server relinks the predecessor only afterwards.
+    pub fn withdraw(&self) -> Result<(), LindaError> {
+        // Once the ring is gone there is nobody left to take the tuples, which only matters
+        // if this node still holds some.
+        if self.worker_finished()? || self.disconnected.load(sync::atomic::Ordering::SeqCst) {
+            self.closed.store(true, sync::atomic::Ordering::SeqCst);
+            self.local_tuples.withdraw();
+            return match self.local_tuples.lock() {
+                Ok(guard) if guard.is_empty() => Ok(()),
+                Ok(_) => Err(LindaError::WorkerDisconnected),
+                Err(e) => Err(LindaError::MutexLockFailure(e.to_string())),
+            };
+        }
+        self.leave()?;
+
+        let deadline = time::Instant::now() + SHUTDOWN_TIMEOUT;
+        while !self.left.load(sync::atomic::Ordering::SeqCst) && !self.worker_finished()? {
+            if time::Instant::now() >= deadline {
+                let e = io::Error::new(io::ErrorKind::TimedOut, RING_UNRESPONSIVE);
+                return Err(LindaError::IoFailure(e));
+            }
+            thread::sleep(SHUTDOWN_POLL);
+        }
+
+        Ok(())
+    }
+
+    fn worker_finished(&self) -> Result<bool, LindaError> {
+        match self.worker.lock() {
+            Ok(guard) => Ok(guard.as_ref().is_none_or(thread::JoinHandle::is_finished)),
+            Err(e) => Err(LindaError::MutexLockFailure(e.to_string())),
+        }
+    }
+
+    fn check_open(&self) -> Result<(), LindaError> {
+        match self.closed.load(sync::atomic::Ordering::SeqCst) {
+            true => Err(LindaError::ShutDown),
+            false => Ok(()),
+        }
+    }
+
+    // Hands the local tuples over to the next node and tells the ring that this node leaves.
+    // The worker keeps relaying messages for as long as its predecessor sends any.
+    fn leave(&self) -> Result<(), LindaError> {
+        let tuples = match self.local_tuples.lock() {
+            Ok(mut guard) if !self.closed.swap(true, sync::atomic::Ordering::SeqCst) => {
+                self.local_tuples.withdraw();
+                mem::take(&mut *guard)
+            }
+            Ok(_) => return Ok(()),
+            Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+        };
+        if let Ok(mut guard) = self.surveyor.lock() {
+            guard.take();
+        }
+
+        for tuple in tuples {
+            send(
+                &self.output_stream,
+                Message::handoff(tuple, self.node.clone()),
+            )?;
+        }
+        send(&self.output_stream, Message::leave(self.node.clone()))
+    }
+}
+
+impl<Output: io::Write> Drop for Linda<Output> {
+    fn drop(&mut self) {
+        if let Err(e) = self.leave() {
+            tracing::error!(error = e.describe(), "Failed to leave the ring");
+        }
+    }
+}
+
+impl<Output: 'static + io::Write + marker::Send> TupleSpace for Linda<Output> {
+    fn out(&self, tuple: Tuple<Value>) -> Result<(), LindaError> {
+        self.check_open()?;
+        if !self.access.allows(Right::Out, &tuple) {
+            return Err(LindaError::AccessDenied(self.access.identity.clone()));
+        }
+
+        let msg = Message::value(tuple, self.node.clone());
+        send(&self.output_stream, msg)
+    }
+
+    fn input(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError> {
+        self.take_waiting(tuple, Some(timeout))
+    }
+
+    fn input_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.take_waiting(tuple, None)
+    }
+
+    // The holder keeps the tuple, so its policy goes on governing it.
+    fn read(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError> {
+        self.wait_for(tuple, Some(timeout), Right::Rd)
+    }
+
+    fn read_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.wait_for(tuple, None, Right::Rd)
+    }
+
+    fn inp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.take(tuple, Right::In)
+    }
+
+    fn rdp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.take(tuple, Right::Rd)
+    }
+
+    // Only the tuples this node has the right to read are visible.
+    fn list(&self, template: Option<&Tuple<Request>>) -> Result<Vec<Tuple<Value>>, LindaError> {
+        self.local_tuples
+            .readable(template, &self.access, |tuples| tuples.cloned().collect())
+    }
+
+    fn count(&self, template: Option<&Tuple<Request>>) -> Result<usize, LindaError> {
+        self.local_tuples
+            .readable(template, &self.access, |tuples| tuples.count())
+    }
+
+    fn stats(&self) -> Option<Stats> {
+        self.monitor().stats()
+    }
+
+    // The worker stops once the predecessor sends past this node, which the server arranges
+    // after the node withdrew.
+    fn shutdown(&self) -> Result<(), LindaError> {
+        self.withdraw()?;
+
+        let mut worker = match self.worker.lock() {
+            Ok(guard) => guard,
+            Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+        };
+        let deadline = time::Instant::now() + SHUTDOWN_TIMEOUT;
+        while !worker.as_ref().is_none_or(thread::JoinHandle::is_finished) {
+            if time::Instant::now() >= deadline {
+                let e = io::Error::new(io::ErrorKind::TimedOut, PREDECESSOR_UNRESPONSIVE);
+                return Err(LindaError::IoFailure(e));
+            }
+            thread::sleep(SHUTDOWN_POLL);
+        }
+        match worker.take() {
+            Some(worker) => worker.join().map_err(|_| LindaError::WorkerDisconnected),
+            None => Ok(()),
+        }
     }
 }
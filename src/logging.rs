@@ -0,0 +1,48 @@
+use std::{env, io};
+
+use tracing_subscriber::EnvFilter;
+
+// Holds the log filter unless `--log` is given, e.g. `LINDA_LOG=warn` for problems only or
+// `LINDA_LOG=info,linda=debug` to follow the ring traffic in a release build.
+pub const LOG_VAR: &str = "LINDA_LOG";
+
+fn default_filter() -> &'static str {
+    match cfg!(debug_assertions) {
+        true => "debug",
+        false => "info",
+    }
+}
+
+pub fn filter(flag: Option<&str>) -> Result<EnvFilter, String> {
+    let directives = match flag {
+        Some(flag) => flag.to_owned(),
+        None => env::var(LOG_VAR).unwrap_or_else(|_| String::from(default_filter())),
+    };
+
+    EnvFilter::builder()
+        .parse(&directives)
+        .map_err(|e| format!("Invalid log filter '{directives}'! {e}"))
+}
+
+// Log records go to stderr so they never mix with the tuples a client prints.
+pub fn init(flag: Option<&str>) -> Result<(), String> {
+    tracing_subscriber::fmt()
+        .with_env_filter(filter(flag)?)
+        .with_writer(io::stderr)
+        .try_init()
+        .map_err(|e| format!("Failed to set up logging! {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_filters() {
+        assert!(filter(Some("debug")).is_ok());
+        assert!(filter(Some("warn,linda::standalone=trace")).is_ok());
+        assert!(filter(Some("linda=loud"))
+            .unwrap_err()
+            .starts_with("Invalid log filter 'linda=loud'!"));
+    }
+}
@@ -1,51 +1,371 @@
-use std::{io, mem, net, sync};
+use std::{error, fmt, io, mem, sync, time};
 
-use crate::{tuple::*, utils::*};
+use crate::{acl::Right, balance::Member, transport::Address, tuple::*, utils::*};
 
 const VALUE_ID: u8 = 0;
 const REQUEST_ID: u8 = 1;
+const OUT_ID: u8 = 2;
+const OPERATION_ID: u8 = 3;
+const REPLY_ID: u8 = 4;
+const DENIED_ID: u8 = 5;
+const HANDOFF_ID: u8 = 6;
+const LEAVE_ID: u8 = 7;
+const ADMIN_ID: u8 = 8;
+const CENSUS_ID: u8 = 9;
+const PLACED_ID: u8 = 10;
+const COPY_ID: u8 = 11;
+const HELLO_ID: u8 = 12;
+const MAX_MESSAGE_SIZE: usize = 1 << 24;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    In,
+    Rd,
+    Inp,
+    Rdp,
+}
+
+// Requests the server sends to a ring node over the connection the node registered with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Admin {
+    Ping,
+    Count,
+    Purge(Tuple<Request>),
+    Evict,
+    // Makes the node send to the given node from now on, bypassing an evicted successor.
+    Relink(Address),
+    // Sent the other way: the node asks the server to evict it, so that the ring is closed
+    // behind it.
+    Leave,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reply {
+    Done,
+    Tuple(Tuple<Value>),
+    NoTuple,
+    Timeout,
+    Failed(String),
+    Count(usize),
+    // The identity the owner refused.
+    Denied(String),
+    // The owner has left the ring and serves its tuples no longer.
+    Closed,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum MessageType {
     Value(Tuple<Value>),
-    Request(Tuple<Request>),
+    Request(Tuple<Request>, Right, Option<String>),
+    Denied(Tuple<Request>),
+    Handoff(Tuple<Value>),
+    Leave,
+    Census(Vec<Member>),
+    Placed(Tuple<Value>, Address),
+    Copy(Tuple<Value>),
+    // The first message on a ring link, naming the node that sends over it.
+    Hello,
+    Out(u64, Tuple<Value>),
+    Operation(u64, Operation, Tuple<Request>, Timeout),
+    Reply(u64, Reply),
+    Admin(u64, Admin),
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Message {
     pub tuple: MessageType,
-    pub ip: net::SocketAddr,
+    pub node: Address,
 }
 
 #[derive(Debug)]
 pub enum LindaError {
     MutexLockFailure(String),
     IoFailure(io::Error),
-    MessageParseFailure,
+    ParseFailure(String),
     ChannelSendFailure(sync::mpsc::SendError<Message>),
-    NoTuple,
-    Timeout,
+    ConversionFailure(Tuple<Value>),
+    WorkerDisconnected,
+    ServerFailure(String),
+    // `node` is the node that reported the failure, if the space has one.
+    NoTuple {
+        template: Tuple<Request>,
+        node: Option<Address>,
+    },
+    Timeout {
+        template: Tuple<Request>,
+        node: Option<Address>,
+        elapsed: time::Duration,
+    },
+    AccessDenied(String),
+    ShutDown,
+    Unsupported(&'static str),
+    FileFailure(String, io::Error),
+    NotMember(Address),
+}
+
+impl LindaError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LindaError::MutexLockFailure(_) => "MUTEX_LOCK_FAILURE",
+            LindaError::IoFailure(_) => "IO_FAILURE",
+            LindaError::ParseFailure(_) => "MESSAGE_PARSE_FAILURE",
+            LindaError::ChannelSendFailure(_) => "CHANNEL_SEND_FAILURE",
+            LindaError::ConversionFailure(_) => "CONVERSION_FAILURE",
+            LindaError::WorkerDisconnected => "WORKER_DISCONNECTED",
+            LindaError::ServerFailure(_) => "SERVER_FAILURE",
+            LindaError::NoTuple { .. } => "NO_TUPLE",
+            LindaError::Timeout { .. } => "TIMEOUT",
+            LindaError::AccessDenied(_) => "ACCESS_DENIED",
+            LindaError::ShutDown => "SHUT_DOWN",
+            LindaError::Unsupported(_) => "UNSUPPORTED",
+            LindaError::FileFailure(..) => "FILE_FAILURE",
+            LindaError::NotMember(_) => "NOT_MEMBER",
+        }
+    }
+
+    // The error followed by everything that caused it, e.g.
+    // "Connection failed: Broken pipe (os error 32)".
+    pub fn describe(&self) -> String {
+        let mut description = self.to_string();
+        let mut source = error::Error::source(self);
+        while let Some(e) = source {
+            description.push_str(&format!(": {e}"));
+            source = e.source();
+        }
+
+        description
+    }
+}
+
+impl fmt::Display for LindaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LindaError::MutexLockFailure(e) => write!(f, "Failed to lock shared state ({e})"),
+            LindaError::IoFailure(_) => write!(f, "Connection failed"),
+            LindaError::ParseFailure(what) => write!(f, "Failed to decode message: {what}"),
+            LindaError::ChannelSendFailure(_) => write!(f, "Failed to hand over request to worker"),
+            LindaError::ConversionFailure(tuple) => {
+                write!(f, "Tuple {tuple} does not have the requested shape")
+            }
+            LindaError::WorkerDisconnected => write!(f, "Worker thread disconnected"),
+            LindaError::ServerFailure(e) => write!(f, "Server failed: {e}"),
+            LindaError::NoTuple { template, node } => {
+                write!(f, "No tuple matching {template}")?;
+                match node {
+                    Some(node) => write!(f, " at {node}"),
+                    None => Ok(()),
+                }
+            }
+            LindaError::Timeout {
+                template,
+                node,
+                elapsed,
+            } => {
+                write!(
+                    f,
+                    "Timed out after {elapsed:.3?} waiting for a tuple matching {template}"
+                )?;
+                match node {
+                    Some(node) => write!(f, " at {node}"),
+                    None => Ok(()),
+                }
+            }
+            LindaError::AccessDenied(identity) => write!(f, "Access denied for {identity}"),
+            LindaError::ShutDown => write!(f, "Tuple space has been shut down"),
+            LindaError::Unsupported(what) => write!(f, "This tuple space does not support {what}"),
+            LindaError::FileFailure(path, _) => write!(f, "Failed to access {path}"),
+            LindaError::NotMember(node) => write!(f, "{node} is not a member of the ring"),
+        }
+    }
+}
+
+impl error::Error for LindaError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LindaError::IoFailure(e) => Some(e),
+            LindaError::ChannelSendFailure(e) => Some(e),
+            LindaError::FileFailure(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl MessageType {
+    pub fn name(&self) -> &'static str {
+        kind(self.tag()).unwrap_or("unknown")
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            MessageType::Value(_) => VALUE_ID,
+            MessageType::Request(..) => REQUEST_ID,
+            MessageType::Denied(_) => DENIED_ID,
+            MessageType::Handoff(_) => HANDOFF_ID,
+            MessageType::Leave => LEAVE_ID,
+            MessageType::Census(_) => CENSUS_ID,
+            MessageType::Placed(..) => PLACED_ID,
+            MessageType::Copy(_) => COPY_ID,
+            MessageType::Hello => HELLO_ID,
+            MessageType::Out(..) => OUT_ID,
+            MessageType::Operation(..) => OPERATION_ID,
+            MessageType::Reply(..) => REPLY_ID,
+            MessageType::Admin(..) => ADMIN_ID,
+        }
+    }
+
+    // Correlates the messages between a client and a standalone server, or between the
+    // server and a ring node.
+    pub fn id(&self) -> Option<u64> {
+        match self {
+            MessageType::Out(id, _)
+            | MessageType::Operation(id, ..)
+            | MessageType::Reply(id, _)
+            | MessageType::Admin(id, _) => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> Option<&Tuple<Value>> {
+        match self {
+            MessageType::Value(tuple)
+            | MessageType::Handoff(tuple)
+            | MessageType::Placed(tuple, _)
+            | MessageType::Copy(tuple)
+            | MessageType::Out(_, tuple)
+            | MessageType::Reply(_, Reply::Tuple(tuple)) => Some(tuple),
+            _ => None,
+        }
+    }
+
+    pub fn template(&self) -> Option<&Tuple<Request>> {
+        match self {
+            MessageType::Request(template, ..)
+            | MessageType::Denied(template)
+            | MessageType::Operation(_, _, template, _)
+            | MessageType::Admin(_, Admin::Purge(template)) => Some(template),
+            _ => None,
+        }
+    }
 }
 
 impl Message {
-    pub fn value(tuple: Tuple<Value>, ip: net::SocketAddr) -> Message {
+    pub fn value(tuple: Tuple<Value>, node: Address) -> Message {
         Message {
             tuple: MessageType::Value(tuple),
-            ip,
+            node,
         }
     }
 
-    pub fn request(tuple: Tuple<Request>, ip: net::SocketAddr) -> Message {
+    // Sent without an identity, which the first node it reaches fills in with the one its
+    // predecessor authenticated as.
+    pub fn request(
+        tuple: Tuple<Request>,
+        right: Right,
+        identity: Option<String>,
+        node: Address,
+    ) -> Message {
         Message {
-            tuple: MessageType::Request(tuple),
-            ip,
+            tuple: MessageType::Request(tuple, right, identity),
+            node,
         }
     }
 
-    pub fn from_ip(ip: net::SocketAddr) -> Message {
+    // Sent on to `node` when the holder of a matching tuple refuses it; the request itself
+    // goes on to the other holders.
+    pub fn denied(tuple: Tuple<Request>, node: Address) -> Message {
+        Message {
+            tuple: MessageType::Denied(tuple),
+            node,
+        }
+    }
+
+    // A tuple passed on by a node leaving the ring; the first node to receive it keeps it.
+    pub fn handoff(tuple: Tuple<Value>, node: Address) -> Message {
+        Message {
+            tuple: MessageType::Handoff(tuple),
+            node,
+        }
+    }
+
+    // Travels once around the ring, so the leaving node knows everyone has seen it once it
+    // comes back.
+    pub fn leave(node: Address) -> Message {
+        Message {
+            tuple: MessageType::Leave,
+            node,
+        }
+    }
+
+    // Collects every member's address and tuple count on its way around the ring.
+    pub fn hello(node: Address) -> Message {
+        Message {
+            tuple: MessageType::Hello,
+            node,
+        }
+    }
+
+    pub fn census(members: Vec<Member>, node: Address) -> Message {
+        Message {
+            tuple: MessageType::Census(members),
+            node,
+        }
+    }
+
+    // A tuple that has been around the ring once, on its way to the node chosen to store it.
+    pub fn placed(tuple: Tuple<Value>, target: Address, node: Address) -> Message {
+        Message {
+            tuple: MessageType::Placed(tuple, target),
+            node,
+        }
+    }
+
+    // A tuple read by `node`, which the holder keeps. Nobody else stores it, so it is gone
+    // once it reached `node`.
+    pub fn copy(tuple: Tuple<Value>, node: Address) -> Message {
+        Message {
+            tuple: MessageType::Copy(tuple),
+            node,
+        }
+    }
+
+    pub fn out(id: u64, tuple: Tuple<Value>, node: Address) -> Message {
+        Message {
+            tuple: MessageType::Out(id, tuple),
+            node,
+        }
+    }
+
+    pub fn operation(
+        id: u64,
+        operation: Operation,
+        template: Tuple<Request>,
+        timeout: Timeout,
+        node: Address,
+    ) -> Message {
+        Message {
+            tuple: MessageType::Operation(id, operation, template, timeout),
+            node,
+        }
+    }
+
+    pub fn reply(id: u64, reply: Reply, node: Address) -> Message {
+        Message {
+            tuple: MessageType::Reply(id, reply),
+            node,
+        }
+    }
+
+    pub fn admin(id: u64, admin: Admin, node: Address) -> Message {
+        Message {
+            tuple: MessageType::Admin(id, admin),
+            node,
+        }
+    }
+
+    pub fn from_address(node: Address) -> Message {
         Message {
             tuple: MessageType::Value(Tuple::new()),
-            ip,
+            node,
         }
     }
 
@@ -54,40 +374,74 @@ impl Message {
         stream: &mut OutputStream,
     ) -> Result<(), LindaError> {
         let bytes = self.to_bytes();
-        let size = bytes.len().to_le_bytes();
+        // One write per frame, so a TCP peer does not hold the body back waiting for an
+        // acknowledgement of the size.
+        let mut frame = bytes.len().to_le_bytes().to_vec();
+        frame.extend_from_slice(&bytes);
 
-        for buf in [&size[..], &bytes[..]] {
-            if let Err(e) = stream.write(buf) {
-                return Err(LindaError::IoFailure(e));
-            }
-        }
-        Ok(())
+        stream.write_all(&frame).map_err(LindaError::IoFailure)
     }
 
     pub fn recv<InputStream: io::Read>(stream: &mut InputStream) -> Result<Message, LindaError> {
         let mut size = [0u8; mem::size_of::<usize>()];
-        if let Err(e) = stream.read(&mut size[..]) {
+        if let Err(e) = stream.read_exact(&mut size[..]) {
             return Err(LindaError::IoFailure(e));
         }
 
         let size = match read_le_usize(&mut &size[..]) {
-            Some(val) => val,
-            None => return Err(LindaError::MessageParseFailure),
+            Some(val) if val <= MAX_MESSAGE_SIZE => val,
+            Some(val) => {
+                return Err(LindaError::ParseFailure(format!(
+                    "message of {val} bytes exceeds the limit of {MAX_MESSAGE_SIZE} bytes"
+                )))
+            }
+            None => {
+                return Err(LindaError::ParseFailure(String::from(
+                    "missing message size",
+                )))
+            }
         };
 
-        let mut bytes = Vec::new();
-        bytes.resize(size, 0);
-        if let Err(e) = stream.read(&mut bytes[..]) {
+        let mut bytes = vec![0; size];
+        if let Err(e) = stream.read_exact(&mut bytes[..]) {
             return Err(LindaError::IoFailure(e));
         }
 
         match Message::from_bytes(&mut &bytes[..]) {
             Some(msg) => Ok(msg),
-            None => return Err(LindaError::MessageParseFailure),
+            None => Err(LindaError::ParseFailure(malformed(&bytes))),
         }
     }
 }
 
+// The name of each kind of message as it is logged, by the tag it is sent with.
+fn kind(tag: u8) -> Option<&'static str> {
+    match tag {
+        VALUE_ID => Some("tuple"),
+        REQUEST_ID => Some("request"),
+        DENIED_ID => Some("denial"),
+        HANDOFF_ID => Some("handoff"),
+        LEAVE_ID => Some("leave"),
+        CENSUS_ID => Some("census"),
+        PLACED_ID => Some("placement"),
+        COPY_ID => Some("copy"),
+        HELLO_ID => Some("hello"),
+        OUT_ID => Some("out"),
+        OPERATION_ID => Some("operation"),
+        REPLY_ID => Some("reply"),
+        ADMIN_ID => Some("admin"),
+        _ => None,
+    }
+}
+
+fn malformed(bytes: &[u8]) -> String {
+    match bytes.first().map(|&tag| (tag, kind(tag))) {
+        Some((_, Some(kind))) => format!("malformed {kind} message of {} bytes", bytes.len()),
+        Some((tag, None)) => format!("unknown message type {tag}"),
+        None => String::from("empty message"),
+    }
+}
+
 impl Serializable for Message {
     fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
@@ -96,12 +450,62 @@ impl Serializable for Message {
                 bytes.append(&mut VALUE_ID.to_le_bytes().to_vec());
                 bytes.append(&mut tuple.to_bytes());
             }
-            MessageType::Request(tuple) => {
+            MessageType::Request(tuple, right, identity) => {
                 bytes.append(&mut REQUEST_ID.to_le_bytes().to_vec());
                 bytes.append(&mut tuple.to_bytes());
+                bytes.append(&mut right.to_bytes());
+                bytes.append(&mut Value::String(identity.clone()).to_bytes());
+            }
+            MessageType::Denied(tuple) => {
+                bytes.append(&mut DENIED_ID.to_le_bytes().to_vec());
+                bytes.append(&mut tuple.to_bytes());
+            }
+            MessageType::Handoff(tuple) => {
+                bytes.append(&mut HANDOFF_ID.to_le_bytes().to_vec());
+                bytes.append(&mut tuple.to_bytes());
+            }
+            MessageType::Leave => bytes.append(&mut LEAVE_ID.to_le_bytes().to_vec()),
+            MessageType::Hello => bytes.append(&mut HELLO_ID.to_le_bytes().to_vec()),
+            MessageType::Census(members) => {
+                bytes.append(&mut CENSUS_ID.to_le_bytes().to_vec());
+                bytes.append(&mut members.len().to_le_bytes().to_vec());
+                for member in members {
+                    bytes.append(&mut member.to_bytes());
+                }
+            }
+            MessageType::Placed(tuple, target) => {
+                bytes.append(&mut PLACED_ID.to_le_bytes().to_vec());
+                bytes.append(&mut tuple.to_bytes());
+                bytes.append(&mut target.to_bytes());
+            }
+            MessageType::Copy(tuple) => {
+                bytes.append(&mut COPY_ID.to_le_bytes().to_vec());
+                bytes.append(&mut tuple.to_bytes());
+            }
+            MessageType::Out(id, tuple) => {
+                bytes.append(&mut OUT_ID.to_le_bytes().to_vec());
+                bytes.append(&mut id.to_le_bytes().to_vec());
+                bytes.append(&mut tuple.to_bytes());
+            }
+            MessageType::Operation(id, operation, template, timeout) => {
+                bytes.append(&mut OPERATION_ID.to_le_bytes().to_vec());
+                bytes.append(&mut id.to_le_bytes().to_vec());
+                bytes.append(&mut operation.to_bytes());
+                bytes.append(&mut template.to_bytes());
+                bytes.append(&mut timeout_to_bytes(timeout));
+            }
+            MessageType::Reply(id, reply) => {
+                bytes.append(&mut REPLY_ID.to_le_bytes().to_vec());
+                bytes.append(&mut id.to_le_bytes().to_vec());
+                bytes.append(&mut reply.to_bytes());
+            }
+            MessageType::Admin(id, admin) => {
+                bytes.append(&mut ADMIN_ID.to_le_bytes().to_vec());
+                bytes.append(&mut id.to_le_bytes().to_vec());
+                bytes.append(&mut admin.to_bytes());
             }
         };
-        bytes.append(&mut ip_to_bytes(&self.ip));
+        bytes.append(&mut self.node.to_bytes());
 
         bytes
     }
@@ -110,61 +514,203 @@ impl Serializable for Message {
         match read_le_u8(bytes) {
             Some(VALUE_ID) => Some(Message::value(
                 Tuple::<Value>::from_bytes(bytes)?,
-                bytes_to_ip(bytes)?,
+                Address::from_bytes(bytes)?,
             )),
             Some(REQUEST_ID) => Some(Message::request(
                 Tuple::<Request>::from_bytes(bytes)?,
-                bytes_to_ip(bytes)?,
+                Right::from_bytes(bytes)?,
+                match Value::from_bytes(bytes)? {
+                    Value::String(identity) => identity,
+                    _ => return None,
+                },
+                Address::from_bytes(bytes)?,
+            )),
+            Some(DENIED_ID) => Some(Message::denied(
+                Tuple::<Request>::from_bytes(bytes)?,
+                Address::from_bytes(bytes)?,
+            )),
+            Some(HANDOFF_ID) => Some(Message::handoff(
+                Tuple::<Value>::from_bytes(bytes)?,
+                Address::from_bytes(bytes)?,
+            )),
+            Some(LEAVE_ID) => Some(Message::leave(Address::from_bytes(bytes)?)),
+            Some(HELLO_ID) => Some(Message::hello(Address::from_bytes(bytes)?)),
+            Some(CENSUS_ID) => {
+                let count = read_le_usize(bytes)?;
+                let mut members = Vec::new();
+                for _ in 0..count {
+                    members.push(Member::from_bytes(bytes)?);
+                }
+                Some(Message::census(members, Address::from_bytes(bytes)?))
+            }
+            Some(PLACED_ID) => Some(Message::placed(
+                Tuple::<Value>::from_bytes(bytes)?,
+                Address::from_bytes(bytes)?,
+                Address::from_bytes(bytes)?,
+            )),
+            Some(COPY_ID) => Some(Message::copy(
+                Tuple::<Value>::from_bytes(bytes)?,
+                Address::from_bytes(bytes)?,
+            )),
+            Some(OUT_ID) => Some(Message::out(
+                read_le_u64(bytes)?,
+                Tuple::<Value>::from_bytes(bytes)?,
+                Address::from_bytes(bytes)?,
+            )),
+            Some(OPERATION_ID) => Some(Message::operation(
+                read_le_u64(bytes)?,
+                Operation::from_bytes(bytes)?,
+                Tuple::<Request>::from_bytes(bytes)?,
+                bytes_to_timeout(bytes)?,
+                Address::from_bytes(bytes)?,
+            )),
+            Some(REPLY_ID) => Some(Message::reply(
+                read_le_u64(bytes)?,
+                Reply::from_bytes(bytes)?,
+                Address::from_bytes(bytes)?,
+            )),
+            Some(ADMIN_ID) => Some(Message::admin(
+                read_le_u64(bytes)?,
+                Admin::from_bytes(bytes)?,
+                Address::from_bytes(bytes)?,
             )),
             _ => None,
         }
     }
 }
 
-fn ip_to_bytes(addr: &net::SocketAddr) -> Vec<u8> {
-    let mut buffer = match addr {
-        net::SocketAddr::V4(addr) => {
-            let mut buffer = 4u8.to_le_bytes().to_vec();
-            buffer.append(&mut addr.ip().octets().to_vec());
-            buffer
+impl Serializable for Operation {
+    fn to_bytes(&self) -> Vec<u8> {
+        let value = match self {
+            Operation::In => 0u8,
+            Operation::Rd => 1,
+            Operation::Inp => 2,
+            Operation::Rdp => 3,
+        };
+
+        value.to_le_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &mut &[u8]) -> Option<Operation> {
+        match read_le_u8(bytes)? {
+            0 => Some(Operation::In),
+            1 => Some(Operation::Rd),
+            2 => Some(Operation::Inp),
+            3 => Some(Operation::Rdp),
+            _ => None,
         }
-        net::SocketAddr::V6(addr) => {
-            let mut buffer = 6u8.to_le_bytes().to_vec();
-            buffer.append(&mut addr.ip().octets().to_vec());
-            buffer
+    }
+}
+
+impl Serializable for Reply {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Reply::Done => bytes.push(0),
+            Reply::Tuple(tuple) => {
+                bytes.push(1);
+                bytes.append(&mut tuple.to_bytes());
+            }
+            Reply::NoTuple => bytes.push(2),
+            Reply::Timeout => bytes.push(3),
+            Reply::Failed(message) => {
+                bytes.push(4);
+                bytes.append(&mut Value::string(message.clone()).to_bytes());
+            }
+            Reply::Count(count) => {
+                bytes.push(5);
+                bytes.append(&mut count.to_le_bytes().to_vec());
+            }
+            Reply::Denied(identity) => {
+                bytes.push(6);
+                bytes.append(&mut Value::string(identity.clone()).to_bytes());
+            }
+            Reply::Closed => bytes.push(7),
         }
-    };
-    buffer.append(&mut addr.port().to_le_bytes().to_vec());
 
-    buffer
+        bytes
+    }
+
+    fn from_bytes(bytes: &mut &[u8]) -> Option<Reply> {
+        match read_le_u8(bytes)? {
+            0 => Some(Reply::Done),
+            1 => Some(Reply::Tuple(Tuple::<Value>::from_bytes(bytes)?)),
+            2 => Some(Reply::NoTuple),
+            3 => Some(Reply::Timeout),
+            4 => match Value::from_bytes(bytes)? {
+                Value::String(Some(message)) => Some(Reply::Failed(message)),
+                _ => None,
+            },
+            5 => Some(Reply::Count(read_le_usize(bytes)?)),
+            6 => match Value::from_bytes(bytes)? {
+                Value::String(Some(identity)) => Some(Reply::Denied(identity)),
+                _ => None,
+            },
+            7 => Some(Reply::Closed),
+            _ => None,
+        }
+    }
 }
 
-fn bytes_to_ip(bytes: &mut &[u8]) -> Option<net::SocketAddr> {
-    let ip_ver = read_le_u8(bytes)?;
-    let addr = match ip_ver {
-        4 => {
-            let (ip_bytes, rest) = bytes.split_at(IPV4_ADDR_LENGTH);
-            *bytes = rest;
-            let buffer: [u8; IPV4_ADDR_LENGTH] = ip_bytes.try_into().ok()?;
-            net::IpAddr::V4(net::Ipv4Addr::from(buffer))
+impl Serializable for Admin {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Admin::Ping => bytes.push(0),
+            Admin::Count => bytes.push(1),
+            Admin::Purge(template) => {
+                bytes.push(2);
+                bytes.append(&mut template.to_bytes());
+            }
+            Admin::Evict => bytes.push(3),
+            Admin::Leave => bytes.push(5),
+            Admin::Relink(address) => {
+                bytes.push(4);
+                bytes.append(&mut address.to_bytes());
+            }
         }
-        6 => {
-            let (ip_bytes, rest) = bytes.split_at(IPV6_ADDR_LENGTH);
-            *bytes = rest;
-            let buffer: [u8; IPV6_ADDR_LENGTH] = ip_bytes.try_into().ok()?;
-            net::IpAddr::V6(net::Ipv6Addr::from(buffer))
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &mut &[u8]) -> Option<Admin> {
+        match read_le_u8(bytes)? {
+            0 => Some(Admin::Ping),
+            1 => Some(Admin::Count),
+            2 => Some(Admin::Purge(Tuple::<Request>::from_bytes(bytes)?)),
+            3 => Some(Admin::Evict),
+            4 => Some(Admin::Relink(Address::from_bytes(bytes)?)),
+            5 => Some(Admin::Leave),
+            _ => None,
         }
-        _ => return None,
-    };
-    let port = read_le_u16(bytes)?;
+    }
+}
 
-    Some(net::SocketAddr::new(addr, port))
+fn timeout_to_bytes(timeout: &Timeout) -> Vec<u8> {
+    match timeout {
+        Timeout::After(duration) => {
+            let millis = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+            let mut buffer = 1u8.to_le_bytes().to_vec();
+            buffer.append(&mut millis.to_le_bytes().to_vec());
+            buffer
+        }
+        Timeout::Forever => 0u8.to_le_bytes().to_vec(),
+    }
+}
+
+fn bytes_to_timeout(bytes: &mut &[u8]) -> Option<Timeout> {
+    match read_le_u8(bytes)? {
+        0 => Some(Timeout::Forever),
+        1 => Some(Timeout::After(time::Duration::from_millis(read_le_u64(
+            bytes,
+        )?))),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use core::time;
-    use std::thread;
+    use std::{net, thread};
 
     use super::*;
 
@@ -177,24 +723,147 @@ mod tests {
 
     #[test]
     fn serialize_message() {
-        let ip: net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let node: Address = "127.0.0.1:0".parse().unwrap();
 
         let mut tuple = Tuple::new();
         tuple.push(Request::new(Value::int(420), ComparisonOperator::LE));
+        check_message(Message::denied(tuple.clone(), node.clone()));
+        check_message(Message::leave(node.clone()));
+        check_message(Message::hello(node.clone()));
         check_message(Message {
-            tuple: MessageType::Request(tuple),
-            ip: ip.clone(),
+            tuple: MessageType::Request(tuple.clone(), Right::Rd, Some(String::from("scheduler"))),
+            node: node.clone(),
         });
+        check_message(Message::request(tuple, Right::In, None, node.clone()));
 
         let mut tuple = Tuple::new();
         tuple.push(Value::int(69));
         tuple.push(Value::String(None));
+        check_message(Message::handoff(tuple.clone(), node.clone()));
+        check_message(Message::copy(tuple.clone(), node.clone()));
+        let target: Address = "unix:/tmp/linda.sock".parse().unwrap();
+        check_message(Message::placed(tuple.clone(), target.clone(), node.clone()));
+        check_message(Message::census(vec![], node.clone()));
+        let members = vec![
+            Member {
+                address: node.clone(),
+                load: 3,
+                endpoint: None,
+            },
+            Member {
+                address: target.clone(),
+                load: 0,
+                endpoint: Some(target),
+            },
+        ];
+        check_message(Message::census(members, node.clone()));
         check_message(Message {
             tuple: MessageType::Value(tuple),
-            ip,
+            node,
         });
 
-        check_message(Message::from_ip("[::1]:0".parse().unwrap()));
+        check_message(Message::from_address("[::1]:0".parse().unwrap()));
+        check_message(Message::from_address(
+            "unix:/tmp/linda.sock".parse().unwrap(),
+        ));
+    }
+
+    #[test]
+    fn serialize_operations() {
+        let node: Address = "127.0.0.1:4000".parse().unwrap();
+        let template = Tuple::from_vec(vec![
+            Request::new(Value::int(1), ComparisonOperator::EQ),
+            Request::new(Value::String(None), ComparisonOperator::ANY),
+        ]);
+        let tuple = Tuple::from_vec(vec![Value::int(1), Value::string(String::from("x"))]);
+
+        check_message(Message::out(7, tuple.clone(), node.clone()));
+        for (operation, timeout) in [
+            (Operation::In, Timeout::Forever),
+            (
+                Operation::Rd,
+                Timeout::After(time::Duration::from_millis(1500)),
+            ),
+            (Operation::Inp, Timeout::After(time::Duration::ZERO)),
+            (Operation::Rdp, Timeout::Forever),
+        ] {
+            check_message(Message::operation(
+                u64::MAX,
+                operation,
+                template.clone(),
+                timeout,
+                node.clone(),
+            ));
+        }
+        for reply in [
+            Reply::Done,
+            Reply::Tuple(tuple),
+            Reply::NoTuple,
+            Reply::Timeout,
+            Reply::Failed(String::from("broken")),
+            Reply::Count(usize::MAX),
+            Reply::Denied(String::from("worker")),
+            Reply::Closed,
+        ] {
+            check_message(Message::reply(3, reply, node.clone()));
+        }
+        for admin in [
+            Admin::Ping,
+            Admin::Count,
+            Admin::Purge(template),
+            Admin::Evict,
+            Admin::Relink("unix:/tmp/linda.sock".parse().unwrap()),
+            Admin::Leave,
+        ] {
+            check_message(Message::admin(0, admin, node.clone()));
+        }
+    }
+
+    #[test]
+    fn reject_truncated_message() {
+        let node: Address = "[::1]:4000".parse().unwrap();
+        let bytes = Message::reply(1, Reply::Done, node).to_bytes();
+        for len in 0..bytes.len() {
+            assert_eq!(Message::from_bytes(&mut &bytes[..len]), None);
+        }
+    }
+
+    #[test]
+    fn describe_errors() {
+        let recv = |bytes: &[u8]| Message::recv(&mut &bytes[..]).unwrap_err();
+        let mut oversized = (MAX_MESSAGE_SIZE + 1).to_le_bytes().to_vec();
+        oversized.push(0);
+        assert_eq!(
+            recv(&oversized).to_string(),
+            format!(
+                "Failed to decode message: message of {} bytes exceeds the limit of \
+                 {MAX_MESSAGE_SIZE} bytes",
+                MAX_MESSAGE_SIZE + 1
+            )
+        );
+
+        let mut unknown = 1usize.to_le_bytes().to_vec();
+        unknown.push(42);
+        assert_eq!(
+            recv(&unknown).to_string(),
+            "Failed to decode message: unknown message type 42"
+        );
+
+        let mut truncated = 2usize.to_le_bytes().to_vec();
+        truncated.extend_from_slice(&[REPLY_ID, 0]);
+        assert!(recv(&truncated)
+            .to_string()
+            .ends_with("malformed reply message of 2 bytes"));
+
+        let e = recv(&[1, 0]);
+        assert!(error::Error::source(&e).is_some());
+        assert!(e.describe().starts_with("Connection failed: "));
+
+        let e = LindaError::NoTuple {
+            template: "(int: *, string: \"x\")".parse().unwrap(),
+            node: None,
+        };
+        assert_eq!(e.describe(), "No tuple matching (int: *, string: \"x\")");
     }
 
     #[test]
@@ -207,17 +876,19 @@ mod tests {
         ));
         tuple.push(Request::new(Value::int(36), ComparisonOperator::GE));
 
-        let msg = Message::request(tuple, "[::1]:0".parse().unwrap());
+        let identity = Some(String::from("worker"));
+        let msg = Message::request(tuple, Right::In, identity, "[::1]:0".parse().unwrap());
         let msg_clone = msg.clone();
 
-        thread::spawn(move || {
-            let listener = net::TcpListener::bind("127.0.0.1:1999").unwrap();
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let receiver = thread::spawn(move || {
             let (mut stream, _) = listener.accept().unwrap();
-            assert_eq!(Message::recv(&mut stream).unwrap(), msg);
+            Message::recv(&mut stream).unwrap()
         });
 
-        thread::sleep(time::Duration::from_millis(100));
-        let mut stream = net::TcpStream::connect("127.0.0.1:1999").unwrap();
+        let mut stream = net::TcpStream::connect(address).unwrap();
         msg_clone.send(&mut stream).unwrap();
+        assert_eq!(receiver.join().unwrap(), msg);
     }
 }
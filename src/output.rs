@@ -0,0 +1,292 @@
+use std::{fmt::Write, str, time};
+
+use crate::{
+    message::LindaError,
+    parser::ParseError,
+    stats::{Histogram, Stats, LATENCY_BOUNDS},
+    tuple::*,
+};
+
+const INVALID_FORMAT: &str = "Output format needs to be either 'text' or 'json'!";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+pub enum Outcome<'a> {
+    Done,
+    Tuple(&'a Tuple<Value>),
+    Tuples(&'a [Tuple<Value>]),
+    Count(usize),
+    Text(&'a str),
+    Stats(&'a Stats),
+    Failed(&'a LindaError),
+    Invalid(&'a ParseError),
+}
+
+pub struct Record<'a> {
+    pub line: Option<usize>,
+    pub input: &'a str,
+    pub command: Option<&'static str>,
+    pub outcome: Outcome<'a>,
+    pub elapsed: time::Duration,
+}
+
+impl str::FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(INVALID_FORMAT),
+        }
+    }
+}
+
+impl Record<'_> {
+    pub fn to_json(&self) -> String {
+        let mut fields = Vec::new();
+        if let Some(line) = self.line {
+            fields.push(format!("\"line\":{line}"));
+        }
+        fields.push(format!("\"input\":{}", json_string(self.input)));
+        if let Some(command) = self.command {
+            fields.push(format!("\"command\":{}", json_string(command)));
+        }
+
+        match &self.outcome {
+            Outcome::Done => fields.push(String::from("\"status\":\"ok\"")),
+            Outcome::Tuple(tuple) => {
+                fields.push(String::from("\"status\":\"ok\""));
+                fields.push(format!("\"tuple\":{}", json_tuple(tuple)));
+            }
+            Outcome::Tuples(tuples) => {
+                let tuples: Vec<String> = tuples.iter().map(json_tuple).collect();
+                fields.push(String::from("\"status\":\"ok\""));
+                fields.push(format!("\"tuples\":[{}]", tuples.join(",")));
+            }
+            Outcome::Count(count) => {
+                fields.push(String::from("\"status\":\"ok\""));
+                fields.push(format!("\"count\":{count}"));
+            }
+            Outcome::Text(text) => {
+                fields.push(String::from("\"status\":\"ok\""));
+                fields.push(format!("\"text\":{}", json_string(text)));
+            }
+            Outcome::Stats(stats) => {
+                fields.push(String::from("\"status\":\"ok\""));
+                fields.push(format!("\"stats\":{}", json_stats(stats)));
+            }
+            Outcome::Failed(e) => {
+                fields.push(String::from("\"status\":\"error\""));
+                fields.push(format!(
+                    "\"error\":{{\"code\":{},\"message\":{}}}",
+                    json_string(e.code()),
+                    json_string(&e.describe())
+                ));
+            }
+            Outcome::Invalid(e) => {
+                fields.push(String::from("\"status\":\"error\""));
+                fields.push(format!(
+                    "\"error\":{{\"code\":\"PARSE_ERROR\",\"message\":{},\"line\":{},\"column\":{}}}",
+                    json_string(e.message),
+                    e.line,
+                    e.column
+                ));
+            }
+        }
+        fields.push(format!(
+            "\"elapsed_ms\":{:.3}",
+            self.elapsed.as_secs_f64() * 1000.0
+        ));
+
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+fn json_tuple(tuple: &Tuple<Value>) -> String {
+    let values: Vec<String> = tuple
+        .iter()
+        .map(|value| {
+            let json = match value {
+                Value::Int(Some(i)) => i.to_string(),
+                Value::Float(Some(f)) if f.is_finite() => format!("{f:?}"),
+                Value::Float(Some(f)) => json_string(&f.to_string()),
+                Value::String(Some(s)) => json_string(s),
+                _ => String::from("null"),
+            };
+            format!(
+                "{{\"type\":{},\"value\":{json}}}",
+                json_string(value.type_name())
+            )
+        })
+        .collect();
+
+    format!("[{}]", values.join(","))
+}
+
+fn json_stats(stats: &Stats) -> String {
+    format!(
+        "{{\"local_tuples\":{},\"received\":{},\"forwarded\":{},\"satisfied_locally\":{},\
+         \"satisfied_remotely\":{},\"timeouts\":{},\"input_latency\":{},\"errors\":{},\
+         \"departures\":{},\"placed\":{},\"rebalanced\":{},\
+         \"routed\":{}}}",
+        stats.local_tuples,
+        stats.received,
+        stats.forwarded,
+        stats.satisfied_locally,
+        stats.satisfied_remotely,
+        stats.timeouts,
+        json_histogram(&stats.input_latency),
+        stats.errors,
+        stats.departures,
+        stats.placed,
+        stats.rebalanced,
+        stats.routed
+    )
+}
+
+// Buckets carry their upper bound in milliseconds; the open last bucket has none.
+fn json_histogram(histogram: &Histogram) -> String {
+    let buckets: Vec<String> = histogram
+        .buckets
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let bound = match LATENCY_BOUNDS.get(i) {
+                Some(bound) => bound.as_millis().to_string(),
+                None => String::from("null"),
+            };
+            format!("{{\"le_ms\":{bound},\"count\":{count}}}")
+        })
+        .collect();
+
+    format!(
+        "{{\"count\":{},\"sum_ms\":{:.3},\"buckets\":[{}]}}",
+        histogram.count(),
+        histogram.sum.as_secs_f64() * 1000.0,
+        buckets.join(",")
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(result, "\\u{:04x}", c as u32);
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn record(outcome: Outcome) -> String {
+        Record {
+            line: None,
+            input: "in (int: *)",
+            command: Some("in"),
+            outcome,
+            elapsed: time::Duration::from_micros(1500),
+        }
+        .to_json()
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("json".parse(), Ok(Format::Json));
+        assert_eq!("Text".parse(), Ok(Format::Text));
+        assert_eq!("xml".parse::<Format>(), Err(INVALID_FORMAT));
+    }
+
+    #[test]
+    fn tuple_record() {
+        let tuple = Tuple::from_vec(vec![
+            Value::int(1),
+            Value::float(2.5),
+            Value::float(f64::NEG_INFINITY),
+            Value::string(String::from("a\"\n\u{1}")),
+            Value::Int(None),
+        ]);
+        assert_eq!(
+            record(Outcome::Tuple(&tuple)),
+            "{\"input\":\"in (int: *)\",\"command\":\"in\",\"status\":\"ok\",\"tuple\":[\
+             {\"type\":\"int\",\"value\":1},{\"type\":\"float\",\"value\":2.5},\
+             {\"type\":\"float\",\"value\":\"-inf\"},\
+             {\"type\":\"string\",\"value\":\"a\\\"\\n\\u0001\"},\
+             {\"type\":\"int\",\"value\":null}],\"elapsed_ms\":1.500}"
+        );
+    }
+
+    #[test]
+    fn error_record() {
+        let e = LindaError::Timeout {
+            template: "(int: *)".parse().unwrap(),
+            node: Some("127.0.0.1:1999".parse().unwrap()),
+            elapsed: time::Duration::from_millis(1500),
+        };
+        assert_eq!(
+            record(Outcome::Failed(&e)),
+            "{\"input\":\"in (int: *)\",\"command\":\"in\",\"status\":\"error\",\
+             \"error\":{\"code\":\"TIMEOUT\",\"message\":\"Timed out after 1.500s waiting for a \
+             tuple matching (int: *) at 127.0.0.1:1999\"},\"elapsed_ms\":1.500}"
+        );
+
+        let e = Parser::new("out (1 2)").parse().unwrap_err();
+        let json = Record {
+            line: Some(3),
+            input: "out (1 2)",
+            command: None,
+            outcome: Outcome::Invalid(&e),
+            elapsed: time::Duration::ZERO,
+        }
+        .to_json();
+        assert_eq!(
+            json,
+            "{\"line\":3,\"input\":\"out (1 2)\",\"status\":\"error\",\"error\":{\"code\":\
+             \"PARSE_ERROR\",\"message\":\"Tuple elements need to be separated with commas \
+             (',')!\",\"line\":1,\"column\":8},\"elapsed_ms\":0.000}"
+        );
+    }
+
+    #[test]
+    fn stats_record() {
+        let mut buckets = vec![0; LATENCY_BOUNDS.len() + 1];
+        buckets[0] = 1;
+        let stats = Stats {
+            local_tuples: 3,
+            forwarded: 7,
+            input_latency: Histogram {
+                buckets,
+                sum: time::Duration::from_micros(250),
+            },
+            ..Stats::default()
+        };
+        let json = record(Outcome::Stats(&stats));
+        assert!(json.contains(
+            "\"stats\":{\"local_tuples\":3,\"received\":0,\"forwarded\":7,\"satisfied_locally\":0,\
+             \"satisfied_remotely\":0,\"timeouts\":0,\"input_latency\":{\"count\":1,\
+             \"sum_ms\":0.250,\"buckets\":[{\"le_ms\":1,\"count\":1},{\"le_ms\":5,\"count\":0}"
+        ));
+        assert!(json.contains(
+            "{\"le_ms\":null,\"count\":0}]},\"errors\":0,\"departures\":0,\
+             \"placed\":0,\"rebalanced\":0,\"routed\":0}"
+        ));
+    }
+}
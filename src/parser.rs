@@ -1,110 +1,317 @@
-use std::str::Chars;
+use std::{
+    error, fmt,
+    str::{self, Chars},
+    time,
+};
 
 use crate::tuple::*;
 use crate::utils::*;
 
 pub struct Parser<'a> {
+    input: &'a str,
     curr: Option<char>,
     it: Chars<'a>,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: &'static str,
+    pub line: usize,
+    pub column: usize,
+    pub found: Option<String>,
+    pub expected: &'static str,
+    source_line: String,
 }
 
 const INVALID_COMMAND: &str = "Invalid command given!";
 const NO_OPENING_PARENTHESIS: &str = "Tuple needs to start with opening parenthesis ('(')!";
-const ERROR_PARSING_TUPLE: &str = "Encountered an error while parsing tuple values!";
 const NO_CLOSING_PARENTHESIS: &str = "Tuple needs to end with closing parenthesis (')')!";
+const MISSING_SEPARATOR: &str = "Tuple elements need to be separated with commas (',')!";
+const TRAILING_INPUT: &str = "Unexpected input after tuple!";
+const INVALID_VALUE: &str = "Invalid tuple value!";
+const INVALID_NUMBER: &str = "Invalid numeric literal!";
+const NUMBER_OUT_OF_RANGE: &str = "Integer literal out of range!";
+const UNTERMINATED_STRING: &str = "String needs to end with a quote ('\"')!";
+const UNTERMINATED_RAW_STRING: &str = "Raw string needs to end with a single quote (\"'\")!";
+const INVALID_ESCAPE: &str = "Invalid escape sequence in string!";
+const UNKNOWN_TYPE: &str = "Unknown type name!";
+const MISSING_COLON: &str = "Type name needs to be followed by a colon (':')!";
+const INVALID_OPERATOR: &str = "Invalid comparison operator!";
+const TYPE_MISMATCH: &str = "Value does not match the declared type!";
+const INVALID_TIMEOUT: &str = "Invalid timeout!";
+const MISSING_PATH: &str = "File name needed!";
+
+const EXPECTED_COMMAND: &str =
+    "one of: out, in, rd, inp, rdp, ls, count, dump, load, stats, help, exit";
+const EXPECTED_VALUE: &str = "a number or a string";
+const EXPECTED_ESCAPE: &str = "one of: \\n, \\r, \\t, \\0, \\\\, \\\", \\', \\u{...}";
+const EXPECTED_NUMBER: &str = "a decimal, hexadecimal (0x), octal (0o) or binary (0b) number";
+const EXPECTED_INT: &str = "an integer between -2147483648 and 2147483647";
+const EXPECTED_TYPE: &str = "one of: int, float, string";
+const EXPECTED_SEPARATOR: &str = "',' or ')'";
+const EXPECTED_TIMEOUT: &str = "a duration such as 500ms, 2s, 1m or 1h, or forever";
+const EXPECTED_PATH: &str = "a file name, quoted if it contains spaces";
+
+impl ParseError {
+    pub fn source_line(&self) -> &str {
+        &self.source_line
+    }
+}
 
-impl<'a> Parser<'a> {
-    pub fn new(s: &'a String) -> Parser<'a> {
-        let it = s.chars();
-        let curr = None;
-        let mut parser = Parser { it, curr };
-        parser.next();
-
-        parser
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let found = match &self.found {
+            Some(token) => format!("'{token}'"),
+            None => String::from("end of input"),
+        };
+        writeln!(
+            f,
+            "{} (line {}, column {}: expected {}, found {found})",
+            self.message, self.line, self.column, self.expected
+        )?;
+
+        let padding: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(f, "{}", self.source_line)?;
+        write!(f, "{padding}^")
     }
+}
+
+impl error::Error for ParseError {}
 
-    pub fn parse(&mut self) -> Result<Command, &'static str> {
-        match &self.word().to_lowercase()[..] {
-            "out" => Ok(Command::Out(self.tuple()?)),
-            "in" => Ok(Command::In(self.request()?, self.number() as Timeout)),
-            "rd" | "read" => Ok(Command::Rd(self.request()?, self.number() as Timeout)),
-            "inp" => Ok(Command::Inp(self.request()?)),
-            "rdp" | "readp" => Ok(Command::Rdp(self.request()?)),
-            "help" => Ok(Command::Help),
-            "exit" => Ok(Command::Exit),
-            _ => Err(INVALID_COMMAND),
+impl<'a> Parser<'a> {
+    pub fn new(s: &'a str) -> Parser<'a> {
+        let mut it = s.chars();
+        let curr = it.next();
+
+        Parser {
+            input: s,
+            curr,
+            it,
+            line: 1,
+            column: 1,
         }
     }
 
-    fn tuple(&mut self) -> Result<Tuple<Value>, &'static str> {
+    pub fn parse(&mut self) -> Result<Command, ParseError> {
         self.skip_ws();
-        if !self.check('(') {
-            return Err(NO_OPENING_PARENTHESIS);
-        }
+        let (line, column) = (self.line, self.column);
+        let word = self.word();
+
+        let command = match command_name(&word) {
+            Some("out") => Command::Out(self.tuple()?),
+            Some("in") => Command::In(self.request()?, self.timeout()?),
+            Some("rd") => Command::Rd(self.request()?, self.timeout()?),
+            Some("inp") => Command::Inp(self.request()?),
+            Some("rdp") => Command::Rdp(self.request()?),
+            Some("ls") => Command::Ls(self.optional_request()?),
+            Some("count") => Command::Count(self.optional_request()?),
+            Some("dump") => Command::Dump(self.path()?),
+            Some("load") => Command::Load(self.path()?),
+            Some("stats") => Command::Stats,
+            Some("help") => Command::Help(self.help_topic()?),
+            Some("exit") => Command::Exit,
+            _ => {
+                let found = if word.is_empty() { None } else { Some(word) };
+                return Err(self.error_at(line, column, INVALID_COMMAND, EXPECTED_COMMAND, found));
+            }
+        };
+        self.end()?;
+
+        Ok(command)
+    }
+
+    fn tuple(&mut self) -> Result<Tuple<Value>, ParseError> {
+        self.open()?;
 
         let mut values = Tuple::new();
-        while let Some(_) = self.curr {
-            if self.check(')') {
+        loop {
+            if self.close()? {
                 return Ok(values);
             }
 
-            match self.value() {
-                Some(val) => values.push(val),
-                None => return Err(ERROR_PARSING_TUPLE),
-            }
-
-            self.check(',');
+            values.push(self.value()?);
+            self.separator()?;
         }
-
-        Err(NO_CLOSING_PARENTHESIS)
     }
 
-    fn request(&mut self) -> Result<Tuple<Request>, &'static str> {
-        self.skip_ws();
-        if !self.check('(') {
-            return Err(NO_OPENING_PARENTHESIS);
-        }
+    fn request(&mut self) -> Result<Tuple<Request>, ParseError> {
+        self.open()?;
 
         let mut requests = Tuple::new();
-        while let Some(_) = self.curr {
-            if self.check(')') {
+        loop {
+            if self.close()? {
                 return Ok(requests);
             }
 
-            let type_name = match self.type_name() {
-                Some(val) => val,
-                _ => return Err(ERROR_PARSING_TUPLE),
-            };
-
-            let operator = match self.operator() {
-                Some(op) => op,
-                _ => return Err(ERROR_PARSING_TUPLE),
-            };
+            let type_name = self.type_name()?;
+            let operator = self.operator()?;
 
             let value = if operator != ComparisonOperator::ANY {
                 self.skip_ws();
-                match self.value() {
-                    Some(val) if val.is_same_type(&type_name) => val,
-                    _ => return Err(ERROR_PARSING_TUPLE),
+                let (line, column) = (self.line, self.column);
+                match self.value()? {
+                    val if val.is_same_type(&type_name) => val,
+                    val => {
+                        let expected = match type_name {
+                            Value::Int(_) => "an int value",
+                            Value::Float(_) => "a float value",
+                            Value::String(_) => "a string value",
+                        };
+                        return Err(self.error_at(
+                            line,
+                            column,
+                            TYPE_MISMATCH,
+                            expected,
+                            Some(val.to_string()),
+                        ));
+                    }
                 }
             } else {
                 type_name
             };
 
             requests.push(Request::new(value, operator));
-            self.check(',');
+            self.separator()?;
         }
+    }
+
+    // Without a template, commands like `ls` cover every tuple.
+    fn optional_request(&mut self) -> Result<Option<Tuple<Request>>, ParseError> {
+        self.skip_ws();
+        match self.curr {
+            Some(_) => Ok(Some(self.request()?)),
+            None => Ok(None),
+        }
+    }
 
-        Err(NO_CLOSING_PARENTHESIS)
+    fn path(&mut self) -> Result<String, ParseError> {
+        self.skip_ws();
+        let quoted = match self.curr {
+            Some('"') => self.string()?,
+            Some('\'') => self.raw_string()?,
+            Some(_) => return Ok(self.word()),
+            None => return Err(self.error(MISSING_PATH, EXPECTED_PATH)),
+        };
+
+        match quoted {
+            Value::String(Some(path)) => Ok(path),
+            _ => unreachable!("strings parse to string values"),
+        }
+    }
+
+    fn help_topic(&mut self) -> Result<Option<String>, ParseError> {
+        self.skip_ws();
+        let (line, column) = (self.line, self.column);
+        let word = self.word();
+        if word.is_empty() {
+            return Ok(None);
+        }
+
+        match command_name(&word) {
+            Some(name) => Ok(Some(String::from(name))),
+            None => Err(self.error_at(line, column, INVALID_COMMAND, EXPECTED_COMMAND, Some(word))),
+        }
+    }
+
+    pub fn at_end(&mut self) -> bool {
+        self.skip_ws();
+        self.curr.is_none()
+    }
+
+    fn open(&mut self) -> Result<(), ParseError> {
+        if self.check('(') {
+            Ok(())
+        } else {
+            Err(self.error(NO_OPENING_PARENTHESIS, "'('"))
+        }
+    }
+
+    fn close(&mut self) -> Result<bool, ParseError> {
+        if self.check(')') {
+            Ok(true)
+        } else if self.curr.is_none() {
+            Err(self.error(NO_CLOSING_PARENTHESIS, "')'"))
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn separator(&mut self) -> Result<(), ParseError> {
+        self.skip_ws();
+        match self.curr {
+            Some(')') => Ok(()),
+            Some(',') => {
+                self.next();
+                Ok(())
+            }
+            Some(_) => Err(self.error(MISSING_SEPARATOR, EXPECTED_SEPARATOR)),
+            None => Err(self.error(NO_CLOSING_PARENTHESIS, EXPECTED_SEPARATOR)),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), ParseError> {
+        self.skip_ws();
+        match self.curr {
+            Some(_) => Err(self.error(TRAILING_INPUT, "end of input")),
+            None => Ok(()),
+        }
+    }
+
+    fn error(&self, message: &'static str, expected: &'static str) -> ParseError {
+        let found = self.curr.map(String::from);
+        self.error_at(self.line, self.column, message, expected, found)
+    }
+
+    fn error_at(
+        &self,
+        line: usize,
+        column: usize,
+        message: &'static str,
+        expected: &'static str,
+        found: Option<String>,
+    ) -> ParseError {
+        let source_line = self.input.lines().nth(line - 1).unwrap_or("");
+
+        ParseError {
+            message,
+            line,
+            column,
+            found,
+            expected,
+            source_line: String::from(source_line),
+        }
     }
 
     fn next(&mut self) {
+        match self.curr {
+            Some('\n') => {
+                self.line += 1;
+                self.column = 1;
+            }
+            Some(_) => self.column += 1,
+            None => (),
+        }
         self.curr = self.it.next();
     }
 
     fn skip_ws(&mut self) {
-        while matches!(self.curr, Some(c) if c.is_whitespace()) {
-            self.next();
+        loop {
+            match self.curr {
+                Some(c) if c.is_whitespace() => self.next(),
+                Some('#') => {
+                    while !matches!(self.curr, None | Some('\n')) {
+                        self.next();
+                    }
+                }
+                _ => break,
+            }
         }
     }
 
@@ -128,97 +335,239 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn number(&mut self) -> u32 {
+    // A number directly followed by its unit, or `forever`. Whatever comes after it, such as
+    // a comment, is left to the caller.
+    fn timeout(&mut self) -> Result<Timeout, ParseError> {
         self.skip_ws();
-        let mut result = 0;
+        let (line, column) = (self.line, self.column);
+        let mut token = self.take_while(|c| c.is_ascii_digit());
+        token.push_str(&self.take_while(char::is_alphabetic));
+
+        let timeout = match self.curr {
+            Some(c) if continues_literal(c) => None,
+            _ if token.is_empty() => return Ok(Timeout::Forever),
+            _ => parse_timeout(&token),
+        };
+        match timeout {
+            Some(timeout) => Ok(timeout),
+            None => {
+                token.push_str(&self.take_while(continues_literal));
+                Err(self.error_at(line, column, INVALID_TIMEOUT, EXPECTED_TIMEOUT, Some(token)))
+            }
+        }
+    }
+
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> String {
+        let mut result = String::new();
         while let Some(c) = self.curr {
-            if c.is_digit(10) {
-                result = result * 10 + c as u32 - '0' as u32;
-                self.next();
-            } else {
+            if !accept(c) {
                 break;
             }
+
+            result.push(c);
+            self.next();
         }
 
         result
     }
 
-    fn string(&mut self) -> Option<Value> {
+    fn string(&mut self) -> Result<Value, ParseError> {
         let mut result = String::new();
         self.next();
 
         while let Some(c) = self.curr {
-            if c == '"' {
-                if result.ends_with('\\') {
-                    result.pop();
-                } else {
+            match c {
+                '"' => {
+                    self.next();
+                    return Ok(Value::string(result));
+                }
+                '\\' => result.push(self.escape()?),
+                c => {
+                    result.push(c);
                     self.next();
-                    return Some(Value::string(result));
                 }
             }
+        }
+
+        Err(self.error(UNTERMINATED_STRING, "'\"'"))
+    }
+
+    fn raw_string(&mut self) -> Result<Value, ParseError> {
+        let mut result = String::new();
+        self.next();
+
+        while let Some(c) = self.curr {
+            self.next();
+            if c == '\'' {
+                return Ok(Value::string(result));
+            }
 
             result.push(c);
+        }
+
+        Err(self.error(UNTERMINATED_RAW_STRING, "\"'\""))
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let (line, column) = (self.line, self.column);
+        let mut sequence = String::from('\\');
+        self.next();
+
+        let c = match self.curr {
+            Some(c) => c,
+            None => return Err(self.error(UNTERMINATED_STRING, "'\"'")),
+        };
+        sequence.push(c);
+        self.next();
+
+        let escaped = match c {
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            '0' => Some('\0'),
+            '\\' | '"' | '\'' => Some(c),
+            'u' => self.unicode_escape(&mut sequence),
+            _ => None,
+        };
+
+        match escaped {
+            Some(c) => Ok(c),
+            None => Err(self.error_at(
+                line,
+                column,
+                INVALID_ESCAPE,
+                EXPECTED_ESCAPE,
+                Some(sequence),
+            )),
+        }
+    }
+
+    fn unicode_escape(&mut self, sequence: &mut String) -> Option<char> {
+        if self.curr != Some('{') {
+            return None;
+        }
+        sequence.push('{');
+        self.next();
+
+        let mut digits = String::new();
+        while let Some(c) = self.curr {
+            sequence.push(c);
             self.next();
+            if c == '}' {
+                if digits.is_empty() || digits.len() > 6 {
+                    return None;
+                }
+                return u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32);
+            }
+
+            if !c.is_ascii_hexdigit() {
+                return None;
+            }
+            digits.push(c);
         }
 
         None
     }
 
-    fn value(&mut self) -> Option<Value> {
+    fn value(&mut self) -> Result<Value, ParseError> {
+        self.skip_ws();
         match self.curr {
             Some('"') => self.string(),
-            Some(c) if c.is_digit(10) || c == '+' || c == '-' || c == '.' => {
-                let mut sign = 1;
-                if self.check('-') {
-                    sign = -1;
-                } else {
-                    self.check('+');
-                }
+            Some('\'') => self.raw_string(),
+            Some(c) if c.is_alphabetic() && self.at_type_name() => self.empty_value(),
+            Some(c) if c.is_alphanumeric() || c == '+' || c == '-' || c == '.' => {
+                self.number_literal()
+            }
+            _ => Err(self.error(INVALID_VALUE, EXPECTED_VALUE)),
+        }
+    }
 
-                if let Some(c) = self.curr {
-                    if !c.is_digit(10) && c != '.' {
-                        return None;
-                    }
-                }
+    fn at_type_name(&self) -> bool {
+        let name: String = self
+            .curr
+            .into_iter()
+            .chain(self.it.clone())
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
 
-                let result = sign * self.number() as i32;
-                if self.check('.') {
-                    let mut decimal = self.number() as f64;
-                    if decimal != 0.0 {
-                        decimal /= 10_f64.powf(decimal.log10().ceil());
-                    }
+        matches!(&name.to_lowercase()[..], "int" | "float" | "string")
+    }
 
-                    Some(Value::float(result as f64 + sign as f64 * decimal))
-                } else {
-                    Some(Value::int(result))
-                }
+    // A value without contents, e.g. `int: *`.
+    fn empty_value(&mut self) -> Result<Value, ParseError> {
+        let value = self.type_name()?;
+        if self.check('*') {
+            Ok(value)
+        } else {
+            Err(self.error(INVALID_VALUE, "'*'"))
+        }
+    }
+
+    fn number_literal(&mut self) -> Result<Value, ParseError> {
+        let (line, column) = (self.line, self.column);
+        let mut token = String::new();
+        if let Some(c @ ('+' | '-')) = self.curr {
+            token.push(c);
+            self.next();
+        }
+
+        while let Some(c) = self.curr {
+            let exponent_sign = (c == '+' || c == '-')
+                && token.ends_with(['e', 'E'])
+                && radix_prefix(&token).is_none();
+            if !c.is_alphanumeric() && c != '_' && c != '.' && !exponent_sign {
+                break;
             }
-            _ => None,
+
+            token.push(c);
+            self.next();
+        }
+
+        match parse_number(&token) {
+            Ok(val) => Ok(val),
+            Err(_) if token.starts_with(char::is_alphabetic) => {
+                Err(self.error_at(line, column, INVALID_VALUE, EXPECTED_VALUE, Some(token)))
+            }
+            Err(NUMBER_OUT_OF_RANGE) => {
+                Err(self.error_at(line, column, NUMBER_OUT_OF_RANGE, EXPECTED_INT, Some(token)))
+            }
+            Err(message) => Err(self.error_at(line, column, message, EXPECTED_NUMBER, Some(token))),
         }
     }
 
-    fn type_name(&mut self) -> Option<Value> {
+    fn type_name(&mut self) -> Result<Value, ParseError> {
+        self.skip_ws();
+        let (line, column) = (self.line, self.column);
         let mut name = String::new();
         while let Some(c) = self.curr {
-            if self.check(':') {
-                return match &name.to_lowercase()[..] {
-                    "int" => Some(Value::Int(None)),
-                    "float" => Some(Value::Float(None)),
-                    "string" => Some(Value::String(None)),
-                    _ => None,
-                };
+            if !c.is_alphanumeric() && c != '_' {
+                break;
             }
 
             name.push(c);
             self.next();
         }
 
-        None
+        let value = match &name.to_lowercase()[..] {
+            "int" => Value::Int(None),
+            "float" => Value::Float(None),
+            "string" => Value::String(None),
+            "" => return Err(self.error(UNKNOWN_TYPE, EXPECTED_TYPE)),
+            _ => return Err(self.error_at(line, column, UNKNOWN_TYPE, EXPECTED_TYPE, Some(name))),
+        };
+
+        if self.check(':') {
+            Ok(value)
+        } else {
+            Err(self.error(MISSING_COLON, "':'"))
+        }
     }
 
-    fn operator(&mut self) -> Option<ComparisonOperator> {
+    fn operator(&mut self) -> Result<ComparisonOperator, ParseError> {
         self.skip_ws();
-        match self.curr {
+        let op = match self.curr {
             Some('=') => self.check_next('=', Some(ComparisonOperator::EQ), None),
             Some('!') => self.check_next('=', Some(ComparisonOperator::NEQ), None),
             Some('<') => self.check_next(
@@ -236,7 +585,9 @@ impl<'a> Parser<'a> {
                 Some(ComparisonOperator::ANY)
             }
             _ => Some(ComparisonOperator::EQ),
-        }
+        };
+
+        op.ok_or_else(|| self.error(INVALID_OPERATOR, "'='"))
     }
 
     fn word(&mut self) -> String {
@@ -255,27 +606,203 @@ impl<'a> Parser<'a> {
     }
 }
 
+fn command_name(word: &str) -> Option<&'static str> {
+    match &word.to_lowercase()[..] {
+        "read" => Some("rd"),
+        "readp" => Some("rdp"),
+        word => COMMANDS.iter().find(|name| **name == word).copied(),
+    }
+}
+
+// Characters that would make a timeout part of a longer, malformed literal.
+fn continues_literal(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '_' | '+' | '-')
+}
+
+fn parse_timeout(word: &str) -> Option<Timeout> {
+    if word.eq_ignore_ascii_case("forever") {
+        return Some(Timeout::Forever);
+    }
+
+    let split = word
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(word.len());
+    let (amount, unit) = word.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let millis = match &unit.to_lowercase()[..] {
+        "ms" => 1,
+        "" | "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return None,
+    };
+
+    let millis = amount.checked_mul(millis)?;
+    Some(Timeout::After(time::Duration::from_millis(millis)))
+}
+
+fn radix_prefix(literal: &str) -> Option<u32> {
+    let body = literal.trim_start_matches(['+', '-']);
+    match body.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0o" | "0O") => Some(8),
+        Some("0b" | "0B") => Some(2),
+        _ => None,
+    }
+}
+
+fn separated_digits(digits: &str, radix: u32) -> Result<String, &'static str> {
+    let chars: Vec<char> = digits.chars().collect();
+    let mut result = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_digit(radix) {
+            result.push(*c);
+        } else if *c != '_'
+            || i == 0
+            || !chars[i - 1].is_digit(radix)
+            || !matches!(chars.get(i + 1), Some(next) if next.is_digit(radix))
+        {
+            return Err(INVALID_NUMBER);
+        }
+    }
+
+    Ok(result)
+}
+
+fn parse_number(literal: &str) -> Result<Value, &'static str> {
+    let (negative, body) = match literal.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, literal.strip_prefix('+').unwrap_or(literal)),
+    };
+
+    match &body.to_ascii_lowercase()[..] {
+        "inf" | "infinity" if negative => return Ok(Value::float(f64::NEG_INFINITY)),
+        "inf" | "infinity" => return Ok(Value::float(f64::INFINITY)),
+        "nan" => return Ok(Value::float(f64::NAN)),
+        _ => (),
+    }
+
+    if let Some(radix) = radix_prefix(body) {
+        let digits = separated_digits(&body[2..], radix)?;
+        if digits.is_empty() {
+            return Err(INVALID_NUMBER);
+        }
+
+        let magnitude = match u64::from_str_radix(&digits, radix) {
+            Ok(val) => val as i128,
+            Err(_) => return Err(NUMBER_OUT_OF_RANGE),
+        };
+        let value = if negative { -magnitude } else { magnitude };
+        return match i32::try_from(value) {
+            Ok(val) => Ok(Value::int(val)),
+            Err(_) => Err(NUMBER_OUT_OF_RANGE),
+        };
+    }
+
+    let (mantissa, exponent) = match body.find(['e', 'E']) {
+        Some(i) => (&body[..i], Some(&body[i + 1..])),
+        None => (body, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+
+    let integer = separated_digits(integer, 10)?;
+    let fraction = match fraction {
+        Some(fraction) => Some(separated_digits(fraction, 10)?),
+        None => None,
+    };
+    if integer.is_empty() && fraction.as_ref().is_none_or(|f| f.is_empty()) {
+        return Err(INVALID_NUMBER);
+    }
+
+    let exponent = match exponent {
+        Some(exponent) => {
+            let (sign, digits) = match exponent.strip_prefix(['+', '-']) {
+                Some(digits) => (&exponent[..1], digits),
+                None => ("", exponent),
+            };
+            let digits = separated_digits(digits, 10)?;
+            if digits.is_empty() {
+                return Err(INVALID_NUMBER);
+            }
+            Some(format!("{sign}{digits}"))
+        }
+        None => None,
+    };
+
+    let mut literal = String::from(if negative { "-" } else { "" });
+    literal.push_str(&integer);
+    if fraction.is_none() && exponent.is_none() {
+        return match literal.parse() {
+            Ok(val) => Ok(Value::int(val)),
+            Err(_) => Err(NUMBER_OUT_OF_RANGE),
+        };
+    }
+
+    if let Some(fraction) = fraction {
+        literal.push('.');
+        literal.push_str(&fraction);
+    }
+    if let Some(exponent) = exponent {
+        literal.push('e');
+        literal.push_str(&exponent);
+    }
+
+    match literal.parse() {
+        Ok(val) => Ok(Value::float(val)),
+        Err(_) => Err(INVALID_NUMBER),
+    }
+}
+
+impl str::FromStr for Tuple<Value> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let tuple = parser.tuple()?;
+        parser.end()?;
+
+        Ok(tuple)
+    }
+}
+
+impl str::FromStr for Tuple<Request> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let tuple = parser.request()?;
+        parser.end()?;
+
+        Ok(tuple)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
 
-    fn parse_impl(input: &str) -> Result<Tuple<Value>, &'static str> {
-        Parser::new(&String::from(input)).tuple()
+    fn parse_impl(input: &str) -> Result<Tuple<Value>, ParseError> {
+        Parser::new(input).tuple()
     }
 
-    fn request_impl(input: &str) -> Result<Tuple<Request>, &'static str> {
-        Parser::new(&String::from(input)).request()
+    fn request_impl(input: &str) -> Result<Tuple<Request>, ParseError> {
+        Parser::new(input).request()
     }
 
-    fn command_impl(input: &str) -> Result<Command, &'static str> {
-        Parser::new(&String::from(input)).parse()
+    fn command_impl(input: &str) -> Result<Command, ParseError> {
+        Parser::new(input).parse()
     }
 
     fn parse(input: &str) -> Tuple<Value> {
         parse_impl(input).unwrap()
     }
 
-    fn parse_err(input: &str) -> &str {
+    fn parse_err(input: &str) -> ParseError {
         parse_impl(input).err().unwrap()
     }
 
@@ -287,10 +814,20 @@ mod tests {
         command_impl(input).unwrap()
     }
 
-    fn command_err(input: &str) -> &str {
+    fn request_err(input: &str) -> ParseError {
+        request_impl(input).err().unwrap()
+    }
+
+    fn command_err(input: &str) -> ParseError {
         command_impl(input).err().unwrap()
     }
 
+    fn check_err(err: &ParseError, message: &str, line: usize, column: usize, found: Option<&str>) {
+        assert_eq!(err.message, message);
+        assert_eq!((err.line, err.column), (line, column));
+        assert_eq!(err.found.as_deref(), found);
+    }
+
     #[test]
     fn test_integer() {
         let result = parse("(1)");
@@ -301,6 +838,20 @@ mod tests {
 
         let result = parse("(-3)");
         assert_eq!(result[0], Value::int(-3));
+
+        let result = parse("(2147483647, -2147483648, 007, 1_000_000)");
+        assert_eq!(result[0], Value::int(i32::MAX));
+        assert_eq!(result[1], Value::int(i32::MIN));
+        assert_eq!(result[2], Value::int(7));
+        assert_eq!(result[3], Value::int(1_000_000));
+
+        let result = parse("(0x1F, -0Xff, 0b1010, 0o17, 0x7fff_ffff, -0x8000_0000)");
+        assert_eq!(result[0], Value::int(31));
+        assert_eq!(result[1], Value::int(-255));
+        assert_eq!(result[2], Value::int(10));
+        assert_eq!(result[3], Value::int(15));
+        assert_eq!(result[4], Value::int(i32::MAX));
+        assert_eq!(result[5], Value::int(i32::MIN));
     }
 
     #[test]
@@ -313,6 +864,113 @@ mod tests {
 
         let result = parse("(-4.)");
         assert_eq!(result[0], Value::float(-4.0));
+
+        let result = parse("(1.05, 0.001, 2.1, 1e3, 2.5E-3, -1.5e+2, 1_000.000_1)");
+        assert_eq!(result[0], Value::float(1.05));
+        assert_eq!(result[1], Value::float(0.001));
+        assert_eq!(result[2], Value::float(2.1));
+        assert_eq!(result[3], Value::float(1e3));
+        assert_eq!(result[4], Value::float(2.5e-3));
+        assert_eq!(result[5], Value::float(-1.5e2));
+        assert_eq!(result[6], Value::float(1_000.000_1));
+
+        let result = parse("(inf, -Infinity, NaN, 1e400)");
+        assert_eq!(result[0], Value::float(f64::INFINITY));
+        assert_eq!(result[1], Value::float(f64::NEG_INFINITY));
+        assert!(matches!(result[2], Value::Float(Some(f)) if f.is_nan()));
+        assert_eq!(result[3], Value::float(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_number_err() {
+        for input in [
+            "(1_)", "(1__0)", "(1._5)", "(1_.5)", "(0x)", "(0xG)", "(0b102)", "(1e)", "(1e+)",
+            "(1.2.3)", "(1e5.5)", "(.)", "(+)", "(12abc)", "(0x1.5)", "(-e5)",
+        ] {
+            let result = parse_err(input);
+            check_err(
+                &result,
+                INVALID_NUMBER,
+                1,
+                2,
+                Some(&input[1..input.len() - 1]),
+            );
+            assert_eq!(result.expected, EXPECTED_NUMBER);
+        }
+
+        for input in [
+            "(2147483648)",
+            "(-2147483649)",
+            "(99999999999999999999999)",
+            "(0x8000_0000)",
+            "(-0x8000_0001)",
+            "(0b1_0000_0000_0000_0000_0000_0000_0000_0000)",
+            "(0xffff_ffff_ffff_ffff_ff)",
+        ] {
+            let result = parse_err(input);
+            check_err(
+                &result,
+                NUMBER_OUT_OF_RANGE,
+                1,
+                2,
+                Some(&input[1..input.len() - 1]),
+            );
+            assert_eq!(result.expected, EXPECTED_INT);
+        }
+
+        let result = parse_err("(infinite)");
+        check_err(&result, INVALID_VALUE, 1, 2, Some("infinite"));
+
+        let result = parse_err("(_1)");
+        check_err(&result, INVALID_VALUE, 1, 2, Some("_"));
+    }
+
+    fn literal(input: &str) -> Option<Value> {
+        let mut parser = Parser::new(input);
+        let value = parser.value().ok()?;
+        parser.end().ok()?;
+
+        Some(value)
+    }
+
+    fn rust_literal(input: &str) -> Option<Value> {
+        let lower = input.to_lowercase();
+        if lower.contains(['.', 'e']) || lower.contains("inf") || lower.contains("nan") {
+            input.parse().ok().map(Value::float)
+        } else {
+            input.parse().ok().map(Value::int)
+        }
+    }
+
+    fn same_value(lhs: &Option<Value>, rhs: &Option<Value>) -> bool {
+        match (lhs, rhs) {
+            (Some(Value::Float(Some(l))), Some(Value::Float(Some(r)))) => {
+                l.to_bits() == r.to_bits() || l.is_nan() && r.is_nan()
+            }
+            _ => lhs == rhs,
+        }
+    }
+
+    #[test]
+    fn test_number_exhaustive() {
+        const ALPHABET: [char; 8] = ['0', '1', '9', '.', 'e', 'E', '+', '-'];
+
+        let mut inputs = vec![String::new()];
+        for _ in 0..5 {
+            inputs = inputs
+                .iter()
+                .flat_map(|prefix| ALPHABET.iter().map(move |c| format!("{prefix}{c}")))
+                .collect();
+
+            for input in &inputs {
+                let (ours, rust) = (literal(input), rust_literal(input));
+                assert!(same_value(&ours, &rust), "{input}: {ours:?} != {rust:?}");
+            }
+        }
+
+        for input in ["inf", "-inf", "+infinity", "INF", "nan", "NaN", "-nan"] {
+            assert!(same_value(&literal(input), &rust_literal(input)), "{input}");
+        }
     }
 
     #[test]
@@ -322,9 +980,61 @@ mod tests {
 
         let result = parse("(\"te\\\"st\")");
         assert_eq!(result[0], Value::string(String::from("te\"st")));
+
+        let result = parse(r#"("a\\", "\n\r\t\0", "\'", "\u{41}\u{1F600}\u{10ffff}", "zażółć")"#);
+        assert_eq!(result[0], Value::string(String::from("a\\")));
+        assert_eq!(result[1], Value::string(String::from("\n\r\t\0")));
+        assert_eq!(result[2], Value::string(String::from("'")));
+        assert_eq!(
+            result[3],
+            Value::string(String::from("A\u{1F600}\u{10ffff}"))
+        );
+        assert_eq!(result[4], Value::string(String::from("zażółć")));
+
+        let result = parse(r#"('C:\new\table', '', '"quoted"')"#);
+        assert_eq!(result[0], Value::string(String::from(r"C:\new\table")));
+        assert_eq!(result[1], Value::string(String::new()));
+        assert_eq!(result[2], Value::string(String::from("\"quoted\"")));
+
+        let result = request(r"(string: 'a\b', string: != '\')");
+        check_request(
+            &result[0],
+            Value::string(String::from(r"a\b")),
+            ComparisonOperator::EQ,
+        );
+        check_request(
+            &result[1],
+            Value::string(String::from("\\")),
+            ComparisonOperator::NEQ,
+        );
     }
 
     #[test]
+    fn test_string_err() {
+        for (input, column, found) in [
+            (r#"("\q")"#, 3, r"\q"),
+            (r#"("ab\x41")"#, 5, r"\x"),
+            (r#"("\u41")"#, 3, r"\u"),
+            (r#"("\u{}")"#, 3, r"\u{}"),
+            (r#"("\u{1234567}")"#, 3, r"\u{1234567}"),
+            (r#"("\u{d800}")"#, 3, r"\u{d800}"),
+            (r#"("\u{110000}")"#, 3, r"\u{110000}"),
+            (r#"("\u{12g4}")"#, 3, r"\u{12g"),
+        ] {
+            let result = parse_err(input);
+            check_err(&result, INVALID_ESCAPE, 1, column, Some(found));
+            assert_eq!(result.expected, EXPECTED_ESCAPE);
+        }
+
+        let result = parse_err(r#"("abc\"#);
+        check_err(&result, UNTERMINATED_STRING, 1, 7, None);
+
+        let result = parse_err("('abc)");
+        check_err(&result, UNTERMINATED_RAW_STRING, 1, 7, None);
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
     fn test_multiple() {
         let result = parse("(+1, -3.14, \"test\", )");
         assert_eq!(result[0], Value::int(1));
@@ -335,13 +1045,195 @@ mod tests {
     #[test]
     fn test_err() {
         let result = parse_err("1");
-        assert_eq!(result, NO_OPENING_PARENTHESIS);
+        check_err(&result, NO_OPENING_PARENTHESIS, 1, 1, Some("1"));
 
         let result = parse_err("(1");
-        assert_eq!(result, NO_CLOSING_PARENTHESIS);
+        check_err(&result, NO_CLOSING_PARENTHESIS, 1, 3, None);
 
         let result = parse_err("(+-1)");
-        assert_eq!(result, ERROR_PARSING_TUPLE);
+        check_err(&result, INVALID_NUMBER, 1, 2, Some("+"));
+
+        let result = parse_err("(1 2)");
+        check_err(&result, MISSING_SEPARATOR, 1, 4, Some("2"));
+        assert_eq!(result.expected, EXPECTED_SEPARATOR);
+
+        let result = parse_err("(1, x)");
+        check_err(&result, INVALID_VALUE, 1, 5, Some("x"));
+
+        let result = parse_err("(\"abc");
+        check_err(&result, UNTERMINATED_STRING, 1, 6, None);
+
+        let result = parse_err("(\"abc\\");
+        check_err(&result, UNTERMINATED_STRING, 1, 7, None);
+
+        let result = parse_err("(1,\n  x)");
+        check_err(&result, INVALID_VALUE, 2, 3, Some("x"));
+        assert_eq!(result.source_line(), "  x)");
+    }
+
+    #[test]
+    fn test_request_err() {
+        let result = request_err("int: 1)");
+        check_err(&result, NO_OPENING_PARENTHESIS, 1, 1, Some("i"));
+
+        let result = request_err("(int: 1");
+        check_err(&result, NO_CLOSING_PARENTHESIS, 1, 8, None);
+
+        let result = request_err("(int: 1 float: 2.0)");
+        check_err(&result, MISSING_SEPARATOR, 1, 9, Some("f"));
+
+        let result = request_err("(int: \"a\")");
+        check_err(&result, TYPE_MISMATCH, 1, 7, Some("\"a\""));
+        assert_eq!(result.expected, "an int value");
+
+        let result = request_err("(string: < 2.5)");
+        check_err(&result, TYPE_MISMATCH, 1, 12, Some("2.5"));
+
+        let result = request_err("(int: >= )");
+        check_err(&result, INVALID_VALUE, 1, 10, Some(")"));
+    }
+
+    #[test]
+    fn test_type_name_err() {
+        let result = request_err("(integer: 1)");
+        check_err(&result, UNKNOWN_TYPE, 1, 2, Some("integer"));
+        assert_eq!(result.expected, EXPECTED_TYPE);
+
+        let result = request_err("(: 1)");
+        check_err(&result, UNKNOWN_TYPE, 1, 2, Some(":"));
+
+        let result = request_err("(int 1)");
+        check_err(&result, MISSING_COLON, 1, 6, Some("1"));
+
+        let result = request(" ( INT : 1 , Float:* )");
+        check_request(&result[0], Value::int(1), ComparisonOperator::EQ);
+        check_request(&result[1], Value::Float(None), ComparisonOperator::ANY);
+    }
+
+    #[test]
+    fn test_operator_err() {
+        let result = request_err("(int: = 1)");
+        check_err(&result, INVALID_OPERATOR, 1, 9, Some("1"));
+
+        let result = request_err("(int: ! 1)");
+        check_err(&result, INVALID_OPERATOR, 1, 9, Some("1"));
+
+        let result = request_err("(int: !");
+        check_err(&result, INVALID_OPERATOR, 1, 8, None);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let template = make_tuple(vec![Request::new(
+            Value::Int(None),
+            ComparisonOperator::ANY,
+        )]);
+        for (input, timeout) in [
+            ("in (int: *)", Timeout::Forever),
+            ("in (int: *) forever", Timeout::Forever),
+            ("in (int: *) FOREVER # no deadline", Timeout::Forever),
+            ("in (int: *) 0", Timeout::After(time::Duration::ZERO)),
+            (
+                "in (int: *) 500ms",
+                Timeout::After(time::Duration::from_millis(500)),
+            ),
+            (
+                "in (int: *) 2s",
+                Timeout::After(time::Duration::from_secs(2)),
+            ),
+            (
+                "in (int: *) 1m",
+                Timeout::After(time::Duration::from_secs(60)),
+            ),
+            (
+                "in (int: *) 2H",
+                Timeout::After(time::Duration::from_secs(7200)),
+            ),
+            (
+                "in (int: *) 5s#comment",
+                Timeout::After(time::Duration::from_secs(5)),
+            ),
+        ] {
+            assert_eq!(
+                command(input),
+                Command::In(template.clone(), timeout),
+                "{input}"
+            );
+        }
+
+        let result = command("rd (int: *) 250ms");
+        assert_eq!(
+            result,
+            Command::Rd(template, Timeout::After(time::Duration::from_millis(250)))
+        );
+
+        for (input, found) in [
+            ("in (int: *) 5x", "5x"),
+            ("in (int: *) -1s", "-1s"),
+            ("in (int: *) ms", "ms"),
+            ("in (int: *) 1.5s", "1.5s"),
+            ("in (int: *) 99999999999999999999", "99999999999999999999"),
+            ("rd (int: *) 9999999999999999h", "9999999999999999h"),
+            ("in (int: *) 2h30m", "2h30m"),
+        ] {
+            let result = command_err(input);
+            check_err(&result, INVALID_TIMEOUT, 1, 13, Some(found));
+            assert_eq!(result.expected, EXPECTED_TIMEOUT);
+        }
+
+        let result = command_err("in (int: *) 5s)");
+        check_err(&result, TRAILING_INPUT, 1, 15, Some(")"));
+    }
+
+    #[test]
+    fn test_comments() {
+        let result = command("out (1, # first\n 2) # done");
+        assert_eq!(
+            result,
+            Command::Out(make_tuple(vec![Value::int(1), Value::int(2)]))
+        );
+
+        let result = parse("(\"#\", '#')");
+        assert_eq!(result[0], Value::string(String::from("#")));
+        assert_eq!(result[1], Value::string(String::from("#")));
+
+        assert!(Parser::new("  # comment only").at_end());
+        assert!(!Parser::new("# comment\nexit").at_end());
+    }
+
+    #[test]
+    fn test_command_err() {
+        let result = command_err("q");
+        check_err(&result, INVALID_COMMAND, 1, 1, Some("q"));
+        assert_eq!(result.expected, EXPECTED_COMMAND);
+
+        let result = command_err("   ");
+        check_err(&result, INVALID_COMMAND, 1, 4, None);
+
+        let result = command_err("out (1) (2)");
+        check_err(&result, TRAILING_INPUT, 1, 9, Some("("));
+
+        let result = command_err("in (int: 1) 2 3");
+        check_err(&result, TRAILING_INPUT, 1, 15, Some("3"));
+
+        let result = command_err("out\t(1,\tx)");
+        check_err(&result, INVALID_VALUE, 1, 9, Some("x"));
+    }
+
+    #[test]
+    fn test_err_display() {
+        let result = command_err("out (1, x)");
+        assert_eq!(
+            result.to_string(),
+            "Invalid tuple value! (line 1, column 9: expected a number or a string, found 'x')\n\
+             out (1, x)\n        ^"
+        );
+
+        let result = command_err("out\t(1,\tx)\n");
+        assert!(result.to_string().ends_with("out\t(1,\tx)\n   \t   \t^"));
+
+        let result = command_err("in (int: 1");
+        assert!(result.to_string().contains("found end of input"));
     }
 
     fn check_request(request: &Request, value: Value, op: ComparisonOperator) {
@@ -349,6 +1241,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_request() {
         let result = request("(int: 1, float: >= 3.0, string: *, int: != 2, float: <= 3.14, string: < \"abc\", int: > 15, )");
 
@@ -384,7 +1277,7 @@ mod tests {
                     Value::Float(None),
                     ComparisonOperator::ANY
                 )]),
-                10 as Timeout
+                Timeout::After(time::Duration::from_secs(10))
             )
         );
 
@@ -396,7 +1289,7 @@ mod tests {
                     Value::string(String::from("a")),
                     ComparisonOperator::EQ
                 )]),
-                3 as Timeout
+                Timeout::After(time::Duration::from_secs(3))
             )
         );
 
@@ -413,12 +1306,193 @@ mod tests {
         assert_eq!(result, Command::Rdp(make_tuple(vec![])));
 
         let result = command("help");
-        assert_eq!(result, Command::Help);
+        assert_eq!(result, Command::Help(None));
+
+        let result = command("HELP Read");
+        assert_eq!(result, Command::Help(Some(String::from("rd"))));
+
+        let result = command_err("help me");
+        check_err(&result, INVALID_COMMAND, 1, 6, Some("me"));
 
         let result = command("exit");
         assert_eq!(result, Command::Exit);
 
+        let result = command("Stats");
+        assert_eq!(result, Command::Stats);
+
+        let result = command("ls");
+        assert_eq!(result, Command::Ls(None));
+
+        let result = command("count (int: > 3) # big ones");
+        assert_eq!(
+            result,
+            Command::Count(Some(make_tuple(vec![Request::new(
+                Value::int(3),
+                ComparisonOperator::GT
+            )])))
+        );
+
+        let result = command("dump 'my tuples.txt'");
+        assert_eq!(result, Command::Dump(String::from("my tuples.txt")));
+
+        let result = command("load /tmp/tuples.txt");
+        assert_eq!(result, Command::Load(String::from("/tmp/tuples.txt")));
+
+        let result = command_err("load");
+        check_err(&result, MISSING_PATH, 1, 5, None);
+
+        let result = command_err("ls 3");
+        check_err(&result, NO_OPENING_PARENTHESIS, 1, 4, Some("3"));
+
         let result = command_err("q");
-        assert_eq!(result, INVALID_COMMAND);
+        assert_eq!(result.message, INVALID_COMMAND);
+    }
+
+    #[test]
+    fn test_display() {
+        let result = parse("(1, 2.5, \"x\", -4.)");
+        assert_eq!(result.to_string(), "(1, 2.5, \"x\", -4.0)");
+
+        let result = parse("(\"a\\\\\", \"\\\"b\\\"\")");
+        assert_eq!(result[0], Value::string(String::from("a\\")));
+        assert_eq!(result.to_string(), "(\"a\\\\\", \"\\\"b\\\"\")");
+
+        let result = request("(int: >= 3, string: *, float: 2.5, string: != \"a\")");
+        assert_eq!(
+            result.to_string(),
+            "(int: >= 3, string: *, float: 2.5, string: != \"a\")"
+        );
+
+        let result = parse("(int: *, Float:*, string : *)");
+        assert_eq!(
+            result,
+            make_tuple(vec![
+                Value::Int(None),
+                Value::Float(None),
+                Value::String(None)
+            ])
+        );
+        assert_eq!(result.to_string(), "(int: *, float: *, string: *)");
+        let result = parse_err("(int: 3)");
+        check_err(&result, INVALID_VALUE, 1, 7, Some("3"));
+
+        assert_eq!(parse("()").to_string(), "()");
+    }
+
+    #[test]
+    fn test_from_str() {
+        let result: Tuple<Value> = " (1, \"a\") ".parse().unwrap();
+        assert_eq!(
+            result,
+            make_tuple(vec![Value::int(1), Value::string(String::from("a"))])
+        );
+
+        let result: Tuple<Request> = "(int: *)".parse().unwrap();
+        assert_eq!(
+            result,
+            make_tuple(vec![Request::new(
+                Value::Int(None),
+                ComparisonOperator::ANY
+            )])
+        );
+
+        let result = "(1) 2".parse::<Tuple<Value>>().err().unwrap();
+        assert_eq!(result.message, TRAILING_INPUT);
+        let result = "(int: *))".parse::<Tuple<Request>>().err().unwrap();
+        assert_eq!(result.message, TRAILING_INPUT);
+        let result = "1".parse::<Tuple<Value>>().err().unwrap();
+        assert_eq!(result.message, NO_OPENING_PARENTHESIS);
+    }
+
+    fn value_strategy() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            any::<i32>().prop_map(Value::int),
+            any::<f64>()
+                .prop_filter("NaN is not equal to itself", |f| !f.is_nan())
+                .prop_map(Value::float),
+            any::<String>().prop_map(Value::string),
+            Just(Value::Int(None)),
+            Just(Value::Float(None)),
+            Just(Value::String(None)),
+        ]
+    }
+
+    fn request_strategy() -> impl Strategy<Value = Request> {
+        let op = prop_oneof![
+            Just(ComparisonOperator::EQ),
+            Just(ComparisonOperator::NEQ),
+            Just(ComparisonOperator::GE),
+            Just(ComparisonOperator::GT),
+            Just(ComparisonOperator::LE),
+            Just(ComparisonOperator::LT),
+            Just(ComparisonOperator::ANY),
+        ];
+
+        (value_strategy(), op).prop_map(|(value, op)| match (value, op) {
+            (Value::Int(_), ComparisonOperator::ANY) => Request::new(Value::Int(None), op),
+            (Value::Float(_), ComparisonOperator::ANY) => Request::new(Value::Float(None), op),
+            (Value::String(_), ComparisonOperator::ANY) => Request::new(Value::String(None), op),
+            (value, op) => Request::new(value, op),
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn value_round_trip(values in prop::collection::vec(value_strategy(), 0..8)) {
+            let tuple = make_tuple(values);
+            prop_assert_eq!(tuple.to_string().parse::<Tuple<Value>>(), Ok(tuple));
+        }
+
+        #[test]
+        fn int_literal(i in any::<i64>()) {
+            let input = i.to_string();
+            prop_assert_eq!(literal(&input), rust_literal(&input));
+        }
+
+        #[test]
+        fn float_literal(f in any::<f64>()) {
+            for input in [format!("{f}"), format!("{f:?}"), format!("{f:e}"), format!("{f:E}")] {
+                let (ours, rust) = (literal(&input), rust_literal(&input));
+                prop_assert!(same_value(&ours, &rust), "{}: {:?} != {:?}", input, ours, rust);
+            }
+        }
+
+        #[test]
+        fn decimal_literal(input in "[+-]?[0-9]{0,20}(\\.[0-9]{0,20})?([eE][+-]?[0-9]{1,4})?") {
+            let (ours, rust) = (literal(&input), rust_literal(&input));
+            prop_assert!(same_value(&ours, &rust), "{}: {:?} != {:?}", input, ours, rust);
+        }
+
+        #[test]
+        fn radix_literal(i in any::<i64>()) {
+            let sign = if i < 0 { "-" } else { "" };
+            let expected = i32::try_from(i).ok().map(Value::int);
+            let magnitude = i.unsigned_abs();
+            prop_assert_eq!(literal(&format!("{sign}0x{magnitude:x}")), expected.clone());
+            prop_assert_eq!(literal(&format!("{sign}0X{magnitude:X}")), expected.clone());
+            prop_assert_eq!(literal(&format!("{sign}0o{magnitude:o}")), expected.clone());
+            prop_assert_eq!(literal(&format!("{sign}0b{magnitude:b}")), expected);
+        }
+
+        #[test]
+        fn separated_literal(input in "[0-9]{1,12}(\\.[0-9]{1,12})?", step in 1usize..4) {
+            let separated: String = input
+                .chars()
+                .enumerate()
+                .flat_map(|(i, c)| {
+                    let next_is_digit = input[i + 1..].starts_with(|n: char| n.is_ascii_digit());
+                    let separator = c.is_ascii_digit() && next_is_digit && i % step == 0;
+                    std::iter::once(c).chain(separator.then_some('_'))
+                })
+                .collect();
+            let (ours, rust) = (literal(&separated), rust_literal(&input));
+            prop_assert!(same_value(&ours, &rust), "{}: {:?} != {:?}", separated, ours, rust);
+        }
+
+        #[test]
+        fn request_round_trip(requests in prop::collection::vec(request_strategy(), 0..8)) {
+            let tuple = make_tuple(requests);
+            prop_assert_eq!(tuple.to_string().parse::<Tuple<Request>>(), Ok(tuple));
+        }
     }
 }
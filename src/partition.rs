@@ -0,0 +1,329 @@
+use std::{collections, io, marker, sync, time};
+
+use crate::{
+    balance::{fnv1a, Member},
+    message::LindaError,
+    space::TupleSpace,
+    stats::Stats,
+    transport::Address,
+    tuple::*,
+    Linda,
+};
+
+// Points each member takes on the hash ring; more of them spread the keys more evenly.
+const VIRTUAL_NODES: usize = 64;
+// How long a waiting operation without a bound key waits for a passing tuple before it sweeps
+// the ring again; keyed tuples go straight to their owner and never pass by.
+const SWEEP_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+// Tuples are owned by the member their `key` field hashes to, which serves them directly at
+// `endpoint`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Partition {
+    pub key: usize,
+    pub endpoint: Address,
+}
+
+// Consistent hashing over the members owning a partition: a member joining or leaving only
+// moves the keys next to its own points.
+#[derive(Debug, Default)]
+pub(crate) struct HashRing {
+    points: collections::BTreeMap<u64, usize>,
+}
+
+pub type Peer = sync::Arc<dyn TupleSpace + marker::Send + marker::Sync>;
+type Dial = Box<dyn Fn(&Address) -> io::Result<Peer> + marker::Send + marker::Sync>;
+
+// A ring node that sends keyed tuples and templates with a bound key straight to the owner
+// of the key. Everything else, and everything before the first census, takes the ring.
+pub struct Partitioned<Output: io::Write> {
+    ring: sync::Arc<Linda<Output>>,
+    key: usize,
+    dial: Dial,
+    peers: sync::Mutex<collections::HashMap<Address, Peer>>,
+}
+
+impl HashRing {
+    // `members` are indexed by the points; those without an endpoint own nothing.
+    pub fn new(members: &[Member]) -> HashRing {
+        let mut points = collections::BTreeMap::new();
+        for (i, member) in members.iter().enumerate() {
+            if member.endpoint.is_none() {
+                continue;
+            }
+            for replica in 0..VIRTUAL_NODES {
+                points.insert(hash(format!("{}#{replica}", member.address).as_bytes()), i);
+            }
+        }
+
+        HashRing { points }
+    }
+
+    pub fn owner(&self, key: &Value) -> Option<usize> {
+        let hash = hash(&key.to_bytes());
+        let mut clockwise = self.points.range(hash..).chain(self.points.iter());
+
+        clockwise.next().map(|(_, owner)| *owner)
+    }
+}
+
+// FNV-1a alone leaves similar inputs close together, so its result is mixed once more.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash = fnv1a(bytes);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+    hash ^ (hash >> 31)
+}
+
+// The key a template is bound to, if it only matches tuples with one value there.
+fn bound_key(template: &Tuple<Request>, key: usize) -> Option<&Value> {
+    template.get(key)?.exact()
+}
+
+impl<Output: 'static + io::Write + marker::Send> Partitioned<Output> {
+    // `dial` connects to another member's endpoint; the connections are kept for later
+    // operations.
+    pub fn new(
+        ring: sync::Arc<Linda<Output>>,
+        key: usize,
+        dial: impl Fn(&Address) -> io::Result<Peer> + marker::Send + marker::Sync + 'static,
+    ) -> Partitioned<Output> {
+        Partitioned {
+            ring,
+            key,
+            dial: Box::new(dial),
+            peers: sync::Mutex::new(collections::HashMap::new()),
+        }
+    }
+
+    fn owner(&self, key: &Value) -> Result<Option<(Address, Peer)>, LindaError> {
+        let member = match self.ring.owner(key) {
+            Some(member) => member,
+            None => return Ok(None),
+        };
+        if member.address == self.ring.node {
+            return Ok(Some((
+                member.address,
+                sync::Arc::new(self.ring.partition()),
+            )));
+        }
+        let endpoint = match member.endpoint {
+            Some(endpoint) => endpoint,
+            None => return Ok(None),
+        };
+
+        let mut peers = match self.peers.lock() {
+            Ok(guard) => guard,
+            Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+        };
+        if let Some(peer) = peers.get(&endpoint) {
+            return Ok(Some((member.address, peer.clone())));
+        }
+        let peer = (self.dial)(&endpoint).map_err(LindaError::IoFailure)?;
+        peers.insert(endpoint, peer.clone());
+
+        Ok(Some((member.address, peer)))
+    }
+
+    fn forget(&self, peer: &Peer) {
+        if let Ok(mut peers) = self.peers.lock() {
+            peers.retain(|_, known| !sync::Arc::ptr_eq(known, peer));
+        }
+    }
+
+    // Performs `operation` at the owner of `key`, or returns `None` if the ring has to
+    // handle it. A connection that failed is dialled again next time, and an owner that is
+    // leaving hands its tuples to the ring, which then takes over its keys.
+    fn routed<T>(
+        &self,
+        key: Option<&Value>,
+        operation: impl FnOnce(&dyn TupleSpace) -> Result<T, LindaError>,
+    ) -> Option<Result<T, LindaError>> {
+        let (owner, peer) = match key.map(|key| self.owner(key)) {
+            Some(Ok(Some(owner))) => owner,
+            Some(Err(e)) => return Some(Err(e)),
+            _ => return None,
+        };
+        self.ring.metrics.routed();
+
+        match operation(&*peer) {
+            Err(LindaError::ShutDown) if owner != self.ring.node => {
+                tracing::debug!(owner = %owner, "Owner is leaving - taking the ring instead");
+                self.ring.balancer.depart(&owner);
+                self.forget(&peer);
+                None
+            }
+            Err(e @ (LindaError::IoFailure(_) | LindaError::WorkerDisconnected)) => {
+                self.forget(&peer);
+                Some(Err(e))
+            }
+            result => Some(result),
+        }
+    }
+
+    // Sweeps the ring until a tuple turns up or `timeout` runs out.
+    fn sweep(
+        &self,
+        template: Tuple<Request>,
+        timeout: Option<time::Duration>,
+        operation: impl Fn(Tuple<Request>, time::Duration) -> Result<Tuple<Value>, LindaError>,
+    ) -> Result<Tuple<Value>, LindaError> {
+        let start = time::Instant::now();
+        loop {
+            let remaining = timeout.map(|timeout| timeout.saturating_sub(start.elapsed()));
+            let wait = remaining.map_or(SWEEP_INTERVAL, |remaining| remaining.min(SWEEP_INTERVAL));
+            match operation(template.clone(), wait) {
+                Err(LindaError::Timeout { node, .. }) if remaining.is_some_and(|r| r <= wait) => {
+                    return Err(LindaError::Timeout {
+                        template,
+                        node,
+                        elapsed: start.elapsed(),
+                    })
+                }
+                Err(LindaError::Timeout { .. }) => continue,
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<Output: 'static + io::Write + marker::Send> TupleSpace for Partitioned<Output> {
+    fn out(&self, tuple: Tuple<Value>) -> Result<(), LindaError> {
+        let key = tuple.get(self.key).cloned();
+        match self.routed(key.as_ref(), |owner| owner.out(tuple.clone())) {
+            Some(result) => result,
+            None => self.ring.out(tuple),
+        }
+    }
+
+    fn input(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError> {
+        let key = bound_key(&tuple, self.key);
+        match self.routed(key, |owner| owner.input(tuple.clone(), timeout)) {
+            Some(result) => result,
+            None => self.sweep(tuple, Some(timeout), |t, wait| self.ring.input(t, wait)),
+        }
+    }
+
+    fn input_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        let key = bound_key(&tuple, self.key);
+        match self.routed(key, |owner| owner.input_blocking(tuple.clone())) {
+            Some(result) => result,
+            None => self.sweep(tuple, None, |t, wait| self.ring.input(t, wait)),
+        }
+    }
+
+    fn read(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError> {
+        let key = bound_key(&tuple, self.key);
+        match self.routed(key, |owner| owner.read(tuple.clone(), timeout)) {
+            Some(result) => result,
+            None => self.sweep(tuple, Some(timeout), |t, wait| self.ring.read(t, wait)),
+        }
+    }
+
+    fn read_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        let key = bound_key(&tuple, self.key);
+        match self.routed(key, |owner| owner.read_blocking(tuple.clone())) {
+            Some(result) => result,
+            None => self.sweep(tuple, None, |t, wait| self.ring.read(t, wait)),
+        }
+    }
+
+    fn inp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        let key = bound_key(tuple, self.key);
+        match self.routed(key, |owner| owner.inp(tuple)) {
+            Some(result) => result,
+            None => self.ring.inp(tuple),
+        }
+    }
+
+    fn rdp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        let key = bound_key(tuple, self.key);
+        match self.routed(key, |owner| owner.rdp(tuple)) {
+            Some(result) => result,
+            None => self.ring.rdp(tuple),
+        }
+    }
+
+    fn list(&self, template: Option<&Tuple<Request>>) -> Result<Vec<Tuple<Value>>, LindaError> {
+        self.ring.list(template)
+    }
+
+    fn count(&self, template: Option<&Tuple<Request>>) -> Result<usize, LindaError> {
+        self.ring.count(template)
+    }
+
+    fn stats(&self) -> Option<Stats> {
+        self.ring.stats()
+    }
+
+    fn shutdown(&self) -> Result<(), LindaError> {
+        self.ring.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::partition_members;
+
+    #[test]
+    fn consistent_owners() {
+        let keys: Vec<_> = (0..1000).map(Value::int).collect();
+        let four = partition_members(4);
+        let ring = HashRing::new(&four);
+        let owners: Vec<_> = keys
+            .iter()
+            .map(|key| four[ring.owner(key).unwrap()].clone())
+            .collect();
+        for member in &four {
+            let owned = owners.iter().filter(|owner| *owner == member).count();
+            assert!((100..=400).contains(&owned), "{owned} keys at one member");
+        }
+
+        // Nodes see the ring starting with themselves and still agree on the owners.
+        let mut rotated = four.clone();
+        rotated.rotate_left(2);
+        let other = HashRing::new(&rotated);
+        assert!(keys
+            .iter()
+            .all(|key| rotated[other.owner(key).unwrap()] == four[ring.owner(key).unwrap()]));
+
+        // A fifth member only takes keys over; nothing moves between the others.
+        let five = partition_members(5);
+        let grown = HashRing::new(&five);
+        let moved = keys
+            .iter()
+            .zip(&owners)
+            .filter(|(key, owner)| five[grown.owner(key).unwrap()] != **owner)
+            .inspect(|(key, _)| assert_eq!(grown.owner(key), Some(4)))
+            .count();
+        assert!(moved > 0 && moved < 400);
+
+        let mut bystander = partition_members(2);
+        bystander[1].endpoint = None;
+        let ring = HashRing::new(&bystander);
+        assert!(keys.iter().all(|key| ring.owner(key) == Some(0)));
+        assert_eq!(HashRing::default().owner(&keys[0]), None);
+    }
+
+    #[test]
+    fn bind_keys() {
+        let template: Tuple<Request> = "(string: \"job\", int: *, int: > 3)".parse().unwrap();
+        assert_eq!(
+            bound_key(&template, 0),
+            Some(&Value::string(String::from("job")))
+        );
+        assert_eq!(bound_key(&template, 1), None);
+        assert_eq!(bound_key(&template, 2), None);
+        assert_eq!(bound_key(&template, 3), None);
+    }
+}
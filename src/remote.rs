@@ -0,0 +1,437 @@
+use std::{collections, io, marker, sync, thread, time};
+
+use crate::{
+    message::{LindaError, Message, MessageType, Operation, Reply},
+    send,
+    space::TupleSpace,
+    trace_message,
+    transport::Address,
+    tuple::*,
+    utils::Timeout,
+    ArcMutex,
+};
+
+const NO_ANSWER: &str = "No answer in time";
+
+type PendingReplies = ArcMutex<collections::HashMap<u64, sync::mpsc::Sender<(Reply, Address)>>>;
+
+pub struct RemoteSpace<Output> {
+    output_stream: ArcMutex<Output>,
+    pending: PendingReplies,
+    next_id: sync::atomic::AtomicU64,
+    node: Address,
+}
+
+fn receiver<Input: io::Read>(mut input: Input, pending: PendingReplies, notices: impl Fn(Message)) {
+    loop {
+        let msg = match Message::recv(&mut input) {
+            Ok(msg) => msg,
+            Err(e) => {
+                tracing::debug!(error = e.describe(), "Disconnected from server");
+                break;
+            }
+        };
+        trace_message("Received", &msg);
+
+        let (id, reply) = match msg.tuple {
+            MessageType::Reply(id, reply) => (id, reply),
+            _ => {
+                notices(msg);
+                continue;
+            }
+        };
+        let waiting = match pending.lock() {
+            Ok(mut guard) => guard.remove(&id),
+            Err(_) => break,
+        };
+        match waiting {
+            Some(tx) => {
+                if let Err(e) = tx.send((reply, msg.node)) {
+                    tracing::warn!(error = %e, "Failed to hand result to the waiting operation");
+                }
+            }
+            None => tracing::warn!(id, "Reply to unknown request - skipping"),
+        }
+    }
+
+    // Dropping the senders wakes up everyone still waiting for a reply.
+    if let Ok(mut guard) = pending.lock() {
+        guard.clear();
+    }
+}
+
+impl<Output: 'static + io::Write + marker::Send> RemoteSpace<Output> {
+    pub fn new<Input: 'static + io::Read + marker::Send>(
+        input_stream: Input,
+        output_stream: Output,
+        node: Address,
+    ) -> RemoteSpace<Output> {
+        let unexpected =
+            |msg: Message| tracing::warn!(kind = msg.tuple.name(), "Unexpected message - skipping");
+        RemoteSpace::with_notices(input_stream, output_stream, node, unexpected)
+    }
+
+    // Messages the other end sends on its own rather than in reply go to `notices`.
+    pub(crate) fn with_notices<Input: 'static + io::Read + marker::Send>(
+        input_stream: Input,
+        output_stream: Output,
+        node: Address,
+        notices: impl Fn(Message) + marker::Send + 'static,
+    ) -> RemoteSpace<Output> {
+        let pending = sync::Arc::new(sync::Mutex::new(collections::HashMap::new()));
+        let pending_clone = pending.clone();
+        thread::spawn(move || receiver(input_stream, pending_clone, notices));
+
+        RemoteSpace {
+            output_stream: sync::Arc::new(sync::Mutex::new(output_stream)),
+            pending,
+            next_id: sync::atomic::AtomicU64::new(0),
+            node,
+        }
+    }
+
+    fn operation(
+        &self,
+        operation: Operation,
+        tuple: Tuple<Request>,
+        timeout: Timeout,
+    ) -> Result<Tuple<Value>, LindaError> {
+        let start = time::Instant::now();
+        let template = tuple.clone();
+        let (reply, node) = self.call(
+            |id| MessageType::Operation(id, operation, tuple, timeout),
+            None,
+        )?;
+        to_tuple(reply, template, node, start.elapsed())
+    }
+
+    // Sends a message nobody waits a reply to.
+    pub(crate) fn notify(&self, tuple: MessageType) -> Result<(), LindaError> {
+        let msg = Message {
+            tuple,
+            node: self.node.clone(),
+        };
+        send(&self.output_stream, msg)
+    }
+
+    // Returns the reply together with the node that sent it. Operations can rely on the
+    // server to time them out, other requests may give up waiting themselves.
+    pub(crate) fn call(
+        &self,
+        body: impl FnOnce(u64) -> MessageType,
+        patience: Option<time::Duration>,
+    ) -> Result<(Reply, Address), LindaError> {
+        let id = self.next_id.fetch_add(1, sync::atomic::Ordering::Relaxed);
+        let (tx, rx) = sync::mpsc::channel();
+        match self.pending.lock() {
+            Ok(mut guard) => guard.insert(id, tx),
+            Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+        };
+
+        let msg = Message {
+            tuple: body(id),
+            node: self.node.clone(),
+        };
+        if let Err(e) = send(&self.output_stream, msg) {
+            if let Ok(mut guard) = self.pending.lock() {
+                guard.remove(&id);
+            }
+            return Err(e);
+        }
+
+        let reply = match patience {
+            Some(patience) => rx.recv_timeout(patience).map_err(|e| match e {
+                sync::mpsc::RecvTimeoutError::Timeout => {
+                    let e = io::Error::new(io::ErrorKind::TimedOut, NO_ANSWER);
+                    LindaError::IoFailure(e)
+                }
+                sync::mpsc::RecvTimeoutError::Disconnected => LindaError::WorkerDisconnected,
+            }),
+            None => rx.recv().map_err(|_| LindaError::WorkerDisconnected),
+        };
+        if reply.is_err() {
+            if let Ok(mut guard) = self.pending.lock() {
+                guard.remove(&id);
+            }
+        }
+
+        reply
+    }
+}
+
+impl<Output: 'static + io::Write + marker::Send> TupleSpace for RemoteSpace<Output> {
+    fn out(&self, tuple: Tuple<Value>) -> Result<(), LindaError> {
+        match self.call(|id| MessageType::Out(id, tuple), None)?.0 {
+            Reply::Done => Ok(()),
+            Reply::Denied(identity) => Err(LindaError::AccessDenied(identity)),
+            Reply::Closed => Err(LindaError::ShutDown),
+            Reply::Failed(message) => Err(LindaError::ServerFailure(message)),
+            reply => Err(unexpected(&reply)),
+        }
+    }
+
+    fn input(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError> {
+        self.operation(Operation::In, tuple, Timeout::After(timeout))
+    }
+
+    fn input_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.operation(Operation::In, tuple, Timeout::Forever)
+    }
+
+    fn read(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError> {
+        self.operation(Operation::Rd, tuple, Timeout::After(timeout))
+    }
+
+    fn read_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.operation(Operation::Rd, tuple, Timeout::Forever)
+    }
+
+    fn inp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.operation(Operation::Inp, tuple.clone(), Timeout::Forever)
+    }
+
+    fn rdp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.operation(Operation::Rdp, tuple.clone(), Timeout::Forever)
+    }
+}
+
+fn to_tuple(
+    reply: Reply,
+    template: Tuple<Request>,
+    node: Address,
+    elapsed: time::Duration,
+) -> Result<Tuple<Value>, LindaError> {
+    match reply {
+        Reply::Tuple(tuple) => Ok(tuple),
+        Reply::NoTuple => Err(LindaError::NoTuple {
+            template,
+            node: Some(node),
+        }),
+        Reply::Timeout => Err(LindaError::Timeout {
+            template,
+            node: Some(node),
+            elapsed,
+        }),
+        Reply::Denied(identity) => Err(LindaError::AccessDenied(identity)),
+        Reply::Closed => Err(LindaError::ShutDown),
+        Reply::Failed(message) => Err(LindaError::ServerFailure(message)),
+        reply => Err(unexpected(&reply)),
+    }
+}
+
+pub(crate) fn unexpected(reply: &Reply) -> LindaError {
+    LindaError::ParseFailure(format!("unexpected reply {reply:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        acl::{Access, Policy},
+        harness::{template, tuple},
+        space::LocalSpace,
+        standalone::{serve, MAX_WAITS},
+        stats::ServerMetrics,
+        tls::{tests::Authority, TlsConfig},
+        transport::{Listener, Stream, Transport},
+    };
+    #[cfg(unix)]
+    use std::{env, fs, process};
+
+    fn start_server(address: Address, tls: Option<TlsConfig>) -> Address {
+        serve_space(address, LocalSpace::new(), tls)
+    }
+
+    fn serve_space(address: Address, space: LocalSpace, tls: Option<TlsConfig>) -> Address {
+        let listener = Listener::bind(&address).unwrap();
+        let address = listener.local_address().unwrap();
+        let space = sync::Arc::new(space);
+        thread::spawn(move || serve(listener, space, tls, None, Default::default()));
+
+        address
+    }
+
+    fn tcp_server() -> Address {
+        start_server(Transport::Tcp.local_address(), None)
+    }
+
+    fn connect_with(server: &Address, tls: Option<&TlsConfig>) -> RemoteSpace<Stream> {
+        let stream = crate::tls::connect(tls, server).unwrap();
+        let node = stream.client_address().unwrap();
+        RemoteSpace::new(stream.try_clone().unwrap(), stream, node)
+    }
+
+    fn connect(server: &Address) -> RemoteSpace<Stream> {
+        connect_with(server, None)
+    }
+
+    #[test]
+    fn predicate_operations() {
+        let space = connect(&tcp_server());
+
+        assert!(matches!(
+            space.inp(&template(1)),
+            Err(LindaError::NoTuple { .. })
+        ));
+        space.out(tuple(1)).unwrap();
+        assert_eq!(space.rdp(&template(1)).unwrap(), tuple(1));
+        assert_eq!(space.inp(&template(1)).unwrap(), tuple(1));
+        assert!(matches!(
+            space.rdp(&template(1)),
+            Err(LindaError::NoTuple { .. })
+        ));
+    }
+
+    #[test]
+    fn blocking_operations() {
+        let server = tcp_server();
+        let consumer = connect(&server);
+        let producer = connect(&server);
+
+        assert!(matches!(
+            consumer.input(template(2), time::Duration::from_millis(50)),
+            Err(LindaError::Timeout { .. })
+        ));
+
+        let waiting = thread::spawn(move || {
+            let read = consumer.read_blocking(template(2)).unwrap();
+            let taken = consumer.input_blocking(template(2)).unwrap();
+            (read, taken)
+        });
+        producer.out(tuple(2)).unwrap();
+        assert_eq!(waiting.join().unwrap(), (tuple(2), tuple(2)));
+        assert!(matches!(
+            producer.inp(&template(2)),
+            Err(LindaError::NoTuple { .. })
+        ));
+    }
+
+    #[test]
+    fn correlate_concurrent_requests() {
+        let space = sync::Arc::new(connect(&tcp_server()));
+
+        let waiting: Vec<_> = (0..4)
+            .map(|i| {
+                let space = space.clone();
+                thread::spawn(move || space.input(template(i), time::Duration::from_secs(5)))
+            })
+            .collect();
+        for i in (0..4).rev() {
+            space.out(tuple(i)).unwrap();
+        }
+        for (i, handle) in waiting.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap().unwrap(), tuple(i as i32));
+        }
+    }
+
+    // A client that disconnects stops waiting, so its operations take no tuples from others.
+    #[test]
+    fn cancel_abandoned_waits() {
+        let listener = Listener::bind(&Transport::Tcp.local_address()).unwrap();
+        let server = listener.local_address().unwrap();
+        let metrics = sync::Arc::new(ServerMetrics::default());
+        let serving = metrics.clone();
+        thread::spawn(move || serve(listener, Default::default(), None, None, serving));
+
+        let mut stream = Stream::connect(&server).unwrap();
+        let node = stream.client_address().unwrap();
+        for id in 0..2 {
+            Message::operation(
+                id,
+                Operation::In,
+                template(6),
+                Timeout::Forever,
+                node.clone(),
+            )
+            .send(&mut stream)
+            .unwrap();
+        }
+        while metrics.snapshot(None).received < 2 {
+            thread::yield_now();
+        }
+        drop(stream);
+        while metrics.snapshot(None).members > 0 {
+            thread::yield_now();
+        }
+
+        let space = connect(&server);
+        space.out(tuple(6)).unwrap();
+        assert_eq!(space.inp(&template(6)).unwrap(), tuple(6));
+    }
+
+    #[test]
+    fn limit_waits_per_client() {
+        let space = sync::Arc::new(connect(&tcp_server()));
+        let (tx, rx) = sync::mpsc::channel();
+        for _ in 0..=MAX_WAITS {
+            let (space, tx) = (space.clone(), tx.clone());
+            thread::spawn(move || tx.send(space.input(template(7), time::Duration::from_secs(5))));
+        }
+
+        assert!(matches!(rx.recv(), Ok(Err(LindaError::ServerFailure(_)))));
+        for _ in 0..MAX_WAITS {
+            space.out(tuple(7)).unwrap();
+        }
+        for _ in 0..MAX_WAITS {
+            assert_eq!(rx.recv().unwrap().unwrap(), tuple(7));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_server() {
+        let path = env::temp_dir().join(format!("linda-remote-test-{}.sock", process::id()));
+        let server = start_server(Address::Unix(path.clone()), None);
+        let consumer = connect(&server);
+        let producer = connect(&server);
+
+        producer.out(tuple(3)).unwrap();
+        assert_eq!(
+            consumer
+                .input(template(3), time::Duration::from_secs(1))
+                .unwrap(),
+            tuple(3)
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tls_server() {
+        let authority = Authority::generate();
+        let server = start_server(Transport::Tcp.local_address(), Some(authority.config()));
+        let space = connect_with(&server, Some(&authority.config()));
+
+        space.out(tuple(4)).unwrap();
+        assert_eq!(space.inp(&template(4)).unwrap(), tuple(4));
+        assert!(crate::tls::connect(Some(&Authority::generate().config()), &server).is_err());
+    }
+
+    // Each client is judged by the identity its certificate names.
+    #[test]
+    fn policy_follows_the_certificate() {
+        let authority = Authority::generate();
+        let policy: Policy = "scheduler in (int: *)\n* out,rd (int: *)".parse().unwrap();
+        let access = Access::new(String::from("owner"), policy);
+        let space = LocalSpace::sharing(Default::default(), access);
+        let tls = Some(authority.config());
+        let server = serve_space(Transport::Tcp.local_address(), space, tls);
+        let worker = connect_with(&server, Some(&authority.config_for("worker")));
+        let scheduler = connect_with(&server, Some(&authority.config_for("scheduler")));
+
+        worker.out(tuple(5)).unwrap();
+        assert_eq!(worker.rdp(&template(5)).unwrap(), tuple(5));
+        assert!(matches!(
+            worker.inp(&template(5)),
+            Err(LindaError::AccessDenied(identity)) if identity == "worker"
+        ));
+        assert_eq!(scheduler.inp(&template(5)).unwrap(), tuple(5));
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fs, io};
+
+use crate::message::LindaError;
+use crate::parser::{ParseError, Parser};
+use crate::tuple::{Tuple, Value};
+use crate::utils::Command;
+
+const DUMP_HEADER: &str = "# Tuples dumped by linda; replay with `load`";
+
+pub struct Script<Input> {
+    input: Input,
+    line: usize,
+}
+
+pub struct Line {
+    pub number: usize,
+    pub text: String,
+    pub command: Result<Command, ParseError>,
+}
+
+impl<Input: io::BufRead> Script<Input> {
+    pub fn new(input: Input) -> Script<Input> {
+        Script { input, line: 0 }
+    }
+}
+
+impl<Input: io::BufRead> Iterator for Script<Input> {
+    type Item = io::Result<Line>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut text = String::new();
+            match self.input.read_line(&mut text) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => return Some(Err(e)),
+            }
+
+            let text = String::from(text.trim_end_matches(['\n', '\r']));
+            let mut parser = Parser::new(&text);
+            if parser.at_end() {
+                continue;
+            }
+
+            let number = self.line;
+            let command = parser.parse().map_err(|mut e| {
+                e.line = number;
+                e
+            });
+            return Some(Ok(Line {
+                number,
+                text,
+                command,
+            }));
+        }
+    }
+}
+
+// Dumps are scripts of `out` commands, so they can be loaded or run like any other script.
+pub fn write_tuples(mut output: impl io::Write, tuples: &[Tuple<Value>]) -> io::Result<()> {
+    writeln!(output, "{DUMP_HEADER}")?;
+    for tuple in tuples {
+        writeln!(output, "out {tuple}")?;
+    }
+
+    output.flush()
+}
+
+pub fn read_tuples(input: impl io::BufRead) -> Result<Vec<Tuple<Value>>, LindaError> {
+    let mut tuples = Vec::new();
+    for line in Script::new(input) {
+        let line = line.map_err(LindaError::IoFailure)?;
+        match line.command {
+            Ok(Command::Out(tuple)) => tuples.push(tuple),
+            Ok(command) => {
+                return Err(LindaError::ParseFailure(format!(
+                    "line {}: expected out, found {}",
+                    line.number,
+                    command.name()
+                )))
+            }
+            Err(e) => {
+                return Err(LindaError::ParseFailure(format!(
+                    "line {}, column {}: {}",
+                    e.line, e.column, e.message
+                )))
+            }
+        }
+    }
+
+    Ok(tuples)
+}
+
+pub fn dump(path: &str, tuples: &[Tuple<Value>]) -> Result<(), LindaError> {
+    let file = fs::File::create(path).map_err(|e| LindaError::FileFailure(path.to_owned(), e))?;
+    write_tuples(io::BufWriter::new(file), tuples)
+        .map_err(|e| LindaError::FileFailure(path.to_owned(), e))
+}
+
+pub fn load(path: &str) -> Result<Vec<Tuple<Value>>, LindaError> {
+    let file = fs::File::open(path).map_err(|e| LindaError::FileFailure(path.to_owned(), e))?;
+    read_tuples(io::BufReader::new(file)).map_err(|e| match e {
+        LindaError::IoFailure(e) => LindaError::FileFailure(path.to_owned(), e),
+        e => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::*;
+
+    #[test]
+    fn skip_comments() {
+        let input = "# setup\n\nout (1) # first\n   \n  # done\nexit\n";
+        let lines: Vec<Line> = Script::new(input.as_bytes())
+            .map(|line| line.unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].number, 3);
+        assert_eq!(lines[0].text, "out (1) # first");
+        assert_eq!(
+            lines[0].command,
+            Ok(Command::Out(Tuple::from_vec(vec![Value::int(1)])))
+        );
+        assert_eq!(lines[1].number, 6);
+        assert_eq!(lines[1].command, Ok(Command::Exit));
+    }
+
+    #[test]
+    fn report_line_numbers() {
+        let input = "out (1)\r\n# comment\r\nout (1 2)\r\n";
+        let lines: Vec<Line> = Script::new(input.as_bytes())
+            .map(|line| line.unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        let err = lines[1].command.as_ref().unwrap_err();
+        assert_eq!((err.line, err.column), (3, 8));
+        assert_eq!(err.source_line(), "out (1 2)");
+    }
+
+    #[test]
+    fn reload_dump() {
+        let tuples = vec![
+            Tuple::from_vec(vec![Value::int(-1), Value::float(2.0)]),
+            Tuple::from_vec(vec![Value::string(String::from("a \"b\"\n# c"))]),
+            Tuple::from_vec(vec![
+                Value::int(1),
+                Value::String(None),
+                Value::Int(None),
+                Value::Float(None),
+            ]),
+        ];
+        let mut dump = Vec::new();
+        write_tuples(&mut dump, &tuples).unwrap();
+
+        assert!(dump.starts_with(DUMP_HEADER.as_bytes()));
+        assert_eq!(read_tuples(&dump[..]).unwrap(), tuples);
+        assert!(matches!(
+            read_tuples("out (1)\nin (int: *)\n".as_bytes()),
+            Err(LindaError::ParseFailure(e)) if e == "line 2: expected out, found in"
+        ));
+    }
+}
@@ -0,0 +1,384 @@
+use std::{sync, time};
+
+use crate::{
+    acl::{Access, Right},
+    claim,
+    message::LindaError,
+    stats::Stats,
+    tuple::*,
+    typed::{FromTuple, IntoTuple},
+    utils::Timeout,
+    Flag, LocalTuples,
+};
+
+pub trait TupleSpace {
+    fn out(&self, tuple: Tuple<Value>) -> Result<(), LindaError>;
+
+    fn input(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError>;
+
+    fn input_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError>;
+
+    fn read(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError>;
+
+    fn read_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError>;
+
+    fn inp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError>;
+
+    fn rdp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError>;
+
+    // Stops accepting operations and makes sure no stored tuple is lost. Spaces that do not
+    // hold tuples for others have nothing to do.
+    fn shutdown(&self) -> Result<(), LindaError> {
+        Ok(())
+    }
+
+    // Copies of the locally stored tuples matching `template`, or of all of them; nothing is
+    // removed. Spaces that store nothing locally cannot be inspected.
+    fn list(&self, _template: Option<&Tuple<Request>>) -> Result<Vec<Tuple<Value>>, LindaError> {
+        Err(LindaError::Unsupported("listing its tuples"))
+    }
+
+    fn count(&self, template: Option<&Tuple<Request>>) -> Result<usize, LindaError> {
+        self.list(template).map(|tuples| tuples.len())
+    }
+
+    // Counters of the node behind this space, if it keeps any.
+    fn stats(&self) -> Option<Stats> {
+        None
+    }
+
+    fn out_typed<T: IntoTuple>(&self, value: &T) -> Result<(), LindaError>
+    where
+        Self: Sized,
+    {
+        self.out(value.to_tuple())
+    }
+
+    fn in_typed<T: FromTuple>(
+        &self,
+        template: impl Into<Tuple<Request>>,
+        timeout: time::Duration,
+    ) -> Result<T, LindaError>
+    where
+        Self: Sized,
+    {
+        from_tuple(self.input(template.into(), timeout)?)
+    }
+
+    fn read_typed<T: FromTuple>(
+        &self,
+        template: impl Into<Tuple<Request>>,
+        timeout: time::Duration,
+    ) -> Result<T, LindaError>
+    where
+        Self: Sized,
+    {
+        from_tuple(self.read(template.into(), timeout)?)
+    }
+}
+
+// The policy of `access` governs every operation, judged by its identity.
+#[derive(Default)]
+pub struct LocalSpace {
+    tuples: LocalTuples,
+    access: Access,
+    // Set once the client the space acts for is gone, so that its waits give up.
+    cancelled: Flag,
+}
+
+fn from_tuple<T: FromTuple>(tuple: Tuple<Value>) -> Result<T, LindaError> {
+    match T::from_tuple(tuple.clone()) {
+        Some(val) => Ok(val),
+        None => Err(LindaError::ConversionFailure(tuple)),
+    }
+}
+
+impl LocalSpace {
+    pub fn new() -> LocalSpace {
+        LocalSpace::default()
+    }
+
+    // A view on tuples held by someone else, e.g. the tuples a ring node owns.
+    pub(crate) fn sharing(tuples: LocalTuples, access: Access) -> LocalSpace {
+        LocalSpace {
+            tuples,
+            access,
+            cancelled: Flag::default(),
+        }
+    }
+
+    // The same tuples under the same policy, used by `identity`.
+    pub fn acting_for(&self, identity: &str) -> LocalSpace {
+        let access = Access {
+            identity: String::from(identity),
+            ..self.access.clone()
+        };
+        LocalSpace::sharing(self.tuples.clone(), access)
+    }
+
+    // Stores `tuple` without consulting the policy, e.g. to put back one that was taken on
+    // behalf of a client who is gone.
+    pub(crate) fn restore(&self, tuple: Tuple<Value>) -> Result<(), LindaError> {
+        match self.tuples.lock() {
+            Ok(_) if self.tuples.withdrawn() => return Err(LindaError::ShutDown),
+            Ok(mut guard) => guard.push(tuple),
+            Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+        }
+        self.tuples.added.notify_all();
+
+        Ok(())
+    }
+
+    // Ends every wait made through this space, e.g. once the client it serves disconnected.
+    pub(crate) fn cancel(&self) {
+        let guard = self.tuples.lock();
+        self.cancelled.store(true, sync::atomic::Ordering::SeqCst);
+        drop(guard);
+        self.tuples.added.notify_all();
+    }
+
+    pub fn len(&self) -> usize {
+        self.tuples.lock().map_or(0, |guard| guard.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn find(&self, template: &Tuple<Request>, right: Right) -> Result<Tuple<Value>, LindaError> {
+        let (policy, identity) = (&self.access.policy, &self.access.identity);
+        let mut guard = match self.tuples.lock() {
+            Ok(_) if self.tuples.withdrawn() => return Err(LindaError::ShutDown),
+            Ok(guard) => guard,
+            Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+        };
+        match claim(&mut guard, template, policy, identity, right)? {
+            Some(tuple) => Ok(tuple),
+            None => Err(LindaError::NoTuple {
+                template: template.clone(),
+                node: None,
+            }),
+        }
+    }
+
+    fn wait_for(
+        &self,
+        template: &Tuple<Request>,
+        timeout: Timeout,
+        right: Right,
+    ) -> Result<Tuple<Value>, LindaError> {
+        let start = time::Instant::now();
+        let deadline = match timeout {
+            Timeout::After(timeout) => Some(start + timeout),
+            Timeout::Forever => None,
+        };
+
+        let (policy, identity) = (&self.access.policy, &self.access.identity);
+        let mut guard = match self.tuples.lock() {
+            Ok(guard) => guard,
+            Err(e) => return Err(LindaError::MutexLockFailure(e.to_string())),
+        };
+        loop {
+            if self.tuples.withdrawn() || self.cancelled.load(sync::atomic::Ordering::SeqCst) {
+                return Err(LindaError::ShutDown);
+            }
+            if let Some(tuple) = claim(&mut guard, template, policy, identity, right)? {
+                return Ok(tuple);
+            }
+
+            let waited = match deadline {
+                Some(deadline) => {
+                    let now = time::Instant::now();
+                    if now >= deadline {
+                        return Err(LindaError::Timeout {
+                            template: template.clone(),
+                            node: None,
+                            elapsed: now - start,
+                        });
+                    }
+                    match self.tuples.added.wait_timeout(guard, deadline - now) {
+                        Ok((guard, _)) => Ok(guard),
+                        Err(e) => Err(e.to_string()),
+                    }
+                }
+                None => self.tuples.added.wait(guard).map_err(|e| e.to_string()),
+            };
+            guard = match waited {
+                Ok(guard) => guard,
+                Err(e) => return Err(LindaError::MutexLockFailure(e)),
+            };
+        }
+    }
+}
+
+impl TupleSpace for LocalSpace {
+    fn out(&self, tuple: Tuple<Value>) -> Result<(), LindaError> {
+        if !self.access.allows(Right::Out, &tuple) {
+            return Err(LindaError::AccessDenied(self.access.identity.clone()));
+        }
+
+        self.restore(tuple)
+    }
+
+    fn input(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError> {
+        self.wait_for(&tuple, Timeout::After(timeout), Right::In)
+    }
+
+    fn input_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.wait_for(&tuple, Timeout::Forever, Right::In)
+    }
+
+    fn read(
+        &self,
+        tuple: Tuple<Request>,
+        timeout: time::Duration,
+    ) -> Result<Tuple<Value>, LindaError> {
+        self.wait_for(&tuple, Timeout::After(timeout), Right::Rd)
+    }
+
+    fn read_blocking(&self, tuple: Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.wait_for(&tuple, Timeout::Forever, Right::Rd)
+    }
+
+    fn inp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.find(tuple, Right::In)
+    }
+
+    fn rdp(&self, tuple: &Tuple<Request>) -> Result<Tuple<Value>, LindaError> {
+        self.find(tuple, Right::Rd)
+    }
+
+    fn list(&self, template: Option<&Tuple<Request>>) -> Result<Vec<Tuple<Value>>, LindaError> {
+        self.tuples
+            .readable(template, &self.access, |tuples| tuples.cloned().collect())
+    }
+
+    fn count(&self, template: Option<&Tuple<Request>>) -> Result<usize, LindaError> {
+        self.tuples
+            .readable(template, &self.access, |tuples| tuples.count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync, thread};
+
+    use super::*;
+    use crate::{acl::Policy, harness::any_int, typed::Template};
+
+    #[derive(Debug, PartialEq, crate::typed::IntoTuple, crate::typed::FromTuple, Template)]
+    struct Task {
+        id: i32,
+        name: String,
+    }
+
+    fn producer_consumer(space: &impl TupleSpace) {
+        for id in 0..3 {
+            space
+                .out_typed(&Task {
+                    id,
+                    name: format!("task {id}"),
+                })
+                .unwrap();
+        }
+
+        let template = Task::template().id(ComparisonOperator::EQ, 1);
+        let task: Task = space
+            .in_typed(template, time::Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(task.name, "task 1");
+    }
+
+    #[test]
+    fn application_code() {
+        let space = LocalSpace::new();
+        producer_consumer(&space);
+
+        let rest = Task::template().name(ComparisonOperator::NEQ, String::new());
+        assert_eq!(space.rdp(&rest.into()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn predicate_operations() {
+        let space = LocalSpace::new();
+        let tuple = Tuple::from_vec(vec![Value::int(5)]);
+
+        assert!(matches!(
+            space.inp(&any_int()),
+            Err(LindaError::NoTuple { .. })
+        ));
+        space.out(tuple.clone()).unwrap();
+        assert_eq!(space.rdp(&any_int()).unwrap(), tuple);
+        assert_eq!(space.inp(&any_int()).unwrap(), tuple);
+        assert!(matches!(
+            space.rdp(&any_int()),
+            Err(LindaError::NoTuple { .. })
+        ));
+    }
+
+    #[test]
+    fn blocking_operations() {
+        let space = sync::Arc::new(LocalSpace::new());
+        let tuple = Tuple::from_vec(vec![Value::int(5)]);
+
+        assert!(matches!(
+            space.input(any_int(), time::Duration::from_millis(20)),
+            Err(LindaError::Timeout { .. })
+        ));
+
+        let consumer = space.clone();
+        let handle = thread::spawn(move || {
+            let read = consumer.read_blocking(any_int()).unwrap();
+            let taken = consumer.input_blocking(any_int()).unwrap();
+            (read, taken)
+        });
+        space.out(tuple.clone()).unwrap();
+
+        assert_eq!(handle.join().unwrap(), (tuple.clone(), tuple));
+        assert!(matches!(
+            space.inp(&any_int()),
+            Err(LindaError::NoTuple { .. })
+        ));
+    }
+
+    #[test]
+    fn policy_governs_every_operation() {
+        let policy: Policy = "scheduler in (int: *)\n* out,rd (int: *)".parse().unwrap();
+        let access = Access::new(String::from("scheduler"), policy);
+        let scheduler = LocalSpace::sharing(LocalTuples::default(), access);
+        let worker = scheduler.acting_for("worker");
+        let tuple = Tuple::from_vec(vec![Value::int(5)]);
+
+        worker.out(tuple.clone()).unwrap();
+        assert_eq!(worker.rdp(&any_int()).unwrap(), tuple);
+        assert_eq!(worker.count(None).unwrap(), 1);
+        assert!(matches!(
+            worker.inp(&any_int()),
+            Err(LindaError::AccessDenied(identity)) if identity == "worker"
+        ));
+        assert!(matches!(
+            worker.input(any_int(), time::Duration::ZERO),
+            Err(LindaError::AccessDenied(_))
+        ));
+        assert_eq!(scheduler.inp(&any_int()).unwrap(), tuple);
+    }
+
+    #[test]
+    fn dyn_tuple_space() {
+        let space: Box<dyn TupleSpace> = Box::new(LocalSpace::new());
+        space.out(Tuple::from_vec(vec![Value::int(1)])).unwrap();
+        assert!(space.read(any_int(), time::Duration::ZERO).is_ok());
+    }
+}
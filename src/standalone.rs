@@ -0,0 +1,202 @@
+use std::{
+    io,
+    sync::{self, atomic},
+    thread,
+};
+
+use crate::{
+    acl::ANONYMOUS,
+    auth::{self, SharedKey},
+    message::{LindaError, Message, MessageType, Operation, Reply},
+    send,
+    space::{LocalSpace, TupleSpace},
+    stats::ServerMetrics,
+    tls::{self, TlsConfig},
+    trace_message,
+    transport::{Address, Listener},
+    tuple::*,
+    utils::Timeout,
+    ArcMutex,
+};
+
+// How many blocking operations a client can have waiting at once.
+pub(crate) const MAX_WAITS: usize = 64;
+const TOO_MANY_WAITS: &str = "Too many operations are waiting for this client!";
+
+// Every client acts on `space` as the identity its certificate names, or anonymously.
+pub fn serve(
+    listener: Listener,
+    space: sync::Arc<LocalSpace>,
+    tls: Option<TlsConfig>,
+    key: Option<SharedKey>,
+    metrics: sync::Arc<ServerMetrics>,
+) -> io::Result<()> {
+    let node = listener.local_address()?;
+    loop {
+        let stream = match listener.accept() {
+            Ok(stream) => stream,
+            Err(e) => {
+                metrics.failed();
+                tracing::warn!(error = %e, "Incoming connection failed - skipping client");
+                continue;
+            }
+        };
+
+        let space = space.clone();
+        let node = node.clone();
+        let tls = tls.clone();
+        let key = key.clone();
+        let metrics = metrics.clone();
+        thread::spawn(move || {
+            // The handshake runs here so that a slow or misbehaving peer does not hold up
+            // the listener.
+            let stream = match tls::accept(tls.as_ref(), stream) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    metrics.rejected();
+                    tracing::warn!(error = %e, "TLS handshake failed - skipping client");
+                    return;
+                }
+            };
+            let stream = match auth::accept(key.as_ref(), stream) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    metrics.rejected();
+                    tracing::warn!(error = %e, "Rejected client");
+                    return;
+                }
+            };
+            let space =
+                sync::Arc::new(space.acting_for(stream.peer_identity().unwrap_or(ANONYMOUS)));
+            match stream.try_clone() {
+                Ok(input) => {
+                    metrics.joined();
+                    handle_client(input, stream, space, node, &metrics);
+                    metrics.left();
+                }
+                Err(e) => {
+                    metrics.failed();
+                    tracing::warn!(error = %e, "Failed to clone client stream - skipping client")
+                }
+            }
+        });
+    }
+}
+
+fn handle_client<Input: io::Read, Output: 'static + io::Write + Send>(
+    mut input: Input,
+    output: Output,
+    space: sync::Arc<LocalSpace>,
+    node: Address,
+    metrics: &ServerMetrics,
+) {
+    let output = sync::Arc::new(sync::Mutex::new(output));
+    let waiting = sync::Arc::new(atomic::AtomicUsize::new(0));
+    loop {
+        let msg = match Message::recv(&mut input) {
+            Ok(msg) => msg,
+            Err(e) => {
+                tracing::debug!(error = e.describe(), "Client disconnected");
+                // Nobody is left to answer, so the waits must not take tuples.
+                space.cancel();
+                break;
+            }
+        };
+        trace_message("Received", &msg);
+        metrics.received();
+
+        match msg.tuple {
+            MessageType::Out(id, tuple) => {
+                let reply = match space.out(tuple) {
+                    Ok(()) => Reply::Done,
+                    Err(LindaError::AccessDenied(identity)) => Reply::Denied(identity),
+                    Err(LindaError::ShutDown) => Reply::Closed,
+                    Err(e) => Reply::Failed(e.describe()),
+                };
+                if !respond(&output, Message::reply(id, reply, node.clone())) {
+                    metrics.failed();
+                }
+            }
+            MessageType::Operation(id, operation, template, timeout) => {
+                if let Operation::Inp | Operation::Rdp = operation {
+                    let reply = to_reply(&perform(&space, operation, template, timeout));
+                    if !respond(&output, Message::reply(id, reply, node.clone())) {
+                        metrics.failed();
+                    }
+                    continue;
+                }
+                if waiting.fetch_add(1, atomic::Ordering::SeqCst) >= MAX_WAITS {
+                    waiting.fetch_sub(1, atomic::Ordering::SeqCst);
+                    let reply = Reply::Failed(String::from(TOO_MANY_WAITS));
+                    if !respond(&output, Message::reply(id, reply, node.clone())) {
+                        metrics.failed();
+                    }
+                    continue;
+                }
+
+                let space = space.clone();
+                let output = output.clone();
+                let node = node.clone();
+                let waiting = waiting.clone();
+                thread::spawn(move || {
+                    let result = perform(&space, operation, template, timeout);
+                    let reply = to_reply(&result);
+                    if !respond(&output, Message::reply(id, reply, node.clone()))
+                        && operation == Operation::In
+                    {
+                        if let Ok(tuple) = result {
+                            if let Err(e) = space.restore(tuple) {
+                                tracing::error!(
+                                    error = e.describe(),
+                                    "Failed to put back unclaimed tuple"
+                                );
+                            }
+                        }
+                    }
+                    waiting.fetch_sub(1, atomic::Ordering::SeqCst);
+                });
+            }
+            _ => {
+                metrics.failed();
+                tracing::warn!(kind = msg.tuple.name(), "Unexpected message - skipping")
+            }
+        }
+    }
+}
+
+fn perform(
+    space: &LocalSpace,
+    operation: Operation,
+    template: Tuple<Request>,
+    timeout: Timeout,
+) -> Result<Tuple<Value>, LindaError> {
+    match (operation, timeout) {
+        (Operation::In, Timeout::After(timeout)) => space.input(template, timeout),
+        (Operation::In, Timeout::Forever) => space.input_blocking(template),
+        (Operation::Rd, Timeout::After(timeout)) => space.read(template, timeout),
+        (Operation::Rd, Timeout::Forever) => space.read_blocking(template),
+        (Operation::Inp, _) => space.inp(&template),
+        (Operation::Rdp, _) => space.rdp(&template),
+    }
+}
+
+fn to_reply(result: &Result<Tuple<Value>, LindaError>) -> Reply {
+    match result {
+        Ok(tuple) => Reply::Tuple(tuple.clone()),
+        Err(LindaError::NoTuple { .. }) => Reply::NoTuple,
+        Err(LindaError::Timeout { .. }) => Reply::Timeout,
+        Err(LindaError::AccessDenied(identity)) => Reply::Denied(identity.clone()),
+        Err(LindaError::ShutDown) => Reply::Closed,
+        Err(e) => Reply::Failed(e.describe()),
+    }
+}
+
+fn respond<Output: io::Write>(output: &ArcMutex<Output>, msg: Message) -> bool {
+    match send(output, msg) {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!(error = e.describe(), "Failed to reply to client");
+            false
+        }
+    }
+}
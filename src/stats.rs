@@ -0,0 +1,307 @@
+use std::{
+    fmt,
+    sync::{
+        self,
+        atomic::{AtomicU64, Ordering},
+    },
+    time,
+};
+
+use crate::LocalTuples;
+
+// Upper bounds of the latency buckets; anything slower ends up in one last, open bucket.
+pub const LATENCY_BOUNDS: [time::Duration; 8] = [
+    time::Duration::from_millis(1),
+    time::Duration::from_millis(5),
+    time::Duration::from_millis(10),
+    time::Duration::from_millis(50),
+    time::Duration::from_millis(100),
+    time::Duration::from_millis(500),
+    time::Duration::from_secs(1),
+    time::Duration::from_secs(5),
+];
+
+// Counters shared between a node's operations and its worker.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    received: AtomicU64,
+    forwarded: AtomicU64,
+    satisfied_locally: AtomicU64,
+    satisfied_remotely: AtomicU64,
+    timeouts: AtomicU64,
+    input_latency: [AtomicU64; LATENCY_BOUNDS.len() + 1],
+    input_time: AtomicU64,
+    errors: AtomicU64,
+    departures: AtomicU64,
+    placed: AtomicU64,
+    rebalanced: AtomicU64,
+    routed: AtomicU64,
+}
+
+// A handle on a node's counters that outlives borrows of the node, e.g. for an exporter.
+#[derive(Clone)]
+pub struct Monitor {
+    metrics: sync::Arc<Metrics>,
+    local_tuples: LocalTuples,
+}
+
+// Counters of a server, whether it only wires up a ring or holds the tuples itself.
+#[derive(Debug, Default)]
+pub struct ServerMetrics {
+    members: AtomicU64,
+    rejected: AtomicU64,
+    received: AtomicU64,
+    errors: AtomicU64,
+}
+
+// A snapshot of a node's counters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub local_tuples: usize,
+    pub received: u64,
+    pub forwarded: u64,
+    pub satisfied_locally: u64,
+    pub satisfied_remotely: u64,
+    pub timeouts: u64,
+    pub input_latency: Histogram,
+    pub errors: u64,
+    pub departures: u64,
+    pub placed: u64,
+    pub rebalanced: u64,
+    pub routed: u64,
+}
+
+// `members` counts the registered ring nodes or the connected clients of a standalone
+// server, which is also the only kind of server holding tuples.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerStats {
+    pub members: u64,
+    pub rejected: u64,
+    pub received: u64,
+    pub errors: u64,
+    pub tuples: Option<usize>,
+}
+
+// Observations per bucket of `LATENCY_BOUNDS`, followed by the open bucket.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Histogram {
+    pub buckets: Vec<u64>,
+    pub sum: time::Duration,
+}
+
+impl Metrics {
+    pub fn received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn forwarded(&self) {
+        self.forwarded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn satisfied_locally(&self) {
+        self.satisfied_locally.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn satisfied_remotely(&self) {
+        self.satisfied_remotely.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn timed_out(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn departed(&self) {
+        self.departures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // A tuple this node produced that another node stores.
+    pub fn placed(&self) {
+        self.placed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rebalanced(&self) {
+        self.rebalanced.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // An operation sent straight to the node owning its key rather than around the ring.
+    pub fn routed(&self) {
+        self.routed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn input_took(&self, elapsed: time::Duration) {
+        let bucket = LATENCY_BOUNDS
+            .iter()
+            .position(|bound| elapsed <= *bound)
+            .unwrap_or(LATENCY_BOUNDS.len());
+        self.input_latency[bucket].fetch_add(1, Ordering::Relaxed);
+        self.input_time
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, local_tuples: usize) -> Stats {
+        Stats {
+            local_tuples,
+            received: self.received.load(Ordering::Relaxed),
+            forwarded: self.forwarded.load(Ordering::Relaxed),
+            satisfied_locally: self.satisfied_locally.load(Ordering::Relaxed),
+            satisfied_remotely: self.satisfied_remotely.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            input_latency: Histogram {
+                buckets: self
+                    .input_latency
+                    .iter()
+                    .map(|bucket| bucket.load(Ordering::Relaxed))
+                    .collect(),
+                sum: time::Duration::from_micros(self.input_time.load(Ordering::Relaxed)),
+            },
+            errors: self.errors.load(Ordering::Relaxed),
+            departures: self.departures.load(Ordering::Relaxed),
+            placed: self.placed.load(Ordering::Relaxed),
+            rebalanced: self.rebalanced.load(Ordering::Relaxed),
+            routed: self.routed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Monitor {
+    pub(crate) fn new(metrics: sync::Arc<Metrics>, local_tuples: LocalTuples) -> Monitor {
+        Monitor {
+            metrics,
+            local_tuples,
+        }
+    }
+
+    pub fn stats(&self) -> Option<Stats> {
+        let local_tuples = match self.local_tuples.lock() {
+            Ok(guard) => guard.len(),
+            Err(_) => return None,
+        };
+
+        Some(self.metrics.snapshot(local_tuples))
+    }
+}
+
+impl ServerMetrics {
+    pub fn joined(&self) {
+        self.members.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn left(&self) {
+        self.members.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, tuples: Option<usize>) -> ServerStats {
+        ServerStats {
+            members: self.members.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            tuples,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    pub fn mean(&self) -> Option<time::Duration> {
+        match self.count() {
+            0 => None,
+            count => {
+                let nanos = self.sum.as_nanos() / u128::from(count);
+                Some(time::Duration::from_nanos(
+                    u64::try_from(nanos).unwrap_or(u64::MAX),
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "local tuples        {}", self.local_tuples)?;
+        writeln!(f, "received messages   {}", self.received)?;
+        writeln!(f, "forwarded messages  {}", self.forwarded)?;
+        writeln!(f, "satisfied locally   {}", self.satisfied_locally)?;
+        writeln!(f, "satisfied remotely  {}", self.satisfied_remotely)?;
+        writeln!(f, "timeouts            {}", self.timeouts)?;
+        writeln!(f, "errors              {}", self.errors)?;
+        writeln!(f, "nodes left the ring {}", self.departures)?;
+        writeln!(f, "placed elsewhere    {}", self.placed)?;
+        writeln!(f, "rebalanced          {}", self.rebalanced)?;
+        writeln!(f, "routed to owners    {}", self.routed)?;
+        write!(f, "{}", self.input_latency)
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mean() {
+            Some(mean) => write!(f, "in latency          {} (mean {mean:.3?})", self.count())?,
+            None => write!(f, "in latency          0")?,
+        }
+        for (i, count) in self.buckets.iter().enumerate() {
+            match LATENCY_BOUNDS.get(i) {
+                Some(bound) => write!(f, "\n  <= {:<15}{count}", format!("{bound:?}"))?,
+                None => write!(
+                    f,
+                    "\n  >  {:<15}{count}",
+                    format!("{:?}", LATENCY_BOUNDS[i - 1])
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_latency() {
+        let metrics = Metrics::default();
+        metrics.input_took(time::Duration::from_micros(500));
+        metrics.input_took(time::Duration::from_millis(5));
+        metrics.input_took(time::Duration::from_secs(60));
+        metrics.timed_out();
+
+        let stats = metrics.snapshot(2);
+        assert_eq!(stats.local_tuples, 2);
+        assert_eq!(stats.timeouts, 1);
+        assert_eq!(stats.input_latency.buckets, vec![1, 1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(stats.input_latency.count(), 3);
+        assert_eq!(
+            stats.input_latency.mean(),
+            Some(time::Duration::from_nanos(20_001_833_333))
+        );
+    }
+
+    #[test]
+    fn mean_of_many_inputs() {
+        let histogram = Histogram {
+            buckets: vec![1 << 32, 1 << 32],
+            sum: time::Duration::from_secs(1 << 34),
+        };
+        assert_eq!(histogram.mean(), Some(time::Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,416 @@
+use std::{io, path, sync};
+
+use rustls::{
+    client::WebPkiServerVerifier,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName},
+    server::WebPkiClientVerifier,
+    ClientConfig, ClientConnection, Connection, RootCertStore, ServerConfig, ServerConnection,
+};
+
+use crate::transport::{Address, Stream};
+
+const RECORD_SIZE: usize = 16 * 1024;
+#[cfg(unix)]
+const UNIX_SERVER_NAME: &str = "localhost";
+const INCOMPLETE_TLS_FLAGS: &str = "TLS needs all of --cert, --key and --ca!";
+
+// Both sides of every connection present a certificate signed by the trusted CA, so a node
+// without one can neither join the ring nor talk to the server.
+#[derive(Clone)]
+pub struct TlsConfig {
+    client: sync::Arc<ClientConfig>,
+    server: sync::Arc<ServerConfig>,
+    identity: Option<String>,
+}
+
+// Records are encrypted under the connection lock but written to the socket after it is
+// released, so a write blocked on a full socket does not keep the reading half from taking
+// records in. `sending` keeps the writes in the order the records were encrypted in.
+pub struct TlsStream {
+    connection: sync::Arc<sync::Mutex<Connection>>,
+    sending: sync::Arc<sync::Mutex<()>>,
+    socket: Box<Stream>,
+    peer: Option<String>,
+}
+
+fn lock<T>(mutex: &sync::Mutex<T>) -> io::Result<sync::MutexGuard<'_, T>> {
+    mutex.lock().map_err(|e| io::Error::other(e.to_string()))
+}
+
+// The first DNS name a certificate is issued for names whoever presents it, which is what
+// access policies refer to.
+fn identity(certs: &[CertificateDer]) -> Option<String> {
+    let cert = webpki::EndEntityCert::try_from(certs.first()?).ok()?;
+    let name = cert.valid_dns_names().next();
+    name.map(String::from)
+}
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl TlsConfig {
+    pub fn new(
+        certs: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+        ca: Vec<CertificateDer<'static>>,
+    ) -> io::Result<TlsConfig> {
+        let identity = identity(&certs);
+        let mut roots = RootCertStore::empty();
+        for cert in ca {
+            roots.add(cert).map_err(invalid_data)?;
+        }
+        let roots = sync::Arc::new(roots);
+        let provider = sync::Arc::new(rustls::crypto::ring::default_provider());
+
+        let client_verifier =
+            WebPkiClientVerifier::builder_with_provider(roots.clone(), provider.clone())
+                .build()
+                .map_err(invalid_data)?;
+        let mut server = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(certs.clone(), key.clone_key())
+            .map_err(invalid_data)?;
+        // Connections are never resumed, and unread tickets make short-lived connections like
+        // the registration end in a reset that can discard the peer's last message.
+        server.send_tls13_tickets = 0;
+
+        let server_verifier = WebPkiServerVerifier::builder_with_provider(roots, provider.clone())
+            .build()
+            .map_err(invalid_data)?;
+        let client = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?
+            .with_webpki_verifier(server_verifier)
+            .with_client_auth_cert(certs, key)
+            .map_err(invalid_data)?;
+
+        Ok(TlsConfig {
+            client: sync::Arc::new(client),
+            server: sync::Arc::new(server),
+            identity,
+        })
+    }
+
+    // The identity the peers of this node authenticate it as.
+    pub fn identity(&self) -> Option<&str> {
+        self.identity.as_deref()
+    }
+
+    pub fn load(cert: &path::Path, key: &path::Path, ca: &path::Path) -> io::Result<TlsConfig> {
+        let certs = CertificateDer::pem_file_iter(cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(invalid_data)?;
+        let key = PrivateKeyDer::from_pem_file(key).map_err(invalid_data)?;
+        let ca = CertificateDer::pem_file_iter(ca)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(invalid_data)?;
+
+        TlsConfig::new(certs, key, ca)
+    }
+
+    // Builds the configuration from the `--cert`, `--key` and `--ca` command line flags, which
+    // have to be given together.
+    pub fn from_flags(
+        cert: Option<String>,
+        key: Option<String>,
+        ca: Option<String>,
+    ) -> Result<Option<TlsConfig>, String> {
+        match (cert, key, ca) {
+            (None, None, None) => Ok(None),
+            (Some(cert), Some(key), Some(ca)) => {
+                let (cert, key, ca) = (
+                    path::Path::new(&cert),
+                    path::Path::new(&key),
+                    path::Path::new(&ca),
+                );
+                match TlsConfig::load(cert, key, ca) {
+                    Ok(config) => Ok(Some(config)),
+                    Err(e) => Err(format!("Failed to load TLS configuration! {e}")),
+                }
+            }
+            _ => Err(String::from(INCOMPLETE_TLS_FLAGS)),
+        }
+    }
+
+    pub fn accept(&self, socket: Stream) -> io::Result<Stream> {
+        let connection = ServerConnection::new(self.server.clone()).map_err(invalid_data)?;
+        TlsStream::handshake(connection.into(), socket)
+    }
+
+    pub fn connect(&self, address: &Address, socket: Stream) -> io::Result<Stream> {
+        let name = match address {
+            Address::Tcp(addr) => ServerName::IpAddress(addr.ip().into()),
+            #[cfg(unix)]
+            Address::Unix(_) => ServerName::try_from(UNIX_SERVER_NAME).map_err(invalid_data)?,
+        };
+        let connection = ClientConnection::new(self.client.clone(), name).map_err(invalid_data)?;
+        TlsStream::handshake(connection.into(), socket)
+    }
+}
+
+// Optional TLS on top of a plain connection; `None` leaves the stream untouched.
+pub fn accept(tls: Option<&TlsConfig>, socket: Stream) -> io::Result<Stream> {
+    match tls {
+        Some(tls) => tls.accept(socket),
+        None => Ok(socket),
+    }
+}
+
+pub fn connect(tls: Option<&TlsConfig>, address: &Address) -> io::Result<Stream> {
+    let socket = Stream::connect(address)?;
+    match tls {
+        Some(tls) => tls.connect(address, socket),
+        None => Ok(socket),
+    }
+}
+
+impl TlsStream {
+    fn handshake(mut connection: Connection, mut socket: Stream) -> io::Result<Stream> {
+        while connection.is_handshaking() {
+            connection.complete_io(&mut socket)?;
+        }
+
+        let peer = connection.peer_certificates().and_then(identity);
+        Ok(Stream::Tls(TlsStream {
+            connection: sync::Arc::new(sync::Mutex::new(connection)),
+            sending: Default::default(),
+            socket: Box::new(socket),
+            peer,
+        }))
+    }
+
+    // The reading and the writing half of a connection live in different threads, so they
+    // share the TLS state but use their own handle to the socket.
+    pub fn try_clone(&self) -> io::Result<TlsStream> {
+        Ok(TlsStream {
+            connection: self.connection.clone(),
+            sending: self.sending.clone(),
+            socket: Box::new(self.socket.try_clone()?),
+            peer: self.peer.clone(),
+        })
+    }
+
+    // The reading half does not wait for a write in progress: the writer checks for records
+    // queued in the meantime once it is done and sends them as well.
+    fn send_records(&mut self, wait: bool) -> io::Result<()> {
+        let sending = self.sending.clone();
+        loop {
+            let guard = match sending.try_lock() {
+                Ok(guard) => guard,
+                Err(sync::TryLockError::WouldBlock) if wait => lock(&sending)?,
+                Err(sync::TryLockError::WouldBlock) => return Ok(()),
+                Err(sync::TryLockError::Poisoned(e)) => {
+                    return Err(io::Error::other(e.to_string()))
+                }
+            };
+            loop {
+                let mut records = vec![];
+                {
+                    let mut connection = lock(&self.connection)?;
+                    while connection.wants_write() {
+                        connection.write_tls(&mut records)?;
+                    }
+                }
+                if records.is_empty() {
+                    break;
+                }
+                io::Write::write_all(&mut self.socket, &records)?;
+            }
+            drop(guard);
+
+            if !lock(&self.connection)?.wants_write() {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn socket(&self) -> &Stream {
+        &self.socket
+    }
+
+    // Named after the certificate the peer authenticated with.
+    pub fn peer_identity(&self) -> Option<&str> {
+        self.peer.as_deref()
+    }
+}
+
+impl io::Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match lock(&self.connection)?.reader().read(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                result => return result,
+            }
+
+            let mut records = [0u8; RECORD_SIZE];
+            let len = self.socket.read(&mut records)?;
+
+            {
+                let mut connection = lock(&self.connection)?;
+                let mut records = &records[..len];
+                loop {
+                    connection.read_tls(&mut records)?;
+                    connection.process_new_packets().map_err(invalid_data)?;
+                    if records.is_empty() {
+                        break;
+                    }
+                }
+            }
+            self.send_records(false)?;
+        }
+    }
+}
+
+impl io::Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = lock(&self.connection)?.writer().write(buf)?;
+        self.send_records(true)?;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        lock(&self.connection)?.writer().flush()?;
+        self.send_records(true)?;
+
+        self.socket.flush()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        io::{Read, Write},
+        net, thread,
+    };
+
+    use super::*;
+    use crate::transport::Listener;
+
+    pub struct Authority {
+        issuer: rcgen::CertifiedKey,
+    }
+
+    impl Authority {
+        pub fn generate() -> Authority {
+            let mut params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+            params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            let cert = params.self_signed(&key_pair).unwrap();
+
+            Authority {
+                issuer: rcgen::CertifiedKey { cert, key_pair },
+            }
+        }
+
+        pub fn config(&self) -> TlsConfig {
+            self.config_for("localhost")
+        }
+
+        // Certifies `name` as well as the local addresses the tests connect to.
+        pub fn config_for(&self, name: &str) -> TlsConfig {
+            let params = rcgen::CertificateParams::new(vec![
+                String::from(name),
+                String::from("localhost"),
+                String::from("127.0.0.1"),
+            ])
+            .unwrap();
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            let cert = params
+                .signed_by(&key_pair, &self.issuer.cert, &self.issuer.key_pair)
+                .unwrap();
+
+            TlsConfig::new(
+                vec![cert.der().clone()],
+                PrivateKeyDer::try_from(key_pair.serialize_der()).unwrap(),
+                vec![self.issuer.cert.der().clone()],
+            )
+            .unwrap()
+        }
+    }
+
+    fn local() -> Address {
+        Address::Tcp(net::SocketAddr::from(([127, 0, 0, 1], 0)))
+    }
+
+    #[test]
+    fn mutual_authentication() {
+        let authority = Authority::generate();
+        let (server, client) = (authority.config(), authority.config_for("worker"));
+        assert_eq!(client.identity(), Some("worker"));
+
+        let listener = Listener::bind(&local()).unwrap();
+        let address = listener.local_address().unwrap();
+        let handle = thread::spawn(move || {
+            let mut stream = server.accept(listener.accept().unwrap()).unwrap();
+            assert_eq!(stream.peer_identity(), Some("worker"));
+            let mut buf = [0; 5];
+            stream.read_exact(&mut buf).unwrap();
+            stream.write_all(&buf).unwrap();
+        });
+
+        let stream = connect(Some(&client), &address).unwrap();
+        assert_eq!(stream.peer_identity(), Some("localhost"));
+        let (mut input, mut output) = (stream.try_clone().unwrap(), stream);
+        output.write_all(b"hello").unwrap();
+        let mut buf = [0; 5];
+        input.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+        handle.join().unwrap();
+    }
+
+    // Both ends write more than the sockets buffer while their reading halves take it in.
+    #[test]
+    fn write_while_reading() {
+        const SIZE: usize = 16 * 1024 * 1024;
+        let authority = Authority::generate();
+        let (server, client) = (authority.config(), authority.config());
+
+        let listener = Listener::bind(&local()).unwrap();
+        let address = listener.local_address().unwrap();
+        let accepted = thread::spawn(move || server.accept(listener.accept().unwrap()).unwrap());
+        let streams = [
+            connect(Some(&client), &address).unwrap(),
+            accepted.join().unwrap(),
+        ];
+
+        let readers: Vec<_> = streams
+            .iter()
+            .map(|stream| {
+                let mut input = stream.try_clone().unwrap();
+                thread::spawn(move || {
+                    let mut buf = vec![0; SIZE];
+                    input.read_exact(&mut buf).unwrap();
+                    buf.iter().all(|byte| *byte == 7)
+                })
+            })
+            .collect();
+        let writers: Vec<_> = streams
+            .into_iter()
+            .map(|mut output| thread::spawn(move || output.write_all(&vec![7; SIZE]).unwrap()))
+            .collect();
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        for reader in readers {
+            assert!(reader.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn reject_untrusted_peer() {
+        let server = Authority::generate().config();
+        let intruder = Authority::generate().config();
+
+        let listener = Listener::bind(&local()).unwrap();
+        let address = listener.local_address().unwrap();
+        let handle = thread::spawn(move || server.accept(listener.accept().unwrap()).is_err());
+
+        assert!(connect(Some(&intruder), &address).is_err());
+        assert!(handle.join().unwrap());
+    }
+}
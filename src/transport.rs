@@ -0,0 +1,448 @@
+#[cfg(unix)]
+use std::{
+    env, fs,
+    os::unix::{ffi::OsStrExt, net as unix},
+    path, process,
+};
+use std::{fmt, io, net, str, time};
+
+use crate::{tls::TlsStream, tuple::Serializable, utils::*};
+
+const INVALID_TRANSPORT: &str = "Transport needs to be either 'tcp' or 'unix'!";
+const UNIX_PREFIX: &str = "unix:";
+#[cfg(unix)]
+const ANONYMOUS_SOCKET: &str = "Unix socket is not bound to a path and has no address!";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transport {
+    Tcp,
+    #[cfg(unix)]
+    Unix,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Address {
+    Tcp(net::SocketAddr),
+    #[cfg(unix)]
+    Unix(path::PathBuf),
+}
+
+pub enum Listener {
+    Tcp(net::TcpListener),
+    #[cfg(unix)]
+    Unix(unix::UnixListener, path::PathBuf),
+}
+
+pub enum Stream {
+    Tcp(net::TcpStream),
+    #[cfg(unix)]
+    Unix(unix::UnixStream),
+    Tls(TlsStream),
+}
+
+impl str::FromStr for Transport {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "tcp" => Ok(Transport::Tcp),
+            #[cfg(unix)]
+            "unix" => Ok(Transport::Unix),
+            _ => Err(INVALID_TRANSPORT),
+        }
+    }
+}
+
+impl Transport {
+    pub fn server_address(&self) -> Address {
+        match self {
+            Transport::Tcp => Address::Tcp(net::SocketAddr::new(
+                net::Ipv4Addr::LOCALHOST.into(),
+                SERVER_PORT,
+            )),
+            #[cfg(unix)]
+            Transport::Unix => Address::Unix(env::temp_dir().join("linda.sock")),
+        }
+    }
+
+    // Address for a listener that only needs to be reachable by the other nodes on this host.
+    pub fn local_address(&self) -> Address {
+        match self {
+            Transport::Tcp => {
+                Address::Tcp(net::SocketAddr::new(net::Ipv4Addr::LOCALHOST.into(), 0))
+            }
+            #[cfg(unix)]
+            Transport::Unix => {
+                Address::Unix(env::temp_dir().join(format!("linda-client-{}.sock", process::id())))
+            }
+        }
+    }
+
+    // Address for the endpoint a partitioned node serves the tuples it owns at.
+    pub fn partition_address(&self) -> Address {
+        match self {
+            Transport::Tcp => self.local_address(),
+            #[cfg(unix)]
+            Transport::Unix => Address::Unix(
+                env::temp_dir().join(format!("linda-partition-{}.sock", process::id())),
+            ),
+        }
+    }
+
+    // Interprets a server address given on the command line; a bare IP address
+    // means the default server port.
+    pub fn parse_address(&self, s: &str) -> Result<Address, String> {
+        match self {
+            Transport::Tcp => match s.parse::<net::IpAddr>() {
+                Ok(ip) => Ok(Address::Tcp(net::SocketAddr::new(ip, SERVER_PORT))),
+                Err(_) => match s.parse() {
+                    Ok(addr) => Ok(Address::Tcp(addr)),
+                    Err(e) => Err(format!("Incorrect server address! {e}")),
+                },
+            },
+            #[cfg(unix)]
+            Transport::Unix => Ok(Address::Unix(path::PathBuf::from(
+                s.strip_prefix(UNIX_PREFIX).unwrap_or(s),
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Tcp(addr) => write!(f, "{addr}"),
+            #[cfg(unix)]
+            Address::Unix(path) => write!(f, "{UNIX_PREFIX}{}", path.display()),
+        }
+    }
+}
+
+impl str::FromStr for Address {
+    type Err = net::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(UNIX_PREFIX) {
+            #[cfg(unix)]
+            Some(path) => Ok(Address::Unix(path::PathBuf::from(path))),
+            _ => Ok(Address::Tcp(s.parse()?)),
+        }
+    }
+}
+
+impl From<net::SocketAddr> for Address {
+    fn from(addr: net::SocketAddr) -> Self {
+        Address::Tcp(addr)
+    }
+}
+
+impl Serializable for Address {
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Address::Tcp(addr) => {
+                let mut buffer = match addr {
+                    net::SocketAddr::V4(addr) => {
+                        let mut buffer = 4u8.to_le_bytes().to_vec();
+                        buffer.append(&mut addr.ip().octets().to_vec());
+                        buffer
+                    }
+                    net::SocketAddr::V6(addr) => {
+                        let mut buffer = 6u8.to_le_bytes().to_vec();
+                        buffer.append(&mut addr.ip().octets().to_vec());
+                        buffer
+                    }
+                };
+                buffer.append(&mut addr.port().to_le_bytes().to_vec());
+                buffer
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                let bytes = path.as_os_str().as_bytes();
+                let mut buffer = 1u8.to_le_bytes().to_vec();
+                buffer.append(&mut (bytes.len() as u16).to_le_bytes().to_vec());
+                buffer.extend_from_slice(bytes);
+                buffer
+            }
+        }
+    }
+
+    fn from_bytes(bytes: &mut &[u8]) -> Option<Address> {
+        let ip = match read_le_u8(bytes)? {
+            4 => {
+                let buffer: [u8; IPV4_ADDR_LENGTH] =
+                    bytes.get(..IPV4_ADDR_LENGTH)?.try_into().ok()?;
+                *bytes = &bytes[IPV4_ADDR_LENGTH..];
+                net::IpAddr::V4(net::Ipv4Addr::from(buffer))
+            }
+            6 => {
+                let buffer: [u8; IPV6_ADDR_LENGTH] =
+                    bytes.get(..IPV6_ADDR_LENGTH)?.try_into().ok()?;
+                *bytes = &bytes[IPV6_ADDR_LENGTH..];
+                net::IpAddr::V6(net::Ipv6Addr::from(buffer))
+            }
+            #[cfg(unix)]
+            1 => {
+                let len = read_le_u16(bytes)? as usize;
+                let path = std::ffi::OsStr::from_bytes(bytes.get(..len)?);
+                let address = Address::Unix(path::PathBuf::from(path));
+                *bytes = &bytes[len..];
+                return Some(address);
+            }
+            _ => return None,
+        };
+        let port = read_le_u16(bytes)?;
+
+        Some(Address::Tcp(net::SocketAddr::new(ip, port)))
+    }
+}
+
+impl Listener {
+    pub fn bind(address: &Address) -> io::Result<Listener> {
+        match address {
+            Address::Tcp(addr) => Ok(Listener::Tcp(net::TcpListener::bind(addr)?)),
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                // A socket left behind by a node that did not shut down cleanly refuses
+                // connections and can be replaced.
+                if let Err(e) = unix::UnixStream::connect(path) {
+                    if e.kind() == io::ErrorKind::ConnectionRefused {
+                        fs::remove_file(path)?;
+                    }
+                }
+                Ok(Listener::Unix(
+                    unix::UnixListener::bind(path)?,
+                    path.clone(),
+                ))
+            }
+        }
+    }
+
+    pub fn accept(&self) -> io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => Ok(Stream::Tcp(listener.accept()?.0)),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => Ok(Stream::Unix(listener.accept()?.0)),
+        }
+    }
+
+    pub fn local_address(&self) -> io::Result<Address> {
+        match self {
+            Listener::Tcp(listener) => Ok(Address::Tcp(listener.local_addr()?)),
+            #[cfg(unix)]
+            Listener::Unix(_, path) => Ok(Address::Unix(path.clone())),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Stream {
+    pub fn connect(address: &Address) -> io::Result<Stream> {
+        match address {
+            Address::Tcp(addr) => Ok(Stream::Tcp(net::TcpStream::connect(addr)?)),
+            #[cfg(unix)]
+            Address::Unix(path) => Ok(Stream::Unix(unix::UnixStream::connect(path)?)),
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Stream> {
+        match self {
+            Stream::Tcp(stream) => Ok(Stream::Tcp(stream.try_clone()?)),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Ok(Stream::Unix(stream.try_clone()?)),
+            Stream::Tls(stream) => Ok(Stream::Tls(stream.try_clone()?)),
+        }
+    }
+
+    // Fails for the unnamed end of a Unix connection, which every connecting client has.
+    pub fn local_address(&self) -> io::Result<Address> {
+        match self {
+            Stream::Tcp(stream) => Ok(Address::Tcp(stream.local_addr()?)),
+            #[cfg(unix)]
+            Stream::Unix(stream) => unix_address(stream.local_addr()?),
+            Stream::Tls(stream) => stream.socket().local_address(),
+        }
+    }
+
+    // Bounds every read and write on the stream; `None` lets them block again.
+    pub fn set_timeout(&self, timeout: Option<time::Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => {
+                stream.set_read_timeout(timeout)?;
+                stream.set_write_timeout(timeout)
+            }
+            #[cfg(unix)]
+            Stream::Unix(stream) => {
+                stream.set_read_timeout(timeout)?;
+                stream.set_write_timeout(timeout)
+            }
+            Stream::Tls(stream) => stream.socket().set_timeout(timeout),
+        }
+    }
+
+    // The address a client is known by on a connection it opened. The connecting end of a
+    // Unix connection is unnamed, so the client is named after the path it would listen at.
+    pub fn client_address(&self) -> io::Result<Address> {
+        match self {
+            Stream::Tcp(_) => self.local_address(),
+            #[cfg(unix)]
+            Stream::Unix(_) => Ok(Transport::Unix.local_address()),
+            Stream::Tls(stream) => stream.socket().client_address(),
+        }
+    }
+
+    pub fn peer_address(&self) -> io::Result<Address> {
+        match self {
+            Stream::Tcp(stream) => Ok(Address::Tcp(stream.peer_addr()?)),
+            #[cfg(unix)]
+            Stream::Unix(stream) => unix_address(stream.peer_addr()?),
+            Stream::Tls(stream) => stream.socket().peer_address(),
+        }
+    }
+
+    // Only TLS authenticates the peer; plain connections have no identity to go by.
+    pub fn peer_identity(&self) -> Option<&str> {
+        match self {
+            Stream::Tls(stream) => stream.peer_identity(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn unix_address(address: unix::SocketAddr) -> io::Result<Address> {
+    match address.as_pathname() {
+        Some(path) => Ok(Address::Unix(path.to_path_buf())),
+        None => Err(io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            ANONYMOUS_SOCKET,
+        )),
+    }
+}
+
+impl io::Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl io::Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_addresses() {
+        assert_eq!("udp".parse::<Transport>(), Err(INVALID_TRANSPORT));
+
+        let tcp = Transport::Tcp.parse_address("127.0.0.1").unwrap();
+        assert_eq!(tcp.to_string(), format!("127.0.0.1:{SERVER_PORT}"));
+        assert_eq!(
+            Transport::Tcp.parse_address("[::1]:4000").unwrap(),
+            "[::1]:4000".parse().unwrap()
+        );
+        assert!(Transport::Tcp.parse_address("localhost").is_err());
+    }
+
+    #[test]
+    fn peer_addresses() {
+        let listener = Listener::bind(&Transport::Tcp.local_address()).unwrap();
+        let client = Stream::connect(&listener.local_address().unwrap()).unwrap();
+        let server = listener.accept().unwrap();
+        assert_eq!(
+            server.peer_address().unwrap(),
+            client.local_address().unwrap()
+        );
+    }
+
+    #[test]
+    fn read_timeout() {
+        let listener = Listener::bind(&Transport::Tcp.local_address()).unwrap();
+        let _client = Stream::connect(&listener.local_address().unwrap()).unwrap();
+        let mut server = listener.accept().unwrap();
+        server
+            .set_timeout(Some(time::Duration::from_millis(10)))
+            .unwrap();
+
+        let e = io::Read::read(&mut server, &mut [0; 1]).unwrap_err();
+        assert!(matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parse_unix_addresses() {
+        assert_eq!("unix".parse(), Ok(Transport::Unix));
+        let unix = Transport::Unix.parse_address("/tmp/linda.sock").unwrap();
+        assert_eq!(unix.to_string(), "unix:/tmp/linda.sock");
+        assert_eq!(unix.to_string().parse(), Ok(unix));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_round_trip() {
+        let address = Address::Unix(
+            env::temp_dir().join(format!("linda-transport-test-{}.sock", process::id())),
+        );
+        let listener = Listener::bind(&address).unwrap();
+        assert_eq!(listener.local_address().unwrap(), address);
+
+        let mut client = Stream::connect(&address).unwrap();
+        let mut server = listener.accept().unwrap();
+        io::Write::write_all(&mut client, b"ping").unwrap();
+        let mut buf = [0; 4];
+        io::Read::read_exact(&mut server, &mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+        // The connecting end is unnamed.
+        assert_eq!(
+            server.peer_address().unwrap_err().kind(),
+            io::ErrorKind::AddrNotAvailable
+        );
+        assert_eq!(client.peer_address().unwrap(), address);
+
+        assert!(Listener::bind(&address).is_err());
+        drop(listener);
+        let Address::Unix(path) = &address else {
+            unreachable!()
+        };
+        assert!(!path.exists());
+
+        // Stale sockets are replaced.
+        drop(unix::UnixListener::bind(path).unwrap());
+        assert!(path.exists());
+        drop(Listener::bind(&address).unwrap());
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops;
+use std::{fmt, ops};
 
 pub trait Serializable {
     fn to_bytes(&self) -> Vec<u8>;
@@ -70,6 +70,40 @@ impl Value {
             || self.is_float() && other.is_float()
             || self.is_string() && other.is_string()
     }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(Some(i)) => write!(f, "{i}"),
+            Value::Float(Some(fl)) => write!(f, "{fl:?}"),
+            Value::String(Some(s)) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' | '\\' => write!(f, "\\{c}")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        '\0' => write!(f, "\\0")?,
+                        c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            // Written like a template field matching any value of the type.
+            _ => write!(f, "{}: *", self.type_name()),
+        }
+    }
 }
 
 impl Serializable for Value {
@@ -92,7 +126,7 @@ impl Serializable for Value {
             },
             Value::String(opt) => match opt {
                 Some(s) => {
-                    result.append(&mut (s.as_bytes().len() as i32).to_le_bytes().to_vec());
+                    result.append(&mut (s.len() as i32).to_le_bytes().to_vec());
                     result.append(&mut s.as_bytes().to_vec());
                 }
                 None => result.append(&mut EMPTY_STRING.to_le_bytes().to_vec()),
@@ -120,6 +154,22 @@ impl Serializable for Value {
     }
 }
 
+impl fmt::Display for ComparisonOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Self::EQ => "==",
+            Self::NEQ => "!=",
+            Self::GE => ">=",
+            Self::GT => ">",
+            Self::LE => "<=",
+            Self::LT => "<",
+            Self::ANY => "*",
+        };
+
+        write!(f, "{op}")
+    }
+}
+
 impl Serializable for ComparisonOperator {
     fn to_bytes(&self) -> Vec<u8> {
         let value = match self {
@@ -159,6 +209,12 @@ impl<T> Tuple<T> {
     }
 }
 
+impl<T> Default for Tuple<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> ops::Deref for Tuple<T> {
     type Target = Vec<T>;
 
@@ -173,6 +229,28 @@ impl<T> ops::DerefMut for Tuple<T> {
     }
 }
 
+impl<T> IntoIterator for Tuple<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Tuple<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for (i, element) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{element}")?;
+        }
+        write!(f, ")")
+    }
+}
+
 impl<T: Serializable> Serializable for Tuple<T> {
     fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = self.0.len().to_le_bytes().to_vec();
@@ -199,8 +277,16 @@ impl Request {
         Request { value, op }
     }
 
+    // The only value this field matches, if it is pinned down to one.
+    pub fn exact(&self) -> Option<&Value> {
+        match self.op {
+            ComparisonOperator::EQ => Some(&self.value),
+            _ => None,
+        }
+    }
+
     pub fn satisfies(&self, other: &Value) -> bool {
-        self.value.is_same_type(&other)
+        self.value.is_same_type(other)
             && match self.op {
                 ComparisonOperator::ANY => true,
                 ComparisonOperator::EQ => self.value == *other,
@@ -213,6 +299,16 @@ impl Request {
     }
 }
 
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.op {
+            ComparisonOperator::ANY => write!(f, "{}: *", self.value.type_name()),
+            ComparisonOperator::EQ => write!(f, "{}: {}", self.value.type_name(), self.value),
+            op => write!(f, "{}: {op} {}", self.value.type_name(), self.value),
+        }
+    }
+}
+
 impl Serializable for Request {
     fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = self.value.to_bytes();
@@ -257,6 +353,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn serialize_value() {
         check_value(Value::int(1));
         check_value(Value::float(3.14));
@@ -268,6 +365,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn serialize_request() {
         check_request(Request::new(Value::Int(None), ComparisonOperator::ANY));
         check_request(Request::new(Value::float(3.14), ComparisonOperator::EQ));
@@ -284,6 +382,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn serialize_tuple() {
         check_tuple(Tuple(vec![
             Value::int(1),
@@ -299,4 +398,39 @@ mod tests {
             Request::new(Value::String(None), ComparisonOperator::ANY),
         ]))
     }
+
+    #[test]
+    fn display_value() {
+        assert_eq!(Value::int(-1).to_string(), "-1");
+        assert_eq!(Value::float(2.0).to_string(), "2.0");
+        assert_eq!(
+            Value::string(String::from("a\"b\\c")).to_string(),
+            "\"a\\\"b\\\\c\""
+        );
+        assert_eq!(
+            Value::string(String::from("a\nb\tc\r\0\u{7f}\u{1b}ż")).to_string(),
+            "\"a\\nb\\tc\\r\\0\\u{7f}\\u{1b}ż\""
+        );
+        assert_eq!(Value::Float(None).to_string(), "float: *");
+    }
+
+    #[test]
+    fn display_request() {
+        assert_eq!(
+            Request::new(Value::int(3), ComparisonOperator::GE).to_string(),
+            "int: >= 3"
+        );
+        assert_eq!(
+            Request::new(Value::String(None), ComparisonOperator::ANY).to_string(),
+            "string: *"
+        );
+        assert_eq!(
+            Tuple(vec![
+                Request::new(Value::float(0.5), ComparisonOperator::EQ),
+                Request::new(Value::int(1), ComparisonOperator::NEQ),
+            ])
+            .to_string(),
+            "(float: 0.5, int: != 1)"
+        );
+    }
 }
@@ -0,0 +1,175 @@
+use crate::tuple::*;
+
+pub use linda_derive::{FromTuple, IntoTuple, Template};
+
+pub trait Field: Sized {
+    fn to_value(&self) -> Value;
+    fn from_value(value: Value) -> Option<Self>;
+    fn empty() -> Value;
+}
+
+pub trait IntoTuple {
+    fn to_tuple(&self) -> Tuple<Value>;
+}
+
+pub trait FromTuple: Sized {
+    fn from_tuple(tuple: Tuple<Value>) -> Option<Self>;
+}
+
+pub trait Template {
+    type Builder: Into<Tuple<Request>>;
+
+    fn template() -> Self::Builder;
+}
+
+impl Field for i32 {
+    fn to_value(&self) -> Value {
+        Value::int(*self)
+    }
+
+    fn from_value(value: Value) -> Option<i32> {
+        match value {
+            Value::Int(i) => i,
+            _ => None,
+        }
+    }
+
+    fn empty() -> Value {
+        Value::Int(None)
+    }
+}
+
+impl Field for f64 {
+    fn to_value(&self) -> Value {
+        Value::float(*self)
+    }
+
+    fn from_value(value: Value) -> Option<f64> {
+        match value {
+            Value::Float(f) => f,
+            _ => None,
+        }
+    }
+
+    fn empty() -> Value {
+        Value::Float(None)
+    }
+}
+
+impl Field for String {
+    fn to_value(&self) -> Value {
+        Value::string(self.clone())
+    }
+
+    fn from_value(value: Value) -> Option<String> {
+        match value {
+            Value::String(s) => s,
+            _ => None,
+        }
+    }
+
+    fn empty() -> Value {
+        Value::String(None)
+    }
+}
+
+impl<T: Field> Field for Option<T> {
+    fn to_value(&self) -> Value {
+        match self {
+            Some(val) => val.to_value(),
+            None => T::empty(),
+        }
+    }
+
+    fn from_value(value: Value) -> Option<Option<T>> {
+        if value.is_same_type(&T::empty()) {
+            Some(T::from_value(value))
+        } else {
+            None
+        }
+    }
+
+    fn empty() -> Value {
+        T::empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, IntoTuple, FromTuple, Template)]
+    struct Job {
+        id: i32,
+        name: String,
+        weight: f64,
+        owner: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, IntoTuple, FromTuple)]
+    struct Point(i32, i32);
+
+    fn job() -> Job {
+        Job {
+            id: 7,
+            name: String::from("build"),
+            weight: 0.5,
+            owner: None,
+        }
+    }
+
+    #[test]
+    fn struct_to_tuple() {
+        assert_eq!(
+            job().to_tuple(),
+            Tuple::from_vec(vec![
+                Value::int(7),
+                Value::string(String::from("build")),
+                Value::float(0.5),
+                Value::String(None),
+            ])
+        );
+        assert_eq!(
+            Point(1, 2).to_tuple(),
+            Tuple::from_vec(vec![Value::int(1), Value::int(2)])
+        );
+    }
+
+    #[test]
+    fn tuple_to_struct() {
+        assert_eq!(Job::from_tuple(job().to_tuple()), Some(job()));
+        assert_eq!(Point::from_tuple(Point(3, 4).to_tuple()), Some(Point(3, 4)));
+
+        assert_eq!(
+            Point::from_tuple(Tuple::from_vec(vec![Value::int(1)])),
+            None
+        );
+        assert_eq!(
+            Point::from_tuple(Tuple::from_vec(vec![Value::int(1), Value::float(2.0)])),
+            None
+        );
+        assert_eq!(
+            Point::from_tuple(Tuple::from_vec(vec![Value::int(1), Value::Int(None)])),
+            None
+        );
+    }
+
+    #[test]
+    fn template_builder() {
+        let template: Tuple<Request> = Job::template()
+            .id(ComparisonOperator::EQ, 7)
+            .name(ComparisonOperator::EQ, String::from("build"))
+            .into();
+
+        assert_eq!(
+            template,
+            Tuple::from_vec(vec![
+                Request::new(Value::int(7), ComparisonOperator::EQ),
+                Request::new(Value::string(String::from("build")), ComparisonOperator::EQ),
+                Request::new(Value::Float(None), ComparisonOperator::ANY),
+                Request::new(Value::String(None), ComparisonOperator::ANY),
+            ])
+        );
+        assert!(crate::satisfies(&template, &job().to_tuple()));
+    }
+}
@@ -1,12 +1,21 @@
 use std::{
     mem,
     net::{self},
-    process,
+    process, time,
 };
 
 use crate::tuple::{Request, Tuple, Value};
 
-pub type Timeout = usize;
+// The names the parser accepts for the commands, in the order `help` lists them.
+pub const COMMANDS: [&str; 12] = [
+    "out", "in", "rd", "inp", "rdp", "ls", "count", "dump", "load", "stats", "help", "exit",
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Timeout {
+    After(time::Duration),
+    Forever,
+}
 
 #[derive(PartialEq, Debug)]
 pub enum Command {
@@ -15,10 +24,34 @@ pub enum Command {
     Rd(Tuple<Request>, Timeout),
     Inp(Tuple<Request>),
     Rdp(Tuple<Request>),
-    Help,
+    Ls(Option<Tuple<Request>>),
+    Count(Option<Tuple<Request>>),
+    Dump(String),
+    Load(String),
+    Stats,
+    Help(Option<String>),
     Exit,
 }
 
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Out(_) => "out",
+            Command::In(..) => "in",
+            Command::Rd(..) => "rd",
+            Command::Inp(_) => "inp",
+            Command::Rdp(_) => "rdp",
+            Command::Ls(_) => "ls",
+            Command::Count(_) => "count",
+            Command::Dump(_) => "dump",
+            Command::Load(_) => "load",
+            Command::Stats => "stats",
+            Command::Help(_) => "help",
+            Command::Exit => "exit",
+        }
+    }
+}
+
 pub const SERVER_PORT: u16 = 1999;
 pub const IP_ADDR_LENGTH: usize = mem::size_of::<u8>();
 pub const IPV4_ADDR_LENGTH: usize = net::Ipv4Addr::LOCALHOST.octets().len();
@@ -30,32 +63,32 @@ pub fn error(message: &str) -> ! {
     process::exit(1)
 }
 
+fn read_le_bytes<const N: usize>(input: &mut &[u8]) -> Option<[u8; N]> {
+    let bytes = input.get(..N)?.try_into().ok()?;
+    *input = &input[N..];
+    Some(bytes)
+}
+
 pub fn read_le_u8(input: &mut &[u8]) -> Option<u8> {
-    let (byte, rest) = input.split_at(mem::size_of::<u8>());
-    *input = rest;
-    Some(u8::from_le_bytes(byte.try_into().ok()?))
+    Some(u8::from_le_bytes(read_le_bytes(input)?))
 }
 
 pub fn read_le_i32(input: &mut &[u8]) -> Option<i32> {
-    let (int_bytes, rest) = input.split_at(mem::size_of::<i32>());
-    *input = rest;
-    Some(i32::from_le_bytes(int_bytes.try_into().ok()?))
+    Some(i32::from_le_bytes(read_le_bytes(input)?))
 }
 
 pub fn read_le_u16(input: &mut &[u8]) -> Option<u16> {
-    let (int_bytes, rest) = input.split_at(mem::size_of::<u16>());
-    *input = rest;
-    Some(u16::from_le_bytes(int_bytes.try_into().ok()?))
+    Some(u16::from_le_bytes(read_le_bytes(input)?))
+}
+
+pub fn read_le_u64(input: &mut &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(read_le_bytes(input)?))
 }
 
 pub fn read_le_f64(input: &mut &[u8]) -> Option<f64> {
-    let (float_bytes, rest) = input.split_at(mem::size_of::<f64>());
-    *input = rest;
-    Some(f64::from_le_bytes(float_bytes.try_into().ok()?))
+    Some(f64::from_le_bytes(read_le_bytes(input)?))
 }
 
 pub fn read_le_usize(input: &mut &[u8]) -> Option<usize> {
-    let (usize_bytes, rest) = input.split_at(mem::size_of::<usize>());
-    *input = rest;
-    Some(usize::from_le_bytes(usize_bytes.try_into().ok()?))
+    Some(usize::from_le_bytes(read_le_bytes(input)?))
 }